@@ -0,0 +1,55 @@
+use std::env;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use solar_s3::error::{Result, SolarError};
+use solar_s3::server::http;
+use solar_s3::storage::credentials::Credentials;
+use solar_s3::storage::store::{Storage, Store};
+
+const USAGE: &str = "Usage: solar-s3-server <store-path> [--listen <address>] [--space <bytes>]";
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+	let mut args = env::args().skip(1);
+	let mut store_path: Option<String> = None;
+	let mut address: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+	let mut space: Option<u64> = None;
+
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--listen" => {
+				address = args.next()
+					.and_then(|value| value.parse().ok())
+					.ok_or_else(|| SolarError::InvalidInput(USAGE.to_string()))?;
+			}
+			"--space" => {
+				space = Some(args.next()
+					.and_then(|value| value.parse().ok())
+					.ok_or_else(|| SolarError::InvalidInput(USAGE.to_string()))?);
+			}
+			_ if store_path.is_none() => store_path = Some(arg),
+			_ => return Err(SolarError::InvalidInput(USAGE.to_string())),
+		}
+	}
+
+	let store_path = store_path.ok_or_else(|| SolarError::InvalidInput(USAGE.to_string()))?;
+	if !Path::new(&store_path).exists() {
+		let space = space.ok_or_else(|| SolarError::InvalidInput("The store doesn't exist, pass --space to create it".to_string()))?;
+		Store::create(&store_path, space, None).await?;
+	} else if Store::open(&store_path).await.is_err() {
+		let store = Store::restore(&store_path).await?;
+		println!("Restored storage.json: {} of {} bytes used", store.usage_space, store.available_space);
+	}
+
+	if Credentials::load(&store_path).await?.is_empty() {
+		let credential = Credentials::create(&store_path).await?;
+		println!("Created credentials for the store:");
+		println!("  access key: {}", credential.access_key);
+		println!("  secret key: {}", credential.secret_key);
+	}
+
+	println!("solar-s3 is listening on http://{address}");
+	http::run(&store_path, address).await?;
+	return Ok(());
+}
@@ -0,0 +1,208 @@
+use std::str::FromStr;
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+
+use crate::bucket::bucket::Bucket;
+use crate::bucket::bucket_db::BucketDB;
+use crate::bucket::fs_metadata::FsMetadata;
+use crate::error::{Result, SolarError};
+use crate::storage::users::User;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+	// Get, read and list the items.
+	Read,
+	// Add, change and remove the items as well.
+	Write,
+	// Grant access to the items as well. On the whole bucket, change its settings.
+	Admin,
+}
+
+impl Permission {
+	pub fn as_str(&self) -> &'static str {
+		return match self {
+			Permission::Read => "read",
+			Permission::Write => "write",
+			Permission::Admin => "admin",
+		};
+	}
+}
+
+impl FromStr for Permission {
+	type Err = SolarError;
+
+	fn from_str(value: &str) -> Result<Permission> {
+		return match value {
+			"read" => Ok(Permission::Read),
+			"write" => Ok(Permission::Write),
+			"admin" => Ok(Permission::Admin),
+			_ => Err(SolarError::InvalidInput(format!("Unknown permission {}", value))),
+		};
+	}
+}
+
+impl FromSql for Permission {
+	fn column_result(value: ValueRef<'_>) -> FromSqlResult<Permission> {
+		return value.as_str()?.parse().map_err(|_| FromSqlError::InvalidType);
+	}
+}
+
+/// `permission` on the items of the bucket whose path starts with `prefix`, the whole
+/// bucket for an empty prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+	pub user_uuid: String,
+	pub prefix: String,
+	pub permission: Permission,
+}
+
+// What the acting user may do in the bucket: everything, or what their grants allow.
+pub(crate) struct Access {
+	grants: Option<Vec<(String, Permission)>>,
+}
+
+impl Access {
+	fn permission(&self, path: &str) -> Option<Permission> {
+		return match &self.grants {
+			None => Some(Permission::Admin),
+			Some(grants) => grants.iter()
+				.filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+				.map(|(_, permission)| *permission)
+				.max(),
+		};
+	}
+
+	pub(crate) fn allows(&self, path: &str, permission: Permission) -> bool {
+		return self.permission(path).is_some_and(|granted| granted >= permission);
+	}
+
+	pub(crate) fn check(&self, path: &str, permission: Permission) -> Result<()> {
+		if !self.allows(path, permission) {
+			return Err(denied(path, permission));
+		}
+
+		return Ok(());
+	}
+
+	// Whether `path` shows in a listing: it can be read, or it is a directory on the
+	// way to a folder that can.
+	pub(crate) fn can_see(&self, path: &str) -> bool {
+		if self.allows(path, Permission::Read) {
+			return true;
+		}
+
+		let is_dir = path.is_empty() || path.ends_with('/');
+		return is_dir && self.grants.iter().flatten().any(|(prefix, _)| prefix.starts_with(path));
+	}
+
+	pub(crate) fn check_see(&self, path: &str) -> Result<()> {
+		if !self.can_see(path) {
+			return Err(denied(path, Permission::Read));
+		}
+
+		return Ok(());
+	}
+}
+
+/// A bucket opened without a user is used by the store itself and may do anything.
+/// With `set_user`, every method checks what the user may do first: a store admin
+/// and the owner of the bucket may do everything, anyone else what the grants of the
+/// bucket allow them on the path of the item. Listings leave out what the user
+/// can't read. Grants hold to paths, so they don't follow renames and moves.
+impl Bucket {
+	/// Sets the user who acts on the bucket, who is also the author of the changes.
+	pub fn set_user(&mut self, user: Option<&User>) {
+		self.author_uuid = user.map(|user| user.uuid.clone());
+		self.user = user.cloned();
+	}
+
+	pub async fn get_owner(&self) -> Result<Option<String>> {
+		return FsMetadata::new(&self.path).await?.get_owner().await;
+	}
+
+	pub async fn set_owner(&self, owner_uuid: &str) -> Result<()> {
+		self.authorize("", Permission::Admin).await?;
+		return FsMetadata::new(&self.path).await?.set_owner(owner_uuid).await;
+	}
+
+	/// Gives `user_uuid` `permission` on the items under `prefix`, an empty prefix or
+	/// the path of a directory, replacing the grant they had there.
+	pub async fn grant(&self, user_uuid: &str, prefix: &str, permission: Permission) -> Result<()> {
+		if !prefix.is_empty() && !prefix.ends_with('/') {
+			return Err(SolarError::InvalidInput("A grant prefix must be empty or end with '/'".to_string()));
+		}
+		self.authorize(prefix, Permission::Admin).await?;
+
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		BucketDB::set_grant(&Grant { user_uuid: user_uuid.to_string(), prefix: prefix.to_string(), permission }, &transaction).await?;
+		transaction.commit()?;
+
+		return Ok(());
+	}
+
+	pub async fn revoke(&self, user_uuid: &str, prefix: &str) -> Result<()> {
+		self.authorize(prefix, Permission::Admin).await?;
+
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		BucketDB::remove_grant(user_uuid, prefix, &transaction).await?;
+		transaction.commit()?;
+
+		return Ok(());
+	}
+
+	/// The grants of the bucket the user may change, ordered by prefix.
+	pub async fn get_grants(&self) -> Result<Vec<Grant>> {
+		let access = self.access().await?;
+		let connection = BucketDB::open(self.path.as_str()).await?;
+		let mut grants = BucketDB::get_grants(None, &connection).await?;
+		grants.retain(|grant| access.allows(&grant.prefix, Permission::Admin));
+
+		return Ok(grants);
+	}
+
+	/// What the user may do with `key`, or with the whole bucket without one.
+	pub async fn get_permission(&self, key: Option<&String>) -> Result<Option<Permission>> {
+		let path = match key {
+			None => "".to_string(),
+			Some(key) => {
+				let mut connection = BucketDB::open(self.path.as_str()).await?;
+				let transaction = connection.transaction()?;
+				let path = BucketDB::get_path(key, &transaction).await?;
+				transaction.commit()?;
+				path
+			},
+		};
+
+		return Ok(self.access().await?.permission(&path));
+	}
+
+	pub(crate) async fn access(&self) -> Result<Access> {
+		let user = match &self.user {
+			None => return Ok(Access { grants: None }),
+			Some(user) => user,
+		};
+		if user.is_admin || self.get_owner().await?.as_deref() == Some(user.uuid.as_str()) {
+			return Ok(Access { grants: None });
+		}
+
+		let connection = BucketDB::open(self.path.as_str()).await?;
+		let grants = BucketDB::get_grants(Some(&user.uuid), &connection).await?;
+		return Ok(Access { grants: Some(grants.into_iter().map(|grant| (grant.prefix, grant.permission)).collect()) });
+	}
+
+	// Fails with `AccessDenied` unless the user has `permission` on `path`, the bucket
+	// itself for an empty path.
+	pub(crate) async fn authorize(&self, path: &str, permission: Permission) -> Result<()> {
+		if self.user.is_none() {
+			return Ok(());
+		}
+
+		return self.access().await?.check(path, permission);
+	}
+}
+
+fn denied(path: &str, permission: Permission) -> SolarError {
+	return SolarError::AccessDenied(format!("No {} access to /{}", permission.as_str(), path));
+}
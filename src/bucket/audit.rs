@@ -0,0 +1,140 @@
+use std::str::FromStr;
+
+use chrono::Utc;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use rusqlite::Transaction;
+
+use crate::bucket::access::Permission;
+use crate::bucket::bucket::Bucket;
+use crate::bucket::bucket_db::BucketDB;
+use crate::error::{Result, SolarError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOperation {
+	Add,
+	Rename,
+	Move,
+	Copy,
+	Delete,
+	Restore,
+	Favorite,
+	Unfavorite,
+	Remove,
+}
+
+impl AuditOperation {
+	pub fn as_str(&self) -> &'static str {
+		return match self {
+			AuditOperation::Add => "add",
+			AuditOperation::Rename => "rename",
+			AuditOperation::Move => "move",
+			AuditOperation::Copy => "copy",
+			AuditOperation::Delete => "delete",
+			AuditOperation::Restore => "restore",
+			AuditOperation::Favorite => "favorite",
+			AuditOperation::Unfavorite => "unfavorite",
+			AuditOperation::Remove => "remove",
+		};
+	}
+}
+
+impl FromStr for AuditOperation {
+	type Err = SolarError;
+
+	fn from_str(value: &str) -> Result<AuditOperation> {
+		return match value {
+			"add" => Ok(AuditOperation::Add),
+			"rename" => Ok(AuditOperation::Rename),
+			"move" => Ok(AuditOperation::Move),
+			"copy" => Ok(AuditOperation::Copy),
+			"delete" => Ok(AuditOperation::Delete),
+			"restore" => Ok(AuditOperation::Restore),
+			"favorite" => Ok(AuditOperation::Favorite),
+			"unfavorite" => Ok(AuditOperation::Unfavorite),
+			"remove" => Ok(AuditOperation::Remove),
+			_ => Err(SolarError::InvalidInput(format!("Unknown audit operation {}", value))),
+		};
+	}
+}
+
+impl FromSql for AuditOperation {
+	fn column_result(value: ValueRef<'_>) -> FromSqlResult<AuditOperation> {
+		return value.as_str()?.parse().map_err(|_| FromSqlError::InvalidType);
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+	pub id: i64,
+	// The key and the path of the item after the operation.
+	pub key: String,
+	pub path: String,
+	pub operation: AuditOperation,
+	pub author_uuid: Option<String>,
+	pub create_at: i64,
+	pub comment: Option<String>,
+}
+
+/// Limits a log query to a time range, both ends included, and to the newest `limit`
+/// entries.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+	pub since: Option<i64>,
+	pub until: Option<i64>,
+	pub limit: Option<usize>,
+}
+
+/// With `logging` on for the store, every change to the items of a bucket is written
+/// to the `log_paths` table in the transaction of the change, with the `author_uuid`
+/// and the `comment` set on the bucket. The entries follow renames and moves, and
+/// stay after the item is removed.
+impl Bucket {
+	/// Sets who makes the next changes and why. The comment stays until it is set again.
+	pub fn set_author(&mut self, author_uuid: Option<&str>, comment: Option<&str>) {
+		self.author_uuid = author_uuid.map(str::to_string);
+		self.comment = comment.map(str::to_string);
+	}
+
+	/// The log of `key`, the newest entries first.
+	pub async fn get_log(&self, key: &String, filter: &AuditFilter) -> Result<Vec<AuditEntry>> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		let mut entries = BucketDB::get_logs(Some(key), filter, &transaction).await?;
+		transaction.commit()?;
+
+		// The key may be gone, so the access is checked against the entries.
+		let access = self.access().await?;
+		entries.retain(|entry| access.allows(&entry.path, Permission::Read));
+
+		return Ok(entries);
+	}
+
+	/// The log of every item of the bucket, the newest entries first.
+	pub async fn get_bucket_log(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		let mut entries = BucketDB::get_logs(None, filter, &transaction).await?;
+		transaction.commit()?;
+
+		let access = self.access().await?;
+		entries.retain(|entry| access.allows(&entry.path, Permission::Read));
+
+		return Ok(entries);
+	}
+
+	pub(crate) async fn log(&self, operation: AuditOperation, key: &str, path: &str, transaction: &Transaction<'_>) -> Result<()> {
+		if !self.store.logging {
+			return Ok(());
+		}
+
+		return BucketDB::add_log(&AuditEntry {
+			id: 0,
+			key: key.to_string(),
+			path: path.to_string(),
+			operation,
+			author_uuid: self.author_uuid.clone(),
+			create_at: Utc::now().timestamp(),
+			comment: self.comment.clone(),
+		}, transaction).await;
+	}
+}
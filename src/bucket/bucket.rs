@@ -1,13 +1,32 @@
 use std::hash::{Hash, Hasher};
-use std::io::{Error, ErrorKind, Result};
+use std::io::{self, SeekFrom};
 use std::ops::Add;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, ReadBuf, Take};
+use uuid::Uuid;
 
+use crate::bucket::access::Permission;
+use crate::bucket::audit::AuditOperation;
 use crate::bucket::bucket_db::{BucketDB, KeyPath};
+use crate::bucket::checksum::{ChecksumAlgorithm, ChecksumHasher, Checksums};
 use crate::bucket::fs_metadata::{FsItem, FsMetadata, PropertiesItem};
+use crate::bucket::fsck::data_size;
+use crate::bucket::journal::Intent;
+use crate::bucket::precondition::Preconditions;
+use crate::bucket::range::ByteRange;
+use crate::error::{Result, SolarError};
+use crate::storage::space::Space;
 use crate::storage::store::{Storage, Store};
+use crate::storage::users::User;
+
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+// How long deleted items stay in the trash when the bucket doesn't set it, in seconds.
+pub const DEFAULT_TRASH_RETENTION: u64 = 30 * 24 * 60 * 60;
+pub const MAX_TAGS: usize = 10;
 
 pub struct Bucket {
 	pub uuid: String,
@@ -15,6 +34,11 @@ pub struct Bucket {
 	pub store: Store,
 	pub available_space: u64,
 	pub usage_space: u64,
+	// Who makes the changes and why, for the audit log.
+	pub author_uuid: Option<String>,
+	pub comment: Option<String>,
+	// The user the bucket is used by, see `set_user`.
+	pub user: Option<User>,
 }
 
 pub struct GetOptions {
@@ -33,90 +57,391 @@ pub struct PathItem {
 	pub title: String,
 }
 
+// A file written by `stage_stream` that isn't in `files/` yet.
+pub(crate) struct StagedFile {
+	path: String,
+	temp_path: PathBuf,
+	size: u64,
+	checksums: Checksums,
+}
+
+pub struct ObjectReader {
+	pub item: FsItem,
+	pub size: u64,
+	pub start: u64,
+	pub length: u64,
+	reader: Take<fs::File>,
+}
+
+impl ObjectReader {
+	pub(crate) async fn open(item: FsItem, path: &Path, range: Option<&ByteRange>) -> Result<ObjectReader> {
+		// The size is taken from the opened handle so that a concurrent replace can't change it under us.
+		let mut file = fs::File::open(path).await?;
+		let size = file.metadata().await?.len();
+		let (start, length) = match range {
+			Some(range) => range.resolve(size)?,
+			None => (0, size),
+		};
+
+		file.seek(SeekFrom::Start(start)).await?;
+		return Ok(ObjectReader {
+			item,
+			size,
+			start,
+			length,
+			reader: file.take(length),
+		});
+	}
+}
+
+impl AsyncRead for ObjectReader {
+	fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+		return Pin::new(&mut self.reader).poll_read(cx, buf);
+	}
+}
+
 impl Bucket {
 	pub async fn create(store: Store, uuid: &str, bucket_space: u64) -> Result<Bucket> {
 		if store.usage_space + bucket_space > store.available_space {
-			return Err(Error::new(ErrorKind::StorageFull, "There is no free space to create the Bucket!"));
+			return Err(SolarError::QuotaExceeded("There is no free space to create the Bucket!".to_string()));
+		}
+		if bucket_space > Space::get_disc(Path::new(&store.store_path))?.free_space {
+			return Err(SolarError::QuotaExceeded("There is no free space on the disk to create the Bucket!".to_string()));
 		}
 
-		let path = Path::new(&store.store_path).join(&uuid);
+		let path = Path::new(&store.store_path).join(uuid);
 		fs::create_dir(&path).await?;
 		fs::create_dir(path.join("files")).await?;
 
 		let fs_metadata = FsMetadata::new(&path).await?;
-		fs_metadata.set_available_space(bucket_space).await;
+		fs_metadata.set_available_space(bucket_space).await?;
 
-		BucketDB::init(&path).await;
+		BucketDB::init(&path).await?;
 
 		return Ok(Bucket {
 			uuid: uuid.to_string(),
-			path: path.to_str().unwrap().to_string(),
+			path: path.to_string_lossy().to_string(),
 			store,
 			available_space: bucket_space,
 			usage_space: 0,
+			author_uuid: None,
+			comment: None,
+			user: None,
 		});
 	}
 
+	/// Opens the bucket, upgrading its database to the current schema and rolling back
+	/// the operations an earlier process left unfinished.
 	pub async fn open(store: Store, uuid: &str) -> Result<Bucket> {
-		let path = Path::new(&store.store_path).join(&uuid);
+		let path = Path::new(&store.store_path).join(uuid);
 		let fs_metadata = FsMetadata::new(&path).await?;
-		let space = fs_metadata.get_space().await.unwrap();
-		return Ok(Bucket {
+		BucketDB::migrate(&path).await?;
+		let space = fs_metadata.get_space().await?;
+		let mut bucket = Bucket {
 			uuid: uuid.to_string(),
-			path: path.to_str().unwrap().to_string(),
+			path: path.to_string_lossy().to_string(),
 			store,
 			available_space: space.available_space,
 			usage_space: space.usage_space,
-		});
+			author_uuid: None,
+			comment: None,
+			user: None,
+		};
+
+		bucket.recover_intents().await?;
+		return Ok(bucket);
+	}
+
+	/// Removes the bucket with its files from the store. Without `force` only an
+	/// empty bucket can be deleted.
+	pub async fn delete(self, force: bool) -> Result<()> {
+		self.authorize("", Permission::Admin).await?;
+		let files_path = Path::new(&self.path).join("files");
+		if !force && fs::read_dir(&files_path).await?.next_entry().await?.is_some() {
+			return Err(SolarError::BucketNotEmpty(self.uuid));
+		}
+
+		let size = data_size(Path::new(&self.path)).await?.stored_size;
+		self.store.unregister_bucket(&self.uuid).await?;
+		fs::remove_dir_all(&self.path).await?;
+
+		let mut store = self.store;
+		store.release_space(size).await?;
+
+		return Ok(());
+	}
+
+	/// Changes the quota of the bucket. It can't go below the current usage, nor
+	/// above what the store has left besides the data of this bucket.
+	pub async fn resize(&mut self, bucket_space: u64) -> Result<()> {
+		self.authorize("", Permission::Admin).await?;
+		let store = Store::open(&self.store.store_path).await?;
+		let space = FsMetadata::new(&self.path).await?.get_space().await?;
+
+		if bucket_space < space.usage_space {
+			return Err(SolarError::InvalidInput("The bucket already uses more than the new size!".to_string()));
+		}
+		if bucket_space > store.available_space.saturating_sub(store.usage_space) + space.usage_space {
+			return Err(SolarError::QuotaExceeded("There is no free space to resize the Bucket!".to_string()));
+		}
+
+		FsMetadata::new(&self.path).await?.set_available_space(bucket_space).await?;
+		self.store = store;
+		self.available_space = bucket_space;
+		self.usage_space = space.usage_space;
+
+		return Ok(());
 	}
 
 	pub async fn add(&mut self, key_value: &KeyValue) -> Result<String> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		return self.add_if(key_value, &Preconditions::default()).await;
+	}
+
+	/// Like `add`, but only when the current state of the key meets `preconditions`,
+	/// e.g. `if_none_match: Some("*")` to create the key only when it doesn't exist.
+	pub async fn add_if(&mut self, key_value: &KeyValue, preconditions: &Preconditions) -> Result<String> {
+		if let Some(buffer) = &key_value.value {
+			return self.add_stream_if(key_value.key.as_ref(), &key_value.name, &mut buffer.as_slice(), preconditions).await;
+		}
+
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
 		let path = match &key_value.key {
 			None => Path::new("").join(&key_value.name),
 			Some(key) => {
-				let dir_path = BucketDB::get_path(key.as_ref(), &transaction).await.unwrap();
+				let dir_path = BucketDB::get_path(key.as_ref(), &transaction).await?;
 				Path::new(&dir_path).join(&key_value.name)
 			},
 		};
+		transaction.commit()?;
 
-		let path = match &key_value.value {
-			None => format!("{}/", path.to_str().unwrap()),
-			Some(_) => format!("{}", path.to_str().unwrap())
-		};
-
+		let path = format!("{}/", path.to_string_lossy());
+		self.authorize(&path, Permission::Write).await?;
 		let save_path = Path::new(self.path.as_str()).join("files").join(&path);
 		let hash = FsMetadata::calculate_hash(path.as_str());
+		self.check_write(&hash, preconditions).await?;
 
 		let key_path = &KeyPath {
 			key: hash.to_string(),
 			path,
-			is_dir: key_value.value.is_none(),
+			is_dir: true,
 		};
 
-		BucketDB::add_key(key_path, &transaction).await;
-		transaction.commit();
-
-		match &key_value.value {
-			None => fs::create_dir(&save_path).await?,
-			Some(buffer) => {
-				let bucket = FsMetadata::new(&self.path).await.unwrap();
-				let file_size = buffer.len() as u64;
-				bucket.increase_size(file_size).await.unwrap();
-				self.store.update_space(file_size).await.unwrap();
-				fs::write(&save_path, buffer).await?
+		let intent = Intent::AddDir { path: key_path.path.clone() };
+		let intent_id = self.begin_intent(&connection, &intent).await?;
+		let result: Result<String> = async {
+			let transaction = connection.transaction()?;
+			BucketDB::add_key(key_path, &transaction).await?;
+			self.log(AuditOperation::Add, &key_path.key, &key_path.path, &transaction).await?;
+			fs::create_dir(&save_path).await?;
+			self.set_item_owner(&save_path).await?;
+			BucketDB::remove_intent(&intent_id, &transaction).await?;
+			transaction.commit()?;
+
+			return Ok(hash);
+		}.await;
+
+		return self.finish_intent(&intent_id, &intent, result).await;
+	}
+
+	/// Writes a file from `reader` without buffering it in memory.
+	///
+	/// The data goes to a temporary file inside the bucket while the quota is
+	/// reserved chunk by chunk, and is renamed into `files/` only once the whole
+	/// stream has been written. An existing file with the same name is replaced.
+	pub async fn add_stream<R: AsyncRead + Unpin>(&mut self, key: Option<&String>, name: &str, reader: &mut R) -> Result<String> {
+		return self.add_stream_if(key, name, reader, &Preconditions::default()).await;
+	}
+
+	/// Like `add_stream`, with the preconditions checked once the data is written,
+	/// right before it replaces the current file.
+	pub async fn add_stream_if<R: AsyncRead + Unpin>(&mut self, key: Option<&String>, name: &str, reader: &mut R, preconditions: &Preconditions) -> Result<String> {
+		let staged = self.stage_stream(key, name, reader).await?;
+		return self.commit_stream(staged, preconditions).await;
+	}
+
+	// The first half of `add_stream_if`: writes the data to a temporary file. Only the
+	// bucket quota is touched, so a caller may run it next to other changes.
+	pub(crate) async fn stage_stream<R: AsyncRead + Unpin>(&self, key: Option<&String>, name: &str, reader: &mut R) -> Result<StagedFile> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+
+		let transaction = connection.transaction()?;
+		let path = match key {
+			None => Path::new("").join(name),
+			Some(key) => {
+				let dir_path = BucketDB::get_path(key.as_ref(), &transaction).await?;
+				Path::new(&dir_path).join(name)
+			},
+		};
+		transaction.commit()?;
+
+		let path = path.to_string_lossy().to_string();
+		self.authorize(&path, Permission::Write).await?;
+		self.prepare_file(&path).await?;
+
+		let temp_dir = Path::new(&self.path).join("tmp");
+		fs::create_dir_all(&temp_dir).await?;
+
+		let temp_path = temp_dir.join(Uuid::new_v4().to_string());
+		let (size, checksums) = self.write_file(reader, &temp_path).await?;
+
+		return Ok(StagedFile { path, temp_path, size, checksums });
+	}
+
+	// The second half of `add_stream_if`: checks `preconditions` and moves the staged
+	// file into `files/`. On error the staged file and its quota are released.
+	pub(crate) async fn commit_stream(&mut self, staged: StagedFile, preconditions: &Preconditions) -> Result<String> {
+		let result = async {
+			// The file may have been added or removed since it was staged.
+			let (hash, is_replace) = self.prepare_file(&staged.path).await?;
+			self.check_write(&hash, preconditions).await?;
+			FsMetadata::new(&staged.temp_path).await?.set_checksums(&staged.checksums).await?;
+			self.commit_file(&staged.temp_path, staged.size, &staged.path, &hash, is_replace).await?;
+			return Ok(hash);
+		}.await;
+
+		if result.is_err() {
+			fs::remove_file(&staged.temp_path).await.is_err();
+			FsMetadata::new(&self.path).await?.decrease_size(staged.size).await?;
+		}
+
+		return result;
+	}
+
+	// Returns the key of the file at `path` and whether it replaces an existing file.
+	pub(crate) async fn prepare_file(&self, path: &str) -> Result<(String, bool)> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let hash = FsMetadata::calculate_hash(path);
+		let is_replace = BucketDB::has_key(&hash, &transaction).await?;
+		transaction.commit()?;
+
+		let save_path = Path::new(self.path.as_str()).join("files").join(path);
+		if is_replace && fs::metadata(&save_path).await?.is_dir() {
+			return Err(SolarError::IsADirectory("Can't replace a directory with a file!".to_string()));
+		}
+
+		return Ok((hash, is_replace));
+	}
+
+	// Moves a fully written file into `files/`. The bucket usage already includes
+	// `file_size`, the store is charged for it here and the replaced file is released
+	// once the commit succeeded. On error the store charge is undone and the caller
+	// still owns `temp_path`.
+	pub(crate) async fn commit_file(&mut self, temp_path: &Path, file_size: u64, path: &str, hash: &str, is_replace: bool) -> Result<()> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let save_path = Path::new(self.path.as_str()).join("files").join(path);
+		let bucket = FsMetadata::new(&self.path).await?;
+
+		self.store.update_space(file_size).await?;
+
+		let version_id = match is_replace && bucket.is_versioning().await? {
+			true => Some(Uuid::new_v4().simple().to_string()),
+			false => None,
+		};
+		let intent = Intent::AddFile { path: path.to_string(), temp_path: temp_path.to_string_lossy().to_string(), version_id: version_id.clone() };
+		let intent_id = self.begin_intent(&connection, &intent).await?;
+		let result: Result<u64> = async {
+			let mut released_size = 0;
+			let transaction = connection.transaction()?;
+			if is_replace {
+				let old_file = FsMetadata::new(&save_path).await?;
+				let old_size = old_file.get_size().await?;
+				let is_favorite = old_file.is_favorite().await?;
+
+				BucketDB::restore_delete(hash, &transaction).await?;
+				match &version_id {
+					// The old content stays in the bucket usage as a version.
+					Some(version_id) => {
+						self.keep_version(hash, &save_path, version_id, &transaction).await?;
+						fs::rename(temp_path, &save_path).await?;
+					},
+					None => {
+						fs::rename(temp_path, &save_path).await?;
+						released_size = old_size;
+					},
+				}
+
+				if is_favorite {
+					FsMetadata::new(&save_path).await?.set_favorite().await?;
+				}
+			} else {
+				BucketDB::add_key(&KeyPath { key: hash.to_string(), path: path.to_string(), is_dir: false }, &transaction).await?;
+				fs::rename(temp_path, &save_path).await?;
+			}
+			self.set_item_owner(&save_path).await?;
+			self.index_path(&KeyPath { key: hash.to_string(), path: path.to_string(), is_dir: false }, &transaction).await?;
+			self.index_content(hash, path, &transaction).await?;
+			self.log(AuditOperation::Add, hash, path, &transaction).await?;
+			BucketDB::remove_intent(&intent_id, &transaction).await?;
+			transaction.commit()?;
+
+			return Ok(released_size);
+		}.await;
+
+		let released_size = match self.finish_intent(&intent_id, &intent, result).await {
+			Ok(released_size) => released_size,
+			Err(error) => {
+				self.store.release_space(file_size).await?;
+				return Err(error);
+			},
+		};
+		bucket.decrease_size(released_size).await?;
+		self.store.release_space(released_size).await?;
+
+		return Ok(());
+	}
+
+	// Streams `reader` into a new file at `file_path`, reserving the bucket quota
+	// chunk by chunk. Returns the size and the checksums of the written data: the
+	// MD5 always, the other algorithms when they are turned on for the bucket.
+	pub(crate) async fn write_file<R: AsyncRead + Unpin>(&self, reader: &mut R, file_path: &Path) -> Result<(u64, Checksums)> {
+		let bucket = FsMetadata::new(&self.path).await?;
+		let disc = Space::get_disc(file_path)?;
+		let mut hasher = ChecksumHasher::new(&bucket.get_checksum_algorithms().await?);
+		let mut file = fs::File::create(file_path).await?;
+		let mut buffer = vec![0u8; WRITE_BUFFER_SIZE];
+		let mut size: u64 = 0;
+
+		let result: Result<()> = async {
+			loop {
+				let read = reader.read(&mut buffer).await?;
+				if read == 0 {
+					break;
+				}
+
+				if self.store.usage_space + size + read as u64 > self.store.available_space {
+					return Err(SolarError::QuotaExceeded("There is no free space in the Storage!".to_string()));
+				}
+				if size + read as u64 > disc.free_space {
+					return Err(SolarError::QuotaExceeded("There is no free space on the disk!".to_string()));
+				}
+				bucket.increase_size(read as u64).await?;
+				size += read as u64;
+
+				hasher.update(&buffer[..read]);
+				file.write_all(&buffer[..read]).await?;
 			}
+
+			file.sync_all().await?;
+			return Ok(());
+		}.await;
+
+		if let Err(error) = result {
+			fs::remove_file(file_path).await.is_err();
+			bucket.decrease_size(size).await?;
+			return Err(error);
 		}
 
-		return Ok(hash);
+		return Ok((size, hasher.finalize()));
 	}
 
 	pub async fn adds(&mut self, key_values: Vec<KeyValue>) -> Result<Vec<String>> {
 		let mut keys: Vec<String> = Vec::new();
 		for key_value in key_values.iter() {
-			let key = self.add(key_value).await.unwrap();
+			let key = self.add(key_value).await?;
 			keys.push(key);
 		}
 
@@ -124,269 +449,481 @@ impl Bucket {
 	}
 
 	pub async fn get(&self, key: &String, info_only: bool) -> Result<FsItem> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		return self.get_if(key, info_only, &Preconditions::default()).await;
+	}
+
+	/// Like `get`, but fails with `NotModified` or `PreconditionFailed` when the item
+	/// doesn't meet `preconditions`.
+	pub async fn get_if(&self, key: &String, info_only: bool, preconditions: &Preconditions) -> Result<FsItem> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
-		let path = BucketDB::get_path(key, &transaction).await.unwrap();
+		let path = BucketDB::get_path(key, &transaction).await?;
+		self.authorize(&path, Permission::Read).await?;
 		let path = Path::new(&self.path).join("files").join(path);
 
 		let fs_metadata = FsMetadata::new(&path).await?;
-		let mut fs_item = fs_metadata.info().await.unwrap();
+		if !preconditions.is_empty() {
+			preconditions.check_read(key, &fs_metadata.get_properties().await?)?;
+		}
+		let mut fs_item = fs_metadata.info().await?;
 
 		if !info_only && fs_item.is_dir {
-			return Err(Error::new(ErrorKind::IsADirectory, "Can't get data because it's a directory!"));
+			return Err(SolarError::IsADirectory("Can't get data because it's a directory!".to_string()));
 		}
 
 		if !info_only {
 			fs_item.buffer = Some(fs::read(&path).await?);
 		}
 
-		transaction.commit();
+		transaction.commit()?;
 		return Ok(fs_item);
 	}
 
+	pub async fn read(&self, key: &String, range: Option<&ByteRange>) -> Result<ObjectReader> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let path = BucketDB::get_path(key, &transaction).await?;
+		self.authorize(&path, Permission::Read).await?;
+		let path = Path::new(&self.path).join("files").join(path);
+		transaction.commit()?;
+
+		let fs_metadata = FsMetadata::new(&path).await?;
+		let item = fs_metadata.info().await?;
+		if item.is_dir {
+			return Err(SolarError::IsADirectory("Can't read data because it's a directory!".to_string()));
+		}
+
+		return ObjectReader::open(item, &path, range).await;
+	}
+
 	pub async fn get_items(&self, key: Option<&String>) -> Result<Vec<FsItem>> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
-		let path = match key {
+		let dir_path = match key {
 			None => "".to_string(),
-			Some(key) => BucketDB::get_path(key.as_ref(), &transaction).await.unwrap(),
+			Some(key) => BucketDB::get_path(key.as_ref(), &transaction).await?,
 		};
+		let access = self.access().await?;
+		access.check_see(&dir_path)?;
 
-		let path = Path::new(&self.path).join("files").join(path);
+		let path = Path::new(&self.path).join("files").join(&dir_path);
 
 		let mut dir_items: Vec<FsItem> = Vec::new();
 		let mut dir = fs::read_dir(&path).await?;
 		if !fs::metadata(&path).await?.is_dir() {
-			return Err(Error::new(ErrorKind::NotADirectory, "This is not a directory!"));
+			return Err(SolarError::NotADirectory("This is not a directory!".to_string()));
 		}
 
 		while let Some(item) = dir.next_entry().await? {
 			let path = &item.path();
 			let fs_metadata = FsMetadata::new(&path).await?;
-			let fs_item = fs_metadata.info().await.unwrap();
-			if fs_item.is_delete {
+			let fs_item = fs_metadata.info().await?;
+			let item_path = match fs_item.is_dir {
+				true => format!("{}{}/", dir_path, fs_item.name),
+				false => format!("{}{}", dir_path, fs_item.name),
+			};
+			if fs_item.is_delete || !access.can_see(&item_path) {
 				continue;
 			}
 			dir_items.push(fs_item);
 		}
-		transaction.commit();
+		transaction.commit()?;
 
 		return Ok(dir_items);
 	}
 
 	pub async fn rename(&self, key: &String, new_name: &String) -> Result<Vec<String>> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let old_path = BucketDB::get_path(key, &transaction).await?;
+		transaction.commit()?;
 
-		let old_path = BucketDB::get_path(key, &transaction).await.unwrap();
+		let name = Bucket::file_name(&old_path)?;
+		let new_path = old_path.replace(&name, new_name);
+		self.authorize(&old_path, Permission::Write).await?;
+		self.authorize(&new_path, Permission::Write).await?;
 
-		let name = Path::new(old_path.as_str()).file_name().unwrap().to_str().unwrap();
-		let new_path = old_path.replace(name, new_name);
+		let intent = Intent::Move { from: old_path.clone(), to: new_path.clone() };
+		let intent_id = self.begin_intent(&connection, &intent).await?;
+		let result: Result<Vec<String>> = async {
+			let transaction = connection.transaction()?;
+			let new_hashes = BucketDB::update_paths(old_path.as_str(), new_path.as_str(), &transaction).await?;
+			self.log(AuditOperation::Rename, &FsMetadata::calculate_hash(&new_path), &new_path, &transaction).await?;
 
-		let new_hashes = BucketDB::update_paths(old_path.as_str(), new_path.as_str(), &transaction).await.unwrap();
+			let old_path_system = Path::new(&self.path).join("files").join(old_path.as_str());
+			let new_path_system = Path::new(&self.path).join("files").join(new_path.as_str());
 
-		let old_path_system = Path::new(&self.path).join("files").join(old_path.as_str());
-		let new_path_system = Path::new(&self.path).join("files").join(new_path.as_str());
+			fs::rename(old_path_system, new_path_system).await?;
+			BucketDB::remove_intent(&intent_id, &transaction).await?;
+			transaction.commit()?;
 
-		fs::rename(old_path_system, new_path_system).await.unwrap();
-		transaction.commit();
+			return Ok(new_hashes);
+		}.await;
 
-		return Ok(new_hashes);
+		return self.finish_intent(&intent_id, &intent, result).await;
 	}
 
 	pub async fn move_items(&self, key_from: &String, key_to: &String) -> Result<()> {
 		if key_from.as_str() == key_to.as_str() {
-			return Err(Error::new(ErrorKind::InvalidInput, "Keys must not match!"));
+			return Err(SolarError::InvalidInput("Keys must not match!".to_string()));
 		}
 
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
-		let path_from = BucketDB::get_path(key_from, &transaction).await.unwrap();
-		let path_to = BucketDB::get_path(key_to, &transaction).await.unwrap();
+		let path_from = BucketDB::get_path(key_from, &transaction).await?;
+		let path_to = BucketDB::get_path(key_to, &transaction).await?;
+		transaction.commit()?;
 
 		if path_to.replacen(path_from.as_str(), "", 1).len() < path_to.len() {
-			return Err(Error::new(ErrorKind::InvalidInput, "Path error!"));
+			return Err(SolarError::InvalidInput("Path error!".to_string()));
 		}
 
-		let path_from = Path::new(path_from.as_str());
-		let path_to = Path::new(path_to.as_str()).join(path_from.file_name().unwrap());
+		let path_to = Bucket::target_path(&path_from, &path_to)?;
+		self.authorize(&path_from, Permission::Write).await?;
+		self.authorize(&path_to, Permission::Write).await?;
 
-		BucketDB::update_paths(path_from.to_str().unwrap(), path_to.to_str().unwrap(), &transaction).await.unwrap();
+		let intent = Intent::Move { from: path_from.clone(), to: path_to.clone() };
+		let intent_id = self.begin_intent(&connection, &intent).await?;
+		let result: Result<()> = async {
+			let transaction = connection.transaction()?;
+			BucketDB::update_paths(&path_from, &path_to, &transaction).await?;
+			self.log(AuditOperation::Move, &FsMetadata::calculate_hash(&path_to), &path_to, &transaction).await?;
 
-		let path_from = Path::new(&self.path).join("files").join(path_from);
-		let path_to = Path::new(&self.path).join("files").join(path_to);
+			// A rename is atomic, so an interrupted move is either done or not at all.
+			let path_from = Path::new(&self.path).join("files").join(&path_from);
+			let path_to = Path::new(&self.path).join("files").join(&path_to);
+			fs::rename(path_from, path_to).await?;
 
-		let fs_metadata = FsMetadata::new(path_from).await.unwrap();
-		fs_metadata.move_path(path_to, true).await.unwrap();
+			BucketDB::remove_intent(&intent_id, &transaction).await?;
+			transaction.commit()?;
+			return Ok(());
+		}.await;
 
-		transaction.commit();
-		return Ok(());
+		return self.finish_intent(&intent_id, &intent, result).await;
 	}
 
 	pub async fn copy(&self, key_from: &String, key_to: &String) -> Result<Vec<String>> {
+		return self.copy_if(key_from, key_to, &Preconditions::default()).await;
+	}
+
+	/// Like `copy`, but only when the source meets `preconditions`. As with the
+	/// `x-amz-copy-source-if-*` headers of S3, every failure is `PreconditionFailed`.
+	pub async fn copy_if(&self, key_from: &String, key_to: &String, preconditions: &Preconditions) -> Result<Vec<String>> {
 		if key_from == key_to {
-			return Err(Error::new(ErrorKind::InvalidInput, "Keys must not match!"));
+			return Err(SolarError::InvalidInput("Keys must not match!".to_string()));
 		}
 
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
-		let path_from = BucketDB::get_path(key_from, &transaction).await.unwrap();
-		let path_to = BucketDB::get_path(key_to, &transaction).await.unwrap();
+		let path_from = BucketDB::get_path(key_from, &transaction).await?;
+		let path_to = BucketDB::get_path(key_to, &transaction).await?;
+		transaction.commit()?;
 
-		let path_from = Path::new(path_from.as_str());
-		let path_to = match path_from.is_dir() {
-			false => Path::new(path_to.as_str()).join(path_from.file_name().unwrap()).to_str().unwrap().to_string(),
-			true => format!("{}/", Path::new(path_to.as_str()).join(path_from.file_name().unwrap()).to_str().unwrap().to_string()),
-		};
+		let path_to = Bucket::target_path(&path_from, &path_to)?;
+		self.authorize(&path_from, Permission::Read).await?;
+		self.authorize(&path_to, Permission::Write).await?;
+		if !preconditions.is_empty() {
+			preconditions.check_write(key_from, Some(&self.properties(key_from).await?))?;
+		}
+
+		// The copy takes space like a new file, reserved with the same checks.
+		let size = FsMetadata::new(Path::new(&self.path).join("files").join(&path_from)).await?.get_size().await?;
+		if size > Space::get_disc(Path::new(&self.path))?.free_space {
+			return Err(SolarError::QuotaExceeded("There is no free space on the disk!".to_string()));
+		}
+		let bucket = FsMetadata::new(&self.path).await?;
+		let mut store = self.store.clone();
+		bucket.increase_size(size).await?;
+		if let Err(error) = store.update_space(size).await {
+			bucket.decrease_size(size).await?;
+			return Err(error);
+		}
+
+		let intent = Intent::Copy { to: path_to.clone() };
+		let intent_id = self.begin_intent(&connection, &intent).await?;
+		let result: Result<Vec<String>> = async {
+			let transaction = connection.transaction()?;
+			let hashes = BucketDB::copy_paths(&path_from, path_to.as_str(), &transaction).await?;
+			self.log(AuditOperation::Copy, &FsMetadata::calculate_hash(&path_to), &path_to, &transaction).await?;
+
+			let path_from = Path::new(&self.path).join("files").join(&path_from);
+			let path_to = Path::new(&self.path).join("files").join(&path_to);
+
+			let fs_metadata = FsMetadata::new(path_from).await?;
+			fs_metadata.move_path(&path_to, false).await?;
+			self.set_item_owner(&path_to).await?;
+
+			BucketDB::remove_intent(&intent_id, &transaction).await?;
+			transaction.commit()?;
+			return Ok(hashes);
+		}.await;
+
+		let result = self.finish_intent(&intent_id, &intent, result).await;
+		if result.is_err() {
+			bucket.decrease_size(size).await?;
+			store.release_space(size).await?;
+		}
+
+		return result;
+	}
 
-		let hashes = BucketDB::copy_paths(path_from.to_str().unwrap(), path_to.as_str(), &transaction).await.unwrap();
+	pub async fn exists(&self, key: &String) -> Result<bool> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
-		let path_from = Path::new(&self.path).join("files").join(path_from);
-		let path_to = Path::new(&self.path).join("files").join(path_to);
+		let exists = BucketDB::has_key(key, &transaction).await?;
+		transaction.commit()?;
 
-		let fs_metadata = FsMetadata::new(path_from).await.unwrap();
-		fs_metadata.move_path(&path_to, false).await.unwrap();
+		return Ok(exists);
+	}
 
-		transaction.commit();
-		return Ok(hashes);
+	pub fn calculate_key(path: &str) -> String {
+		return FsMetadata::calculate_hash(path);
 	}
 
 	pub async fn properties(&self, key: &String) -> Result<PropertiesItem> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
-		let path = BucketDB::get_path(key, &transaction).await.unwrap();
+		let path = BucketDB::get_path(key, &transaction).await?;
+		self.authorize(&path, Permission::Read).await?;
 		let path = Path::new(&self.path).join("files").join(path);
 
-		let fs_metadata = FsMetadata::new(path).await.unwrap();
-		let properties = fs_metadata.get_properties().await.unwrap();
-		transaction.commit();
+		let fs_metadata = FsMetadata::new(path).await?;
+		let properties = fs_metadata.get_properties().await?;
+		transaction.commit()?;
 
 		return Ok(properties);
 	}
 
+	// Checks `preconditions` for a write to `key`, which may not exist yet.
+	async fn check_write(&self, key: &String, preconditions: &Preconditions) -> Result<()> {
+		if preconditions.is_empty() {
+			return Ok(());
+		}
+
+		let object = match self.exists(key).await? {
+			true => Some(self.properties(key).await?),
+			false => None,
+		};
+		return preconditions.check_write(key, object.as_ref());
+	}
+
 	pub async fn set_favorites(&self, keys: Vec<String>) -> Result<()> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
 		for key in keys {
-			let path = BucketDB::get_path(&key, &transaction).await.unwrap();
+			let path = BucketDB::get_path(&key, &transaction).await?;
+			self.authorize(&path, Permission::Write).await?;
+			self.log(AuditOperation::Favorite, &key, &path, &transaction).await?;
 			let path = Path::new(&self.path).join("files").join(path);
 
-			let fs_metadata = FsMetadata::new(path).await.unwrap();
-			BucketDB::set_favorite(&key, &transaction).await.unwrap();
-			fs_metadata.set_favorite().await.unwrap();
+			let fs_metadata = FsMetadata::new(path).await?;
+			BucketDB::set_favorite(&key, &transaction).await?;
+			fs_metadata.set_favorite().await?;
 		}
 
-		transaction.commit();
+		transaction.commit()?;
 
 		return Ok(());
 	}
 
 	pub async fn unset_favorites(&self, keys: Vec<String>) -> Result<()> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
 		for key in keys {
-			let path = BucketDB::get_path(&key, &transaction).await.unwrap();
+			let path = BucketDB::get_path(&key, &transaction).await?;
+			self.authorize(&path, Permission::Write).await?;
+			self.log(AuditOperation::Unfavorite, &key, &path, &transaction).await?;
 			let path = Path::new(&self.path).join("files").join(path);
 
-			let fs_metadata = FsMetadata::new(path).await.unwrap();
-			BucketDB::unset_favorite(&key, &transaction).await.unwrap();
-			fs_metadata.unset_favorite().await.unwrap();
+			let fs_metadata = FsMetadata::new(path).await?;
+			BucketDB::unset_favorite(&key, &transaction).await?;
+			fs_metadata.unset_favorite().await?;
 		}
 
+		transaction.commit()?;
+
 		return Ok(());
 	}
 
 	pub async fn get_favorites(&self) -> Result<Vec<FsItem>> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
-		let paths = BucketDB::get_favorites(&transaction).await.unwrap();
+		let paths = BucketDB::get_favorites(&transaction).await?;
+		let access = self.access().await?;
 
 		let mut dir_items: Vec<FsItem> = Vec::new();
-		for path in paths {
+		for path in paths.into_iter().filter(|path| access.allows(path, Permission::Read)) {
 			let path = Path::new(&self.path).join("files").join(path);
 			let fs_metadata = FsMetadata::new(&path).await?;
-			let fs_item = fs_metadata.info().await.unwrap();
+			let fs_item = fs_metadata.info().await?;
 			if fs_item.is_delete {
 				continue;
 			}
 			dir_items.push(fs_item);
 		}
 
-		transaction.commit();
+		transaction.commit()?;
 
 		return Ok(dir_items);
 	}
 
+	/// Replaces the tags of `key`. Like S3, an object holds at most `MAX_TAGS` tags.
+	pub async fn set_tags(&self, key: &String, tags: &[(String, String)]) -> Result<()> {
+		if tags.len() > MAX_TAGS {
+			return Err(SolarError::InvalidInput(format!("An object can have at most {} tags", MAX_TAGS)));
+		}
+		if tags.iter().any(|(name, _)| name.is_empty()) {
+			return Err(SolarError::InvalidInput("A tag name can't be empty!".to_string()));
+		}
+		if tags.iter().enumerate().any(|(index, (name, _))| tags[..index].iter().any(|(other, _)| other == name)) {
+			return Err(SolarError::InvalidInput("Tag names must be unique!".to_string()));
+		}
+
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let path = BucketDB::get_path(key, &transaction).await?;
+		self.authorize(&path, Permission::Write).await?;
+		BucketDB::set_tags(key, tags, &transaction).await?;
+		transaction.commit()?;
+
+		return Ok(());
+	}
+
+	pub async fn get_tags(&self, key: &String) -> Result<Vec<(String, String)>> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let path = BucketDB::get_path(key, &transaction).await?;
+		self.authorize(&path, Permission::Read).await?;
+		let tags = BucketDB::get_tags(key, &transaction).await?;
+		transaction.commit()?;
+
+		return Ok(tags);
+	}
+
+	pub async fn set_trash_retention(&self, retention: u64) -> Result<()> {
+		self.authorize("", Permission::Admin).await?;
+		return FsMetadata::new(&self.path).await?.set_trash_retention(retention).await;
+	}
+
+	pub async fn get_trash_retention(&self) -> Result<u64> {
+		self.authorize("", Permission::Read).await?;
+		return self.trash_retention().await;
+	}
+
+	async fn trash_retention(&self) -> Result<u64> {
+		let retention = FsMetadata::new(&self.path).await?.get_trash_retention().await?;
+		return Ok(retention.unwrap_or(DEFAULT_TRASH_RETENTION));
+	}
+
+	/// Selects the checksums computed for new objects besides the MD5 used as the ETag.
+	/// Objects written before keep the checksums they were stored with.
+	pub async fn set_checksum_algorithms(&self, algorithms: &[ChecksumAlgorithm]) -> Result<()> {
+		self.authorize("", Permission::Admin).await?;
+		return FsMetadata::new(&self.path).await?.set_checksum_algorithms(algorithms).await;
+	}
+
+	pub async fn get_checksum_algorithms(&self) -> Result<Vec<ChecksumAlgorithm>> {
+		self.authorize("", Permission::Read).await?;
+		return FsMetadata::new(&self.path).await?.get_checksum_algorithms().await;
+	}
+
+	/// Moves the items to the trash, where they stay for the trash retention of the bucket.
 	pub async fn set_delete(&self, keys: Vec<String>) -> Result<()> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		return self.set_delete_if(keys, &Preconditions::default()).await;
+	}
 
-		for key in keys {
-			let path = BucketDB::get_path(&key, &transaction).await.unwrap();
+	/// Like `set_delete`, but only when every item meets `preconditions`, otherwise
+	/// nothing is moved.
+	pub async fn set_delete_if(&self, keys: Vec<String>, preconditions: &Preconditions) -> Result<()> {
+		let retention = self.trash_retention().await?;
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let mut paths = Vec::new();
+		for key in keys.iter() {
+			let path = BucketDB::get_path(key, &transaction).await?;
+			self.authorize(&path, Permission::Write).await?;
+			paths.push(path);
+		}
+		for key in keys.iter() {
+			self.check_write(key, preconditions).await?;
+		}
+
+		for (key, path) in keys.into_iter().zip(paths) {
+			self.log(AuditOperation::Delete, &key, &path, &transaction).await?;
 			let path = Path::new(&self.path).join("files").join(path);
 
-			let fs_metadata = FsMetadata::new(path).await.unwrap();
-			let timestamp = fs_metadata.set_delete().await.unwrap();
+			let fs_metadata = FsMetadata::new(path).await?;
+			let timestamp = fs_metadata.set_delete().await?;
 
-			BucketDB::set_delete(&key, timestamp + 2592000, &transaction).await.unwrap();
+			BucketDB::set_delete(&key, timestamp + retention as i64, &transaction).await?;
 		}
 
-		transaction.commit();
+		transaction.commit()?;
 
 		return Ok(());
 	}
 
 	pub async fn restore_delete(&self, keys: Vec<String>) -> Result<()> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
 		for key in keys {
-			let path = BucketDB::get_path(&key, &transaction).await.unwrap();
+			let path = BucketDB::get_path(&key, &transaction).await?;
+			self.authorize(&path, Permission::Write).await?;
+			self.log(AuditOperation::Restore, &key, &path, &transaction).await?;
 			let path = Path::new(&self.path).join("files").join(path);
 
-			let fs_metadata = FsMetadata::new(path).await.unwrap();
-			fs_metadata.restore_delete().await.unwrap();
+			let fs_metadata = FsMetadata::new(path).await?;
+			fs_metadata.restore_delete().await?;
 
-			BucketDB::restore_delete(&key, &transaction).await.unwrap();
+			BucketDB::restore_delete(&key, &transaction).await?;
 		}
 
-		transaction.commit();
+		transaction.commit()?;
 
 		return Ok(());
 	}
 
 	pub async fn get_deletes(&self) -> Result<Vec<FsItem>> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
-		let paths = BucketDB::get_deletes(&transaction).await.unwrap();
+		let paths = BucketDB::get_deletes(&transaction).await?;
+		let access = self.access().await?;
 
 		let mut dir_items: Vec<FsItem> = Vec::new();
-		for path in paths {
+		for path in paths.into_iter().filter(|path| access.allows(path, Permission::Read)) {
 			let path = Path::new(&self.path).join("files").join(path);
 			let fs_metadata = FsMetadata::new(&path).await?;
-			let fs_item = fs_metadata.info().await.unwrap();
+			let fs_item = fs_metadata.info().await?;
 			dir_items.push(fs_item);
 		}
 
-		transaction.commit();
+		transaction.commit()?;
 
 		return Ok(dir_items);
 	}
 
 	pub async fn get_path(&self, key: String) -> Result<Vec<PathItem>> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
 
-		let path = BucketDB::get_path(&key, &transaction).await.unwrap();
+		let path = BucketDB::get_path(&key, &transaction).await?;
+		self.authorize(&path, Permission::Read).await?;
 		let paths = path.split("/");
 
 		let mut result: Vec<PathItem> = vec![];
@@ -394,8 +931,8 @@ impl Bucket {
 
 		for path in paths.into_iter() {
 			path_to_hash.push(path.to_string());
-			let path_hash = Path::new(&self.path).join("files").join(&path);
-			let hash = FsMetadata::calculate_hash(path_hash.to_str().unwrap());
+			let path_hash = Path::new(&self.path).join("files").join(path);
+			let hash = FsMetadata::calculate_hash(&path_hash.to_string_lossy());
 
 			result.push(PathItem {
 				key: hash,
@@ -403,47 +940,109 @@ impl Bucket {
 			});
 		}
 
-		transaction.commit();
+		transaction.commit()?;
 		return Ok(result);
 	}
 
 	pub async fn remove(&self, keys: Vec<String>) -> Result<()> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		let bucket = FsMetadata::new(&self.path).await?;
 
 		for key in keys {
-			let path = BucketDB::get_path(&key, &transaction).await.unwrap();
+			let path = BucketDB::get_path(&key, &transaction).await?;
+			self.authorize(&path, Permission::Write).await?;
+			self.remove_versions(&path, &transaction).await?;
+			self.log(AuditOperation::Remove, &key, &path, &transaction).await?;
 			let path = Path::new(&self.path).join("files").join(path);
 
-			let fs_metadata = FsMetadata::new(path).await.unwrap();
-			let file_prop = fs_metadata.get_properties().await.unwrap();
-			BucketDB::remove_trash(&key, &transaction).await.unwrap();
-			fs_metadata.remove().await.unwrap();
-			fs_metadata.decrease_size(file_prop.size);
+			let fs_metadata = FsMetadata::new(&path).await?;
+			let size = fs_metadata.get_size().await?;
+			BucketDB::remove_trash(&key, &transaction).await?;
+			fs_metadata.remove().await?;
+			bucket.decrease_size(size).await?;
+			self.store.clone().release_space(size).await?;
 		}
-		transaction.commit();
+		transaction.commit()?;
 
 		return Ok(());
 	}
 
 	pub async fn clear_trash(&self) -> Result<()> {
-		let mut connection = BucketDB::open(self.path.as_str()).await.unwrap();
-		let transaction = connection.transaction().unwrap();
-		let paths = BucketDB::clear_trash(&transaction).await.unwrap();
+		self.authorize("", Permission::Admin).await?;
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		for path in BucketDB::get_deletes(&transaction).await? {
+			self.remove_versions(&path, &transaction).await?;
+		}
+		let paths = BucketDB::clear_trash(&transaction).await?;
+		let bucket = FsMetadata::new(&self.path).await?;
 
 		for path in paths {
+			self.log(AuditOperation::Remove, &FsMetadata::calculate_hash(&path), &path, &transaction).await?;
 			let path = Path::new(&self.path).join("files").join(path);
-			let fs_metadata = FsMetadata::new(path).await.unwrap();
-			let file_prop = fs_metadata.get_properties().await.unwrap();
-			fs_metadata.remove().await.unwrap();
-			fs_metadata.decrease_size(file_prop.size);
+			let fs_metadata = FsMetadata::new(path).await?;
+			let size = fs_metadata.get_size().await?;
+			fs_metadata.remove().await?;
+			bucket.decrease_size(size).await?;
+			self.store.clone().release_space(size).await?;
+		}
+
+		transaction.commit()?;
+
+		return Ok(());
+	}
+
+	/// Permanently removes the trash items whose retention ended before `date` (a unix
+	/// timestamp) and returns their paths.
+	pub async fn purge_trash(&self, date: i64) -> Result<Vec<String>> {
+		self.authorize("", Permission::Admin).await?;
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		for path in BucketDB::get_date_trash(date, &transaction).await? {
+			self.remove_versions(&path, &transaction).await?;
 		}
+		let paths = BucketDB::remove_date_trash(date, &transaction).await?;
+		let bucket = FsMetadata::new(&self.path).await?;
+
+		for path in paths.iter() {
+			self.log(AuditOperation::Remove, &FsMetadata::calculate_hash(path), path, &transaction).await?;
+			let fs_metadata = FsMetadata::new(Path::new(&self.path).join("files").join(path)).await?;
+			let size = fs_metadata.get_size().await?;
+			fs_metadata.remove().await?;
+			bucket.decrease_size(size).await?;
+			self.store.clone().release_space(size).await?;
+		}
+
+		transaction.commit()?;
 
-		transaction.commit();
+		return Ok(paths);
+	}
+
+	// Records the acting user as the owner of the new item at `path`.
+	async fn set_item_owner(&self, path: &Path) -> Result<()> {
+		if let Some(user) = &self.user {
+			FsMetadata::new(path).await?.set_owner(&user.uuid).await?;
+		}
 
 		return Ok(());
 	}
 
+	// The path of `path_from` once moved or copied into the directory `dir_path`.
+	fn target_path(path_from: &str, dir_path: &str) -> Result<String> {
+		let path_to = Path::new(dir_path).join(Bucket::file_name(path_from)?).to_string_lossy().to_string();
+		return match path_from.ends_with('/') {
+			false => Ok(path_to),
+			true => Ok(format!("{}/", path_to)),
+		};
+	}
+
+	fn file_name(path: &str) -> Result<String> {
+		return Path::new(path).file_name()
+			.map(|name| name.to_string_lossy().to_string())
+			.ok_or_else(|| SolarError::InvalidInput(format!("{} has no file name", path)));
+	}
+
 	pub async fn get_space() -> Result<String> {
 		return Ok("test".to_string());
 	}
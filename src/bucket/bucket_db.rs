@@ -1,10 +1,19 @@
 use std::path::Path;
-use std::str::from_utf8;
 
-use rusqlite::{Connection, Result, Transaction};
-use tokio::fs;
+use mime_guess::mime;
+use rusqlite::types::Value;
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension, Row, Transaction};
 
+use crate::bucket::access::Grant;
+use crate::bucket::audit::{AuditEntry, AuditFilter};
+use crate::bucket::content::{ContentMatch, HIGHLIGHT_END, HIGHLIGHT_START};
 use crate::bucket::fs_metadata::FsMetadata;
+use crate::bucket::journal::Intent;
+use crate::bucket::lifecycle::LifecycleRule;
+use crate::bucket::listing::ListKind;
+use crate::bucket::search::{SearchItem, SearchOptions, SearchSort, SearchToken};
+use crate::error::{Result, SolarError};
 
 #[derive(Debug)]
 pub struct KeyPath {
@@ -15,181 +24,856 @@ pub struct KeyPath {
 
 pub struct BucketDB;
 
+// What search knows about a path besides its name, taken from the filesystem.
+pub struct PathMetadata {
+	pub key: String,
+	pub path: String,
+	pub is_dir: bool,
+	pub size: u64,
+	pub create_at: i64,
+	pub update_at: i64,
+}
+
+pub struct VersionRow {
+	pub version_id: String,
+	pub key: String,
+	pub size: u64,
+	pub etag: Option<String>,
+	pub create_at: i64,
+	pub noncurrent_at: i64,
+}
+
+// The tables of schema version 1, compiled in so the crate runs from any directory.
+const CREATE_TABLES: &str = include_str!("sql/create_tables.sql");
+
+// One row per applied migration. It comes before every other migration, so it is
+// created on its own.
+const CREATE_SCHEMA_VERSION: &str = "CREATE TABLE IF NOT EXISTS schema_version
+(
+    version    INTEGER NOT NULL
+        CONSTRAINT schema_version_pk
+            PRIMARY KEY,
+    applied_at INT     NOT NULL
+);";
+
+// Version 1 means that `paths.hash` holds the SHA-256 keys of `FsMetadata::calculate_hash`,
+// anything lower the old `DefaultHasher` keys.
+const KEY_VERSION: i32 = 1;
+
+// The schema changes made after `create_tables.sql`, applied in order on top of
+// `KEY_VERSION`. A database at schema version N has every migration up to N.
+const SCHEMA_MIGRATIONS: [(i32, &str); 7] = [
+	(2, "CREATE TABLE versions
+(
+    version_id TEXT NOT NULL
+        CONSTRAINT versions_pk
+            PRIMARY KEY,
+    hash       TEXT NOT NULL
+        CONSTRAINT versions_paths_hash_fk
+            REFERENCES paths
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    size       INT  NOT NULL,
+    etag       TEXT,
+    create_at  INT  NOT NULL
+);
+
+CREATE INDEX versions_hash_index
+    ON versions (hash);"),
+	(3, "ALTER TABLE versions ADD COLUMN noncurrent_at INT NOT NULL DEFAULT 0;
+UPDATE versions SET noncurrent_at = create_at;
+
+CREATE TABLE tags
+(
+    hash  TEXT NOT NULL
+        CONSTRAINT tags_paths_hash_fk
+            REFERENCES paths
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    name  TEXT NOT NULL,
+    value TEXT NOT NULL,
+    CONSTRAINT tags_pk
+        PRIMARY KEY (hash, name)
+);
+
+CREATE TABLE lifecycle_rules
+(
+    id                   TEXT              NOT NULL
+        CONSTRAINT lifecycle_rules_pk
+            PRIMARY KEY,
+    prefix               TEXT DEFAULT ''   NOT NULL,
+    tag_name             TEXT,
+    tag_value            TEXT,
+    expiration_days      INT,
+    noncurrent_days      INT,
+    abort_multipart_days INT,
+    enabled              INTEGER DEFAULT 1 NOT NULL
+);"),
+	(4, "CREATE TABLE path_metadata
+(
+    hash      TEXT NOT NULL
+        CONSTRAINT path_metadata_pk
+            PRIMARY KEY
+        CONSTRAINT path_metadata_paths_hash_fk
+            REFERENCES paths
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    name      TEXT NOT NULL,
+    extension TEXT NOT NULL,
+    mime_type TEXT,
+    size      INT  NOT NULL,
+    create_at INT  NOT NULL,
+    update_at INT  NOT NULL
+);
+
+CREATE INDEX path_metadata_name_index
+    ON path_metadata (name);
+
+CREATE INDEX path_metadata_size_index
+    ON path_metadata (size);
+
+CREATE INDEX path_metadata_create_at_index
+    ON path_metadata (create_at);
+
+CREATE INDEX path_metadata_update_at_index
+    ON path_metadata (update_at);"),
+	(5, "CREATE TABLE content_keys
+(
+    id   INTEGER NOT NULL
+        CONSTRAINT content_keys_pk
+            PRIMARY KEY,
+    hash TEXT    NOT NULL
+        CONSTRAINT content_keys_paths_hash_fk
+            REFERENCES paths
+            ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX content_keys_hash_uindex
+    ON content_keys (hash);
+
+CREATE VIRTUAL TABLE content_index USING fts5(content);
+
+CREATE TRIGGER content_keys_delete
+    AFTER DELETE
+    ON content_keys
+BEGIN
+    DELETE FROM content_index WHERE rowid = old.id;
+END;"),
+	(6, "CREATE TABLE intents
+(
+    id        TEXT NOT NULL
+        CONSTRAINT intents_pk
+            PRIMARY KEY,
+    process   TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    create_at INT  NOT NULL
+);"),
+	// `log_paths` was never written. The entries must outlive their path, so the
+	// foreign key goes and `update_paths` moves them along instead.
+	(7, "DROP TABLE log_paths;
+
+CREATE TABLE log_paths
+(
+    id          INTEGER NOT NULL
+        CONSTRAINT log_paths_pk
+            PRIMARY KEY,
+    hash        TEXT    NOT NULL,
+    path        TEXT    NOT NULL,
+    operation   TEXT    NOT NULL,
+    author_uuid TEXT,
+    create_at   INT     NOT NULL,
+    comment     TEXT
+);
+
+CREATE INDEX log_paths_hash_index
+    ON log_paths (hash, create_at);
+
+CREATE INDEX log_paths_create_at_index
+    ON log_paths (create_at);"),
+	(8, "CREATE TABLE grants
+(
+    user_uuid  TEXT NOT NULL,
+    prefix     TEXT DEFAULT '' NOT NULL,
+    permission TEXT NOT NULL,
+    CONSTRAINT grants_pk
+        PRIMARY KEY (user_uuid, prefix)
+);"),
+];
+
+pub const SCHEMA_VERSION: i32 = SCHEMA_MIGRATIONS[SCHEMA_MIGRATIONS.len() - 1].0;
+
+// Whether the path `p.path` is in the trash, itself or through one of its directories.
+const TRASHED_PATH: &str = "EXISTS(SELECT 1 FROM delete_paths d INNER JOIN paths dp ON dp.hash = d.hash
+	WHERE p.path = dp.path OR (dp.is_dir = 1 AND substr(p.path, 1, length(dp.path)) = dp.path))";
+
 impl BucketDB {
 	pub async fn init(bucket_path: impl AsRef<Path>) -> Result<()> {
-		let path = bucket_path.as_ref();
-		let mut connection = Connection::open(path.join("user-paths.sqlite"))?;
+		let mut connection = BucketDB::open(bucket_path).await?;
 
 		let transaction = connection.transaction()?;
+		transaction.execute_batch(CREATE_TABLES)?;
+		transaction.execute_batch(CREATE_SCHEMA_VERSION)?;
+		BucketDB::set_schema_version(KEY_VERSION, &transaction)?;
+		transaction.commit()?;
 
-		let sql_scripts = fs::read("./src/bucket/sql/create_tables.sql").await.unwrap();
+		BucketDB::migrate_schema(&mut connection)?;
+		return Ok(());
+	}
 
-		let create_tables = from_utf8(&sql_scripts).unwrap();
-		for script in create_tables.split(";") {
-			transaction.execute(&script, ()).unwrap();
+	pub async fn open(path: impl AsRef<Path>) -> Result<Connection> {
+		let path = Path::new(path.as_ref()).join("user-paths.sqlite");
+		let connection = Connection::open(path)?;
+		connection.execute("PRAGMA foreign_keys = ON", ())?;
+		return Ok(connection);
+	}
+
+	/// Upgrades the database of the bucket at `bucket_path` to `SCHEMA_VERSION` and
+	/// returns the version it was at.
+	pub async fn migrate(bucket_path: impl AsRef<Path>) -> Result<i32> {
+		let mut connection = BucketDB::open(bucket_path).await?;
+		let version = BucketDB::schema_version(&connection)?;
+		if version > SCHEMA_VERSION {
+			return Err(SolarError::InvalidInput(format!("The bucket database is at schema version {}, this build knows up to {}", version, SCHEMA_VERSION)));
 		}
 
-		transaction.commit();
-		connection.execute("PRAGMA foreign_keys = ON", ()).unwrap();
+		if version < KEY_VERSION {
+			BucketDB::migrate_keys(&mut connection)?;
+		}
+		BucketDB::migrate_schema(&mut connection)?;
+
+		return Ok(version);
+	}
 
+	// The last version in `schema_version`. The databases from before that table kept
+	// their version in `PRAGMA user_version` only.
+	pub fn schema_version(connection: &Connection) -> Result<i32> {
+		connection.execute_batch(CREATE_SCHEMA_VERSION)?;
+		let version: Option<i32> = connection.query_row("SELECT MAX(version) FROM schema_version", [], |row| row.get(0))?;
+
+		return match version {
+			Some(version) => Ok(version),
+			None => Ok(connection.query_row("PRAGMA user_version", [], |row| row.get(0))?),
+		};
+	}
+
+	// `PRAGMA user_version` is kept up to date as well, for the older builds that read it.
+	fn set_schema_version(version: i32, transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("INSERT INTO schema_version (version, applied_at) VALUES (?1, ?2)", (version, Utc::now().timestamp()))?;
+		transaction.pragma_update(None, "user_version", version)?;
 		return Ok(());
 	}
 
-	pub async fn open(path: impl AsRef<Path>) -> Result<Connection> {
-		let path = Path::new(path.as_ref()).join("user-paths.sqlite");
-		return Ok(Connection::open(path)?);
+	// Rewrites the keys of a database created before the SHA-256 keys. The other
+	// tables follow through `ON UPDATE CASCADE`.
+	fn migrate_keys(connection: &mut Connection) -> Result<()> {
+		let transaction = connection.transaction()?;
+		let key_paths = {
+			let mut prepare_query = transaction.prepare("SELECT hash, path FROM paths")?;
+			let rows = prepare_query.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+			rows.collect::<rusqlite::Result<Vec<(String, String)>>>()?
+		};
+
+		for (key, path) in key_paths {
+			let new_key = FsMetadata::calculate_hash(&path);
+			if new_key != key {
+				transaction.execute("UPDATE paths SET hash = ?1 WHERE hash = ?2", [&new_key, &key])?;
+			}
+		}
+
+		BucketDB::set_schema_version(KEY_VERSION, &transaction)?;
+		transaction.commit()?;
+
+		return Ok(());
+	}
+
+	// Applies the migrations the database doesn't have yet, each in its own transaction.
+	fn migrate_schema(connection: &mut Connection) -> Result<()> {
+		let version = BucketDB::schema_version(connection)?;
+
+		for (migration_version, migration) in SCHEMA_MIGRATIONS {
+			if version >= migration_version {
+				continue;
+			}
+
+			let transaction = connection.transaction()?;
+			transaction.execute_batch(migration)?;
+			BucketDB::set_schema_version(migration_version, &transaction)?;
+			transaction.commit()?;
+		}
+
+		return Ok(());
 	}
 
 	pub async fn add_key(key: &KeyPath, transaction: &Transaction<'_>) -> Result<()> {
-		transaction.execute("INSERT INTO paths (hash, path, is_dir) VALUES (?1, ?2, ?3);", (&key.key, &key.path, key.is_dir as i8))
-			.unwrap();
+		BucketDB::check_key(&key.key, &key.path, transaction)?;
+		transaction.execute("INSERT INTO paths (hash, path, is_dir) VALUES (?1, ?2, ?3);", (&key.key, &key.path, key.is_dir as i8))?;
 		return Ok(());
 	}
 
+	// Fails when `key` already belongs to a different path.
+	fn check_key(key: &str, path: &str, transaction: &Transaction<'_>) -> Result<()> {
+		let existing_path: Option<String> = transaction.query_row("SELECT path FROM paths WHERE hash = ?1", [key], |row| row.get(0))
+			.optional()?;
+
+		return match existing_path {
+			Some(existing_path) if existing_path != path => Err(SolarError::AlreadyExists(
+				format!("Key collision: '{}' and '{}' have the same key {}", path, existing_path, key),
+			)),
+			_ => Ok(()),
+		};
+	}
+
 	pub async fn get_path(key: &str, transaction: &Transaction<'_>) -> Result<String> {
-		transaction.query_row("SELECT path FROM paths WHERE hash = ?1",
-							  [key], |row| {
+		let path = transaction.query_row("SELECT path FROM paths WHERE hash = ?1",
+										 [key], |row| {
 				row.get(0)
-			})
+			}).optional()?;
+
+		return path.ok_or_else(|| SolarError::KeyNotFound(key.to_string()));
+	}
+
+	pub async fn has_key(key: &str, transaction: &Transaction<'_>) -> Result<bool> {
+		let exists = transaction.query_row("SELECT EXISTS(SELECT 1 FROM paths WHERE hash = ?1)",
+										   [key], |row| {
+				row.get(0)
+			})?;
+
+		return Ok(exists);
+	}
+
+	fn key_path(row: &Row<'_>) -> rusqlite::Result<KeyPath> {
+		return Ok(KeyPath {
+			key: row.get(0)?,
+			path: row.get(1)?,
+			is_dir: row.get::<_, u8>(2)? != 0,
+		});
 	}
 
 	pub async fn update_paths(old_path: &str, new_path: &str, transaction: &Transaction<'_>) -> Result<Vec<String>> {
-		let mut prepare_query = transaction.prepare("SELECT * FROM paths WHERE path LIKE ?1 || '%'").unwrap();
-		let key_paths = prepare_query.query_map([old_path], |row| {
-			Ok(KeyPath {
-				key: row.get(0).unwrap(),
-				path: row.get(1).unwrap(),
-				is_dir: row.get::<_, u8>(2).unwrap() != 0,
-			})
-		}).unwrap();
+		let mut prepare_query = transaction.prepare("SELECT * FROM paths WHERE path = ?1 OR (substr(?1, -1) = '/' AND substr(path, 1, length(?1)) = ?1)")?;
+		let key_paths = prepare_query.query_map([old_path], BucketDB::key_path)?;
 		let mut vec_hashes: Vec<String> = Vec::new();
 
 		for key_path in key_paths {
-			let key_path = key_path.unwrap();
+			let key_path = key_path?;
 			let updated_path = key_path.path.replacen(old_path, new_path, 1);
 
 			let updated_hash = FsMetadata::calculate_hash(updated_path.as_str());
+			BucketDB::check_key(&updated_hash, &updated_path, transaction)?;
 			vec_hashes.push(updated_hash.clone());
-			transaction.execute("UPDATE paths SET hash = ?1, path = ?2 WHERE hash = ?3", [updated_hash, updated_path, key_path.key]);
+			transaction.execute("UPDATE paths SET hash = ?1, path = ?2 WHERE hash = ?3", [&updated_hash, &updated_path, &key_path.key])?;
+
+			transaction.execute("UPDATE log_paths SET hash = ?1 WHERE hash = ?2", [&updated_hash, &key_path.key])?;
+
+			let (name, extension, mime_type) = BucketDB::path_names(&updated_path, key_path.is_dir);
+			transaction.execute("UPDATE path_metadata SET name = ?1, extension = ?2, mime_type = ?3 WHERE hash = ?4", (name, extension, mime_type, &updated_hash))?;
 		}
 
 		return Ok(vec_hashes);
 	}
 
 	pub async fn copy_paths(from_path: &str, copy_path: &str, transaction: &Transaction<'_>) -> Result<Vec<String>> {
-		let mut prepare_query = transaction.prepare("SELECT * FROM paths WHERE path LIKE ?1 || '%'").unwrap();
-		let key_paths = prepare_query.query_map([from_path], |row| {
-			Ok(KeyPath {
-				key: row.get(0).unwrap(),
-				path: row.get(1).unwrap(),
-				is_dir: row.get::<_, u8>(2).unwrap() != 0,
-			})
-		}).unwrap();
+		let mut prepare_query = transaction.prepare("SELECT * FROM paths WHERE path = ?1 OR (substr(?1, -1) = '/' AND substr(path, 1, length(?1)) = ?1)")?;
+		let key_paths = prepare_query.query_map([from_path], BucketDB::key_path)?;
 		let mut vec_hashes: Vec<String> = Vec::new();
 
 		for key_path in key_paths {
-			let key_path = key_path.unwrap();
+			let key_path = key_path?;
 			let updated_path = key_path.path.replacen(from_path, copy_path, 1);
 			let updated_hash = FsMetadata::calculate_hash(updated_path.as_str());
+			BucketDB::check_key(&updated_hash, &updated_path, transaction)?;
 			vec_hashes.push(updated_hash.clone());
-			transaction.execute("INSERT INTO paths (hash, path, is_dir) VALUES (?1, ?2, ?3);", [updated_hash, updated_path, key_path.key]).unwrap();
+			transaction.execute("INSERT INTO paths (hash, path, is_dir) VALUES (?1, ?2, ?3);", (&updated_hash, updated_path, key_path.is_dir as i8))?;
+			BucketDB::copy_content(&key_path.key, &updated_hash, transaction)?;
 		}
 
 		return Ok(vec_hashes);
 	}
 
 	pub async fn set_favorite(key: &str, transaction: &Transaction<'_>) -> Result<()> {
-		transaction.execute("INSERT INTO favorite_paths (hash) VALUES (?1);", [key]).unwrap();
+		transaction.execute("INSERT INTO favorite_paths (hash) VALUES (?1);", [key])?;
 		return Ok(());
 	}
 
 	pub async fn unset_favorite(key: &str, transaction: &Transaction<'_>) -> Result<()> {
-		transaction.execute("DELETE from favorite_paths WHERE hash = ?1", [key]).unwrap();
+		transaction.execute("DELETE from favorite_paths WHERE hash = ?1", [key])?;
 		return Ok(());
 	}
 
 	pub async fn get_favorites(transaction: &Transaction<'_>) -> Result<Vec<String>> {
-		let mut prepare_query = transaction.prepare("SELECT path FROM favorite_paths INNER JOIN paths p ON p.hash = favorite_paths.hash").unwrap();
-		let key_paths = prepare_query.query_map([], |row| {
-			Ok(row.get::<_, String>(0))
-		}).unwrap();
-		let mut vec_hashes: Vec<String> = Vec::new();
-
-		for key_path in key_paths {
-			let key_path = key_path.unwrap().unwrap();
-			vec_hashes.push(key_path.clone());
-		}
+		let mut prepare_query = transaction.prepare("SELECT path FROM favorite_paths INNER JOIN paths p ON p.hash = favorite_paths.hash")?;
+		let key_paths = prepare_query.query_map([], |row| row.get::<_, String>(0))?;
 
-		return Ok(vec_hashes);
+		return Ok(key_paths.collect::<rusqlite::Result<Vec<String>>>()?);
 	}
 
 	pub async fn set_delete(key: &str, date: i64, transaction: &Transaction<'_>) -> Result<()> {
-		transaction.execute("INSERT INTO delete_paths (hash, delete_time) VALUES (?1, ?2);", (key, date)).unwrap();
+		transaction.execute("INSERT INTO delete_paths (hash, delete_time) VALUES (?1, ?2);", (key, date))?;
 		return Ok(());
 	}
 
 	pub async fn restore_delete(key: &str, transaction: &Transaction<'_>) -> Result<()> {
-		transaction.execute("DELETE FROM delete_paths WHERE hash = ?1", [key]).unwrap();
+		transaction.execute("DELETE FROM delete_paths WHERE hash = ?1", [key])?;
 		return Ok(());
 	}
 
 	pub async fn get_deletes(transaction: &Transaction<'_>) -> Result<Vec<String>> {
-		let mut prepare_query = transaction.prepare("SELECT path FROM delete_paths INNER JOIN paths p ON p.hash = delete_paths.hash").unwrap();
-		let key_paths = prepare_query.query_map([], |row| {
-			Ok(row.get::<_, String>(0))
-		}).unwrap();
-		let mut vec_hashes: Vec<String> = Vec::new();
-
-		for key_path in key_paths {
-			let key_path = key_path.unwrap().unwrap();
-			vec_hashes.push(key_path.clone());
-		}
+		let mut prepare_query = transaction.prepare("SELECT path FROM delete_paths INNER JOIN paths p ON p.hash = delete_paths.hash")?;
+		let key_paths = prepare_query.query_map([], |row| row.get::<_, String>(0))?;
 
-		return Ok(vec_hashes);
+		return Ok(key_paths.collect::<rusqlite::Result<Vec<String>>>()?);
 	}
 
 	pub async fn clear_trash(transaction: &Transaction<'_>) -> Result<Vec<String>> {
-		let mut prepare_query = transaction.prepare("SELECT hash, path, is_dir from paths p join (SELECT hash AS delete_hash FROM delete_paths ) AS dp on p.hash = dp.delete_hash").unwrap();
-		let key_paths = prepare_query.query_map([], |row| {
-			Ok(KeyPath {
-				key: row.get(0).unwrap(),
-				path: row.get(1).unwrap(),
-				is_dir: row.get::<_, u8>(2).unwrap() != 0,
-			})
-		}).unwrap();
+		let mut prepare_query = transaction.prepare("SELECT hash, path, is_dir from paths p join (SELECT hash AS delete_hash FROM delete_paths ) AS dp on p.hash = dp.delete_hash")?;
+		let key_paths = prepare_query.query_map([], BucketDB::key_path)?;
 		let mut vec_hashes: Vec<String> = Vec::new();
 
 		for key_path in key_paths {
-			let key_path = key_path.unwrap();
+			let key_path = key_path?;
 			vec_hashes.push(key_path.path);
-			transaction.execute("DELETE FROM paths WHERE hash = ?1", [key_path.key]).unwrap();
+			transaction.execute("DELETE FROM paths WHERE hash = ?1", [key_path.key])?;
 		}
 
 		return Ok(vec_hashes);
 	}
 
+	// Removes the path of `key` and, for a directory, everything inside it.
 	pub async fn remove_trash(key: &str, transaction: &Transaction<'_>) -> Result<()> {
-		transaction.execute("DELETE FROM paths WHERE hash = ?1", [key]).unwrap();
+		let path = BucketDB::get_path(key, transaction).await?;
+		transaction.execute("DELETE FROM paths WHERE path = ?1 OR (substr(?1, -1) = '/' AND substr(path, 1, length(?1)) = ?1)", [path])?;
 		return Ok(());
 	}
 
+	// Paths whose trash retention ended before `date`.
+	pub async fn get_date_trash(date: i64, transaction: &Transaction<'_>) -> Result<Vec<String>> {
+		let mut prepare_query = transaction.prepare("SELECT path FROM delete_paths INNER JOIN paths p ON p.hash = delete_paths.hash WHERE delete_time <= ?1")?;
+		let paths = prepare_query.query_map([date], |row| row.get::<_, String>(0))?;
+
+		return Ok(paths.collect::<rusqlite::Result<Vec<String>>>()?);
+	}
+
+	// Removes the trash entries that expired before `date`, together with everything
+	// inside the expired directories, and returns their paths.
 	pub async fn remove_date_trash(date: i64, transaction: &Transaction<'_>) -> Result<Vec<String>> {
-		let mut prepare_query = transaction.prepare("SELECT path, delete_time FROM delete_paths INNER JOIN paths p ON p.hash = delete_paths.hash WHERE delete_time < ?1").unwrap();
-		let key_paths = prepare_query.query_map([], |row| {
-			Ok(KeyPath {
-				key: row.get(0).unwrap(),
-				path: row.get(1).unwrap(),
-				is_dir: row.get::<_, u8>(2).unwrap() != 0,
+		let paths = BucketDB::get_date_trash(date, transaction).await?;
+
+		for path in paths.iter() {
+			transaction.execute("DELETE FROM paths WHERE path = ?1 OR (substr(?1, -1) = '/' AND substr(path, 1, length(?1)) = ?1)", [path])?;
+		}
+
+		return Ok(paths);
+	}
+
+	pub async fn add_version(version: &VersionRow, transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("INSERT INTO versions (version_id, hash, size, etag, create_at, noncurrent_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+							(&version.version_id, &version.key, version.size, &version.etag, version.create_at, version.noncurrent_at))?;
+		return Ok(());
+	}
+
+	fn version_row(row: &Row<'_>) -> rusqlite::Result<VersionRow> {
+		return Ok(VersionRow {
+			version_id: row.get(0)?,
+			key: row.get(1)?,
+			size: row.get(2)?,
+			etag: row.get(3)?,
+			create_at: row.get(4)?,
+			noncurrent_at: row.get(5)?,
+		});
+	}
+
+	pub async fn get_version(key: &str, version_id: &str, transaction: &Transaction<'_>) -> Result<VersionRow> {
+		let version = transaction.query_row("SELECT version_id, hash, size, etag, create_at, noncurrent_at FROM versions WHERE hash = ?1 AND version_id = ?2",
+											[key, version_id], BucketDB::version_row).optional()?;
+
+		return version.ok_or_else(|| SolarError::NoSuchVersion(version_id.to_string()));
+	}
+
+	// Newest first.
+	pub async fn get_versions(key: &str, transaction: &Transaction<'_>) -> Result<Vec<VersionRow>> {
+		let mut prepare_query = transaction.prepare("SELECT version_id, hash, size, etag, create_at, noncurrent_at FROM versions WHERE hash = ?1 ORDER BY noncurrent_at DESC, rowid DESC")?;
+		let versions = prepare_query.query_map([key], BucketDB::version_row)?;
+
+		return Ok(versions.collect::<rusqlite::Result<Vec<VersionRow>>>()?);
+	}
+
+	// The versions of `path` and, for a directory, of everything inside it.
+	pub async fn get_path_versions(path: &str, transaction: &Transaction<'_>) -> Result<Vec<VersionRow>> {
+		let mut prepare_query = transaction.prepare("SELECT version_id, v.hash, size, etag, create_at, noncurrent_at FROM versions v INNER JOIN paths p ON p.hash = v.hash WHERE p.path = ?1 OR (substr(?1, -1) = '/' AND substr(p.path, 1, length(?1)) = ?1)")?;
+		let versions = prepare_query.query_map([path], BucketDB::version_row)?;
+
+		return Ok(versions.collect::<rusqlite::Result<Vec<VersionRow>>>()?);
+	}
+
+	pub async fn remove_version(version_id: &str, transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("DELETE FROM versions WHERE version_id = ?1", [version_id])?;
+		return Ok(());
+	}
+
+	pub async fn set_tags(key: &str, tags: &[(String, String)], transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("DELETE FROM tags WHERE hash = ?1", [key])?;
+		for (name, value) in tags {
+			transaction.execute("INSERT INTO tags (hash, name, value) VALUES (?1, ?2, ?3);", [key, name, value])?;
+		}
+		return Ok(());
+	}
+
+	pub async fn get_tags(key: &str, transaction: &Transaction<'_>) -> Result<Vec<(String, String)>> {
+		let mut prepare_query = transaction.prepare("SELECT name, value FROM tags WHERE hash = ?1 ORDER BY name")?;
+		let tags = prepare_query.query_map([key], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+		return Ok(tags.collect::<rusqlite::Result<Vec<(String, String)>>>()?);
+	}
+
+	pub async fn set_lifecycle_rules(rules: &[LifecycleRule], transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("DELETE FROM lifecycle_rules", ())?;
+		for rule in rules {
+			transaction.execute(
+				"INSERT INTO lifecycle_rules (id, prefix, tag_name, tag_value, expiration_days, noncurrent_days, abort_multipart_days, enabled) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8);",
+				(
+					&rule.id, &rule.prefix,
+					rule.tag.as_ref().map(|tag| &tag.0), rule.tag.as_ref().map(|tag| &tag.1),
+					rule.expiration_days, rule.noncurrent_days, rule.abort_multipart_days,
+					rule.enabled as i8,
+				),
+			)?;
+		}
+		return Ok(());
+	}
+
+	pub async fn get_lifecycle_rules(transaction: &Transaction<'_>) -> Result<Vec<LifecycleRule>> {
+		let mut prepare_query = transaction.prepare("SELECT id, prefix, tag_name, tag_value, expiration_days, noncurrent_days, abort_multipart_days, enabled FROM lifecycle_rules ORDER BY id")?;
+		let rules = prepare_query.query_map([], |row| {
+			let tag_name: Option<String> = row.get(2)?;
+			let tag_value: Option<String> = row.get(3)?;
+			Ok(LifecycleRule {
+				id: row.get(0)?,
+				prefix: row.get(1)?,
+				tag: tag_name.map(|name| (name, tag_value.unwrap_or_default())),
+				expiration_days: row.get(4)?,
+				noncurrent_days: row.get(5)?,
+				abort_multipart_days: row.get(6)?,
+				enabled: row.get::<_, u8>(7)? != 0,
 			})
-		}).unwrap();
-		let mut vec_hashes: Vec<String> = Vec::new();
+		})?;
 
-		for key_path in key_paths {
-			let key_path = key_path.unwrap();
-			vec_hashes.push(key_path.path);
-			transaction.execute("DELETE FROM delete_paths WHERE hash = ?1", [key_path.key]).unwrap();
+		return Ok(rules.collect::<rusqlite::Result<Vec<LifecycleRule>>>()?);
+	}
+
+	// Files outside the trash whose path starts with `prefix` and that carry `tag`, when given.
+	pub async fn get_rule_paths(prefix: &str, tag: Option<&(String, String)>, transaction: &Transaction<'_>) -> Result<Vec<KeyPath>> {
+		let mut prepare_query = transaction.prepare("SELECT hash, path, is_dir FROM paths p
+			WHERE is_dir = 0 AND substr(path, 1, length(?1)) = ?1
+			AND hash NOT IN (SELECT hash FROM delete_paths)
+			AND (?2 IS NULL OR EXISTS(SELECT 1 FROM tags t WHERE t.hash = p.hash AND t.name = ?2 AND t.value = ?3))")?;
+		let key_paths = prepare_query.query_map((prefix, tag.map(|tag| &tag.0), tag.map(|tag| &tag.1)), BucketDB::key_path)?;
+
+		return Ok(key_paths.collect::<rusqlite::Result<Vec<KeyPath>>>()?);
+	}
+
+	// Versions that became noncurrent before `date` under the same conditions as `get_rule_paths`.
+	pub async fn get_rule_versions(prefix: &str, tag: Option<&(String, String)>, date: i64, transaction: &Transaction<'_>) -> Result<Vec<VersionRow>> {
+		let mut prepare_query = transaction.prepare("SELECT version_id, v.hash, size, etag, create_at, noncurrent_at FROM versions v
+			INNER JOIN paths p ON p.hash = v.hash
+			WHERE substr(p.path, 1, length(?1)) = ?1 AND v.noncurrent_at <= ?4
+			AND (?2 IS NULL OR EXISTS(SELECT 1 FROM tags t WHERE t.hash = p.hash AND t.name = ?2 AND t.value = ?3))")?;
+		let versions = prepare_query.query_map((prefix, tag.map(|tag| &tag.0), tag.map(|tag| &tag.1), date), BucketDB::version_row)?;
+
+		return Ok(versions.collect::<rusqlite::Result<Vec<VersionRow>>>()?);
+	}
+
+	// Up to `limit` files after `after` in path order whose path starts with `prefix`,
+	// leaving out the trashed files and the files of trashed directories.
+	pub async fn get_object_paths(prefix: &str, after: &str, limit: usize, transaction: &Transaction<'_>) -> Result<Vec<KeyPath>> {
+		let mut prepare_query = transaction.prepare(&format!("SELECT hash, path, is_dir FROM paths p
+			WHERE is_dir = 0 AND path >= ?1 AND substr(path, 1, length(?1)) = ?1 AND path > ?2
+			AND NOT {TRASHED_PATH}
+			ORDER BY path LIMIT ?3"))?;
+		let key_paths = prepare_query.query_map((prefix, after, limit as i64), BucketDB::key_path)?;
+
+		return Ok(key_paths.collect::<rusqlite::Result<Vec<KeyPath>>>()?);
+	}
+
+	pub async fn set_metadata(metadata: &PathMetadata, transaction: &Transaction<'_>) -> Result<()> {
+		let (name, extension, mime_type) = BucketDB::path_names(&metadata.path, metadata.is_dir);
+		transaction.execute("INSERT OR REPLACE INTO path_metadata (hash, name, extension, mime_type, size, create_at, update_at)
+			VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+			(&metadata.key, name, extension, mime_type, metadata.size as i64, metadata.create_at, metadata.update_at))?;
+		return Ok(());
+	}
+
+	pub async fn get_unindexed_paths(transaction: &Transaction<'_>) -> Result<Vec<KeyPath>> {
+		let mut prepare_query = transaction.prepare("SELECT hash, path, is_dir FROM paths WHERE hash NOT IN (SELECT hash FROM path_metadata)")?;
+		let key_paths = prepare_query.query_map([], BucketDB::key_path)?;
+
+		return Ok(key_paths.collect::<rusqlite::Result<Vec<KeyPath>>>()?);
+	}
+
+	// Up to `limit` paths that match `options`, in the order of the sort and then of the
+	// path, starting after `after` when given.
+	// The first `limit` paths that pass `is_visible`, read one by one like in `search_content`.
+	pub async fn search_paths(options: &SearchOptions, after: Option<&SearchToken>, limit: usize, is_visible: impl Fn(&str) -> bool, transaction: &Transaction<'_>) -> Result<Vec<SearchItem>> {
+		let mut conditions: Vec<String> = Vec::new();
+		let mut params: Vec<Value> = Vec::new();
+		// Adds a condition whose `?` placeholders take `values` in order.
+		let mut condition = |sql: &str, values: Vec<Value>| {
+			let mut parts = sql.split('?');
+			let mut numbered = parts.next().unwrap_or_default().to_string();
+			for (part, value) in parts.zip(values) {
+				params.push(value);
+				numbered.push_str(&format!("?{}{}", params.len(), part));
+			}
+			conditions.push(numbered);
+		};
+
+		if let Some(name) = &options.name {
+			condition("instr(lower(m.name), lower(?)) > 0", vec![Value::Text(name.clone())]);
+		}
+		if let Some(name_glob) = &options.name_glob {
+			condition("m.name GLOB ?", vec![Value::Text(name_glob.clone())]);
+		}
+		if let Some(mime_prefix) = &options.mime_prefix {
+			condition("substr(m.mime_type, 1, length(?)) = ?", vec![Value::Text(mime_prefix.clone()), Value::Text(mime_prefix.clone())]);
+		}
+		if let Some(extension) = &options.extension {
+			condition("lower(m.extension) = lower(?)", vec![Value::Text(extension.trim_start_matches('.').to_string())]);
+		}
+		match options.kind {
+			ListKind::All => {},
+			ListKind::Directories => condition("p.is_dir = 1", vec![]),
+			ListKind::Files => condition("p.is_dir = 0", vec![]),
+		}
+		let ranges = [
+			("m.size >= ?", options.min_size), ("m.size <= ?", options.max_size),
+			("m.create_at >= ?", options.created_after), ("m.create_at <= ?", options.created_before),
+			("m.update_at >= ?", options.modified_after), ("m.update_at <= ?", options.modified_before),
+		];
+		for (sql, value) in ranges {
+			if let Some(value) = value {
+				condition(sql, vec![Value::Integer(value as i64)]);
+			}
+		}
+		if let Some(favorite) = options.favorite {
+			condition("EXISTS(SELECT 1 FROM favorite_paths f WHERE f.hash = p.hash) = ?", vec![Value::Integer(favorite as i64)]);
+		}
+		if let Some(trashed) = options.trashed {
+			condition(&format!("{TRASHED_PATH} = ?"), vec![Value::Integer(trashed as i64)]);
 		}
 
-		return Ok(vec_hashes);
+		let column = match options.sort {
+			SearchSort::Name => "m.name",
+			SearchSort::Path => "p.path",
+			SearchSort::Size => "m.size",
+			SearchSort::CreateAt => "m.create_at",
+			SearchSort::UpdateAt => "m.update_at",
+		};
+		let (order, compare) = if options.descending { ("DESC", "<") } else { ("ASC", ">") };
+		if let Some(after) = after {
+			let sql = format!("({column} {compare} ? OR ({column} = ? AND p.path {compare} ?))");
+			condition(&sql, vec![after.value(), after.value(), Value::Text(after.path.clone())]);
+		}
+
+		let where_clause = match conditions.is_empty() {
+			true => "".to_string(),
+			false => format!("WHERE {}", conditions.join(" AND ")),
+		};
+		let mut prepare_query = transaction.prepare(&format!("SELECT p.hash, p.path, p.is_dir, m.name, m.mime_type, m.size, m.create_at, m.update_at,
+			EXISTS(SELECT 1 FROM favorite_paths f WHERE f.hash = p.hash), {TRASHED_PATH}
+			FROM paths p INNER JOIN path_metadata m ON m.hash = p.hash
+			{where_clause}
+			ORDER BY {column} {order}, p.path {order}"))?;
+		let rows = prepare_query.query_map(rusqlite::params_from_iter(params), |row| {
+			return Ok(SearchItem {
+				key: row.get(0)?,
+				path: row.get(1)?,
+				is_dir: row.get::<_, u8>(2)? != 0,
+				name: row.get(3)?,
+				mime_type: row.get(4)?,
+				size: row.get::<_, i64>(5)? as u64,
+				create_at: row.get::<_, i64>(6)? as u64,
+				update_at: row.get::<_, i64>(7)? as u64,
+				is_favorite: row.get(8)?,
+				is_delete: row.get(9)?,
+			});
+		})?;
+
+		let mut items = Vec::new();
+		for row in rows {
+			let item = row?;
+			if is_visible(&item.path) {
+				items.push(item);
+				if items.len() == limit {
+					break;
+				}
+			}
+		}
+
+		return Ok(items);
+	}
+
+	// The name, extension and mime type that search uses for `path`.
+	fn path_names(path: &str, is_dir: bool) -> (String, String, Option<String>) {
+		let name = Path::new(path).file_name().unwrap_or_default().to_string_lossy().to_string();
+		if is_dir {
+			return (name, "".to_string(), None);
+		}
+
+		let extension = Path::new(&name).extension().unwrap_or_default().to_string_lossy().to_string();
+		let mime_type = mime_guess::from_path(&name).first().unwrap_or(mime::TEXT_PLAIN).to_string();
+		return (name, extension, Some(mime_type));
+	}
+
+	pub async fn get_all_paths(transaction: &Transaction<'_>) -> Result<Vec<KeyPath>> {
+		let mut prepare_query = transaction.prepare("SELECT hash, path, is_dir FROM paths")?;
+		let key_paths = prepare_query.query_map([], BucketDB::key_path)?;
+
+		return Ok(key_paths.collect::<rusqlite::Result<Vec<KeyPath>>>()?);
+	}
+
+	pub async fn remove_key(key: &str, transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("DELETE FROM paths WHERE hash = ?1", [key])?;
+		return Ok(());
+	}
+
+	pub async fn get_file_paths(transaction: &Transaction<'_>) -> Result<Vec<KeyPath>> {
+		let mut prepare_query = transaction.prepare("SELECT hash, path, is_dir FROM paths WHERE is_dir = 0")?;
+		let key_paths = prepare_query.query_map([], BucketDB::key_path)?;
+
+		return Ok(key_paths.collect::<rusqlite::Result<Vec<KeyPath>>>()?);
+	}
+
+	pub async fn set_content(key: &str, content: &str, transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("INSERT INTO content_keys (hash) VALUES (?1)", [key])?;
+		transaction.execute("INSERT INTO content_index (rowid, content) VALUES (?1, ?2)", (transaction.last_insert_rowid(), content))?;
+		return Ok(());
+	}
+
+	// The `content_keys_delete` trigger drops the indexed content with the key.
+	pub async fn remove_content(key: &str, transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("DELETE FROM content_keys WHERE hash = ?1", [key])?;
+		return Ok(());
+	}
+
+	pub async fn clear_content(transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("DELETE FROM content_keys", [])?;
+		return Ok(());
+	}
+
+	fn copy_content(from_key: &str, to_key: &str, transaction: &Transaction<'_>) -> Result<()> {
+		let id: Option<i64> = transaction.query_row("SELECT id FROM content_keys WHERE hash = ?1", [from_key], |row| row.get(0)).optional()?;
+		if let Some(id) = id {
+			transaction.execute("INSERT INTO content_keys (hash) VALUES (?1)", [to_key])?;
+			transaction.execute("INSERT INTO content_index (rowid, content) SELECT ?1, content FROM content_index WHERE rowid = ?2", (transaction.last_insert_rowid(), id))?;
+		}
+
+		return Ok(());
+	}
+
+	// `query` is an FTS5 query. The files in the trash are left out.
+	// The first `limit` matches whose path passes `is_visible`. The rows are read one by
+	// one, so the ones left out don't take the place of the others.
+	pub async fn search_content(query: &str, limit: usize, is_visible: impl Fn(&str) -> bool, transaction: &Transaction<'_>) -> Result<Vec<ContentMatch>> {
+		let mut prepare_query = transaction.prepare(&format!("SELECT p.hash, p.path, snippet(content_index, 0, ?2, ?3, '…', 16)
+			FROM content_index INNER JOIN content_keys c ON c.id = content_index.rowid
+			INNER JOIN paths p ON p.hash = c.hash
+			WHERE content_index MATCH ?1 AND NOT {TRASHED_PATH}
+			ORDER BY rank"))?;
+		let rows = prepare_query.query_map((query, HIGHLIGHT_START, HIGHLIGHT_END), |row| {
+			return Ok(ContentMatch {
+				key: row.get(0)?,
+				path: row.get(1)?,
+				snippet: row.get(2)?,
+			});
+		})?;
+
+		let mut matches = Vec::new();
+		for row in rows {
+			let item = row?;
+			if is_visible(&item.path) {
+				matches.push(item);
+				if matches.len() == limit {
+					break;
+				}
+			}
+		}
+
+		return Ok(matches);
+	}
+
+	// Runs outside of a transaction, so the intent is committed at once.
+	pub async fn add_intent(id: &str, process: &str, operation: &str, create_at: i64, connection: &Connection) -> Result<()> {
+		connection.execute("INSERT INTO intents (id, process, operation, create_at) VALUES (?1, ?2, ?3, ?4)", (id, process, operation, create_at))?;
+		return Ok(());
+	}
+
+	pub async fn remove_intent(id: &str, connection: &Connection) -> Result<()> {
+		connection.execute("DELETE FROM intents WHERE id = ?1", [id])?;
+		return Ok(());
+	}
+
+	// The intents of the other processes with the process that logged them, the newest first.
+	pub(crate) async fn get_intents(process: &str, connection: &Connection) -> Result<Vec<(String, String, Intent)>> {
+		let mut prepare_query = connection.prepare("SELECT id, process, operation FROM intents WHERE process != ?1 ORDER BY create_at DESC, rowid DESC")?;
+		let rows = prepare_query.query_map([process], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?;
+
+		let mut intents = Vec::new();
+		for row in rows {
+			let (id, process, operation) = row?;
+			intents.push((id, process, serde_json::from_str(&operation)?));
+		}
+
+		return Ok(intents);
+	}
+
+	// The id of `entry` is left to the database.
+	pub async fn add_log(entry: &AuditEntry, transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("INSERT INTO log_paths (hash, path, operation, author_uuid, create_at, comment) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+							(&entry.key, &entry.path, entry.operation.as_str(), &entry.author_uuid, entry.create_at, &entry.comment))?;
+		return Ok(());
+	}
+
+	// The entries of `key`, or of every key, the newest first.
+	pub async fn get_logs(key: Option<&str>, filter: &AuditFilter, transaction: &Transaction<'_>) -> Result<Vec<AuditEntry>> {
+		let mut prepare_query = transaction.prepare("SELECT id, hash, path, operation, author_uuid, create_at, comment FROM log_paths
+			WHERE (?1 IS NULL OR hash = ?1) AND (?2 IS NULL OR create_at >= ?2) AND (?3 IS NULL OR create_at <= ?3)
+			ORDER BY create_at DESC, id DESC LIMIT ?4")?;
+		let limit = filter.limit.map(|limit| limit as i64).unwrap_or(-1);
+		let entries = prepare_query.query_map((key, filter.since, filter.until, limit), |row| {
+			return Ok(AuditEntry {
+				id: row.get(0)?,
+				key: row.get(1)?,
+				path: row.get(2)?,
+				operation: row.get(3)?,
+				author_uuid: row.get(4)?,
+				create_at: row.get(5)?,
+				comment: row.get(6)?,
+			});
+		})?;
+
+		return Ok(entries.collect::<rusqlite::Result<Vec<AuditEntry>>>()?);
+	}
+
+	pub async fn set_grant(grant: &Grant, transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("INSERT INTO grants (user_uuid, prefix, permission) VALUES (?1, ?2, ?3)
+			ON CONFLICT (user_uuid, prefix) DO UPDATE SET permission = excluded.permission",
+							(&grant.user_uuid, &grant.prefix, grant.permission.as_str()))?;
+		return Ok(());
+	}
+
+	pub async fn remove_grant(user_uuid: &str, prefix: &str, transaction: &Transaction<'_>) -> Result<()> {
+		transaction.execute("DELETE FROM grants WHERE user_uuid = ?1 AND prefix = ?2", (user_uuid, prefix))?;
+		return Ok(());
+	}
+
+	// The grants of `user_uuid`, or of every user, ordered by prefix.
+	pub async fn get_grants(user_uuid: Option<&str>, connection: &Connection) -> Result<Vec<Grant>> {
+		let mut prepare_query = connection.prepare("SELECT user_uuid, prefix, permission FROM grants
+			WHERE ?1 IS NULL OR user_uuid = ?1 ORDER BY prefix, user_uuid")?;
+		let grants = prepare_query.query_map([user_uuid], |row| {
+			return Ok(Grant {
+				user_uuid: row.get(0)?,
+				prefix: row.get(1)?,
+				permission: row.get(2)?,
+			});
+		})?;
+
+		return Ok(grants.collect::<rusqlite::Result<Vec<Grant>>>()?);
 	}
 }
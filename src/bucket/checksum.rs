@@ -0,0 +1,76 @@
+use std::str::FromStr;
+
+use md5::{Digest, Md5};
+use sha2::Sha256;
+
+use crate::error::SolarError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+	Sha256,
+	Crc32c,
+}
+
+impl ChecksumAlgorithm {
+	pub fn as_str(&self) -> &'static str {
+		return match self {
+			ChecksumAlgorithm::Sha256 => "sha256",
+			ChecksumAlgorithm::Crc32c => "crc32c",
+		};
+	}
+}
+
+impl FromStr for ChecksumAlgorithm {
+	type Err = SolarError;
+
+	fn from_str(value: &str) -> Result<ChecksumAlgorithm, SolarError> {
+		return match value.to_lowercase().as_str() {
+			"sha256" => Ok(ChecksumAlgorithm::Sha256),
+			"crc32c" => Ok(ChecksumAlgorithm::Crc32c),
+			_ => Err(SolarError::InvalidInput(format!("Unknown checksum algorithm {}", value))),
+		};
+	}
+}
+
+/// The digests of an object as lowercase hex. The MD5 is always computed and serves
+/// as the ETag, the others only when the bucket asks for them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checksums {
+	pub md5: String,
+	pub sha256: Option<String>,
+	pub crc32c: Option<String>,
+}
+
+pub(crate) struct ChecksumHasher {
+	md5: Md5,
+	sha256: Option<Sha256>,
+	crc32c: Option<u32>,
+}
+
+impl ChecksumHasher {
+	pub(crate) fn new(algorithms: &[ChecksumAlgorithm]) -> ChecksumHasher {
+		return ChecksumHasher {
+			md5: Md5::new(),
+			sha256: algorithms.contains(&ChecksumAlgorithm::Sha256).then(Sha256::new),
+			crc32c: algorithms.contains(&ChecksumAlgorithm::Crc32c).then_some(0),
+		};
+	}
+
+	pub(crate) fn update(&mut self, data: &[u8]) {
+		self.md5.update(data);
+		if let Some(sha256) = &mut self.sha256 {
+			sha256.update(data);
+		}
+		if let Some(crc32c) = &mut self.crc32c {
+			*crc32c = crc32c::crc32c_append(*crc32c, data);
+		}
+	}
+
+	pub(crate) fn finalize(self) -> Checksums {
+		return Checksums {
+			md5: hex::encode(self.md5.finalize()),
+			sha256: self.sha256.map(|sha256| hex::encode(sha256.finalize())),
+			crc32c: self.crc32c.map(|crc32c| hex::encode(crc32c.to_be_bytes())),
+		};
+	}
+}
@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use rusqlite::Transaction;
+use tokio::fs;
+
+use crate::bucket::access::Permission;
+use crate::bucket::bucket::Bucket;
+use crate::bucket::bucket_db::BucketDB;
+use crate::bucket::fs_metadata::FsMetadata;
+use crate::error::Result;
+
+// Larger files are left out of the content index.
+pub const MAX_INDEXED_SIZE: u64 = 1024 * 1024;
+pub const CONTENT_SEARCH_LIMIT: usize = 100;
+pub const HIGHLIGHT_START: &str = "<mark>";
+pub const HIGHLIGHT_END: &str = "</mark>";
+
+// Source code and config files that `mime_guess` doesn't report as text.
+const TEXT_EXTENSIONS: [&str; 24] = [
+	"c", "cpp", "cs", "go", "h", "hpp", "java", "js", "json", "jsx", "kt", "md", "php",
+	"py", "rb", "rs", "sh", "sql", "swift", "toml", "ts", "tsx", "yaml", "yml",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentMatch {
+	pub key: String,
+	pub path: String,
+	// A part of the content around the match, with the matched words between
+	// `HIGHLIGHT_START` and `HIGHLIGHT_END`.
+	pub snippet: String,
+}
+
+/// The optional full-text index of the bucket lives in the `content_index` FTS5 table.
+/// Its rows are tied to the paths through `content_keys`, so renames and moves keep
+/// them, removals drop them and copies duplicate them without reading the files again.
+impl Bucket {
+	/// Turns the content index on, indexing the files already in the bucket, or off,
+	/// dropping the index.
+	pub async fn set_content_index(&self, enabled: bool) -> Result<()> {
+		self.authorize("", Permission::Admin).await?;
+		FsMetadata::new(&self.path).await?.set_content_index(enabled).await?;
+
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		BucketDB::clear_content(&transaction).await?;
+		if enabled {
+			for key_path in BucketDB::get_file_paths(&transaction).await? {
+				self.index_content(&key_path.key, &key_path.path, &transaction).await?;
+			}
+		}
+		transaction.commit()?;
+
+		return Ok(());
+	}
+
+	pub async fn is_content_index(&self) -> Result<bool> {
+		return FsMetadata::new(&self.path).await?.is_content_index().await;
+	}
+
+	/// Finds the files outside the trash that contain every word of `query` and that the
+	/// user can read, the best `CONTENT_SEARCH_LIMIT` matches first.
+	pub async fn search_content(&self, query: &str) -> Result<Vec<ContentMatch>> {
+		// Every word is quoted, so the FTS5 query syntax can't make the query fail.
+		let query = query.split_whitespace()
+			.map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+			.collect::<Vec<String>>()
+			.join(" ");
+		if query.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		let access = self.access().await?;
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		let is_visible = |path: &str| access.allows(path, Permission::Read);
+		let matches = BucketDB::search_content(&query, CONTENT_SEARCH_LIMIT, is_visible, &transaction).await?;
+		transaction.commit()?;
+
+		return Ok(matches);
+	}
+
+	// Puts the content of the file at `path` in the index when the index is on and
+	// the file is text, otherwise makes sure it has no entry.
+	pub(crate) async fn index_content(&self, key: &str, path: &str, transaction: &Transaction<'_>) -> Result<()> {
+		BucketDB::remove_content(key, transaction).await?;
+		if !self.is_content_index().await? || !is_text_like(path) {
+			return Ok(());
+		}
+
+		let file_path = Path::new(&self.path).join("files").join(path);
+		if fs::metadata(&file_path).await?.len() > MAX_INDEXED_SIZE {
+			return Ok(());
+		}
+
+		let content = fs::read(&file_path).await?;
+		return BucketDB::set_content(key, &String::from_utf8_lossy(&content), transaction).await;
+	}
+}
+
+// Whether the file at `path` is text, judging by its extension.
+fn is_text_like(path: &str) -> bool {
+	let extension = Path::new(path).extension().unwrap_or_default().to_string_lossy().to_lowercase();
+	if TEXT_EXTENSIONS.contains(&extension.as_str()) {
+		return true;
+	}
+
+	return mime_guess::from_path(path).first()
+		.map(|mime| mime.type_() == mime_guess::mime::TEXT || mime.suffix() == Some(mime_guess::mime::JSON) || mime.suffix() == Some(mime_guess::mime::XML))
+		.unwrap_or(false);
+}
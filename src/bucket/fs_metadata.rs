@@ -1,22 +1,31 @@
-use std::collections::hash_map::DefaultHasher;
 use std::ffi::OsStr;
-use std::hash::{Hash, Hasher};
-use std::io::{Error, ErrorKind, Result};
+use std::io;
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::str;
-use std::time::UNIX_EPOCH;
+use std::str::{self, FromStr};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use byte_unit::Byte;
 use chrono::Utc;
 use futures::future::{BoxFuture, FutureExt};
 use mime_guess::mime;
 use regex::Regex;
+use sha2::{Digest, Sha256};
 use tokio::fs;
+use tokio::sync::Mutex;
 use xattr::{get, remove, set};
 
+use crate::bucket::checksum::{ChecksumAlgorithm, Checksums};
+use crate::error::{Result, SolarError};
 use crate::storage::space::Space;
 
+// The attributes that describe the content of a file, kept by a copy.
+const CONTENT_ATTRS: [&str; 3] = ["user.etag", "user.checksum_sha256", "user.checksum_crc32c"];
+
+// Makes reading and writing back `user.usage_space` one step, so that writes running
+// side by side don't lose each other's reservations.
+static USAGE_LOCK: Mutex<()> = Mutex::const_new(());
+
 pub struct Size {
 	pub available_space: u64,
 	pub usage_space: u64,
@@ -33,6 +42,9 @@ pub struct FsItem {
 	pub is_delete: bool,
 	pub see_time: u64,
 	pub delete_at: Option<u64>,
+	pub etag: Option<String>,
+	pub sha256: Option<String>,
+	pub crc32c: Option<String>,
 	pub buffer: Option<Vec<u8>>,
 }
 
@@ -40,7 +52,8 @@ pub struct PropertiesItem {
 	pub name: String,
 	pub hash: String,
 	pub is_dir: bool,
-	pub owner: u64,
+	// The uuid of the user who created the item.
+	pub owner: Option<String>,
 	pub create_at: u64,
 	pub update_at: u64,
 	pub see_time: u64,
@@ -48,6 +61,9 @@ pub struct PropertiesItem {
 	pub description: String,
 	pub is_delete: bool,
 	pub size: u64,
+	pub etag: Option<String>,
+	pub sha256: Option<String>,
+	pub crc32c: Option<String>,
 }
 
 #[derive(Debug)]
@@ -60,141 +76,212 @@ pub struct FsMetadata {
 impl FsMetadata {
 	pub async fn new(path: impl AsRef<Path>) -> Result<FsMetadata> {
 		let path_regex = Regex::new(r".*([\da-f]{8}-[\da-f]{4}-[\da-f]{4}-[\da-f]{4}-[\da-f]{12})(/files/)").unwrap();
-		let base_path = path_regex.replace(path.as_ref().to_str().unwrap(), "");
+		let base_path = path_regex.replace(&path.as_ref().to_string_lossy(), "").to_string();
 
 		return Ok(FsMetadata {
 			path: path.as_ref().to_path_buf(),
 			is_dir: fs::metadata(&path).await?.is_dir(),
-			base_path: base_path.to_string(),
+			base_path,
 		});
 	}
 
+	fn get_attr(&self, name: &str) -> Result<Option<String>> {
+		let value = get(&self.path, name).map_err(SolarError::Xattr)?;
+		return Ok(value.map(|value| String::from_utf8_lossy(&value).to_string()));
+	}
+
+	fn parse_attr<T: FromStr>(&self, name: &str) -> Result<Option<T>> {
+		return match self.get_attr(name)? {
+			None => Ok(None),
+			Some(value) => value.parse::<T>().map(Some).map_err(|_| SolarError::Xattr(
+				io::Error::new(io::ErrorKind::InvalidData, format!("Invalid value of {}: {}", name, value)),
+			)),
+		};
+	}
+
+	fn set_attr(&self, name: &str, value: &str) -> Result<()> {
+		return set(&self.path, name, value.as_bytes()).map_err(SolarError::Xattr);
+	}
+
 	pub async fn increase_size(&self, add_size: u64) -> Result<u64> {
-		let current_size = &self.get_space().await.unwrap();
+		let _guard = USAGE_LOCK.lock().await;
+		let current_size = &self.get_space().await?;
 
 		let new_size = current_size.usage_space + add_size;
 		if new_size > current_size.available_space {
-			return Err(Error::new(ErrorKind::StorageFull, "There is no free space to Add file!"));
+			return Err(SolarError::QuotaExceeded("There is no free space to Add file!".to_string()));
 		}
-		set(&self.path, "user.usage_space", new_size.to_string().as_bytes());
+		self.set_attr("user.usage_space", &new_size.to_string())?;
 
 		return Ok(new_size);
 	}
 
 	pub async fn decrease_size(&self, delete_size: u64) -> Result<u64> {
-		let current_size = &self.get_space().await.unwrap();
+		let _guard = USAGE_LOCK.lock().await;
+		let current_size = &self.get_space().await?;
 
-		let new_size = current_size.usage_space - delete_size;
-		set(&self.path, "user.usage_space", new_size.to_string().as_bytes());
+		let new_size = current_size.usage_space.saturating_sub(delete_size);
+		self.set_attr("user.usage_space", &new_size.to_string())?;
 		return Ok(new_size);
 	}
 
+	pub async fn get_size(&self) -> Result<u64> {
+		if self.is_dir {
+			return Space::dir_size(&self.path).await;
+		}
+
+		return Ok(fs::metadata(&self.path).await?.len());
+	}
+
 	pub async fn set_delete(&self) -> Result<i64> {
 		let delete_at = Utc::now().timestamp();
 
-		set(&self.path, "user.is_delete", "true".as_bytes())?;
-		set(&self.path, "user.delete_time", delete_at.to_string().as_bytes())?;
+		self.set_attr("user.is_delete", "true")?;
+		self.set_attr("user.delete_time", &delete_at.to_string())?;
 
 		return Ok(delete_at);
 	}
 
 	pub async fn restore_delete(&self) -> Result<()> {
-		remove(&self.path, "user.is_delete")?;
-		remove(&self.path, "user.delete_time")?;
+		remove(&self.path, "user.is_delete").map_err(SolarError::Xattr)?;
+		remove(&self.path, "user.delete_time").map_err(SolarError::Xattr)?;
 		return Ok(());
 	}
 
 	pub async fn get_delete_time(&self) -> Result<Option<i64>> {
-		const IS_DELETE: i64 = 0;
+		let delete_time = self.parse_attr::<i64>("user.delete_time")?;
+		return Ok(delete_time.filter(|delete_time| *delete_time != 0));
+	}
 
-		let metadata_delete_value = get(&self.path, "user.delete_time")
-			.unwrap_or(Some(IS_DELETE.to_string().as_bytes().to_vec()))
-			.unwrap_or(IS_DELETE.to_string().as_bytes().to_vec());
+	pub async fn set_available_space(&self, available_space: u64) -> Result<()> {
+		return self.set_attr("user.available_space", &available_space.to_string());
+	}
 
-		let metadata_value = str::from_utf8(&metadata_delete_value)
-			.unwrap()
-			.parse::<i64>()
-			.unwrap();
+	pub async fn set_usage_space(&self, usage_space: u64) -> Result<()> {
+		return self.set_attr("user.usage_space", &usage_space.to_string());
+	}
 
-		if metadata_value != 0 {
-			return Ok(Some(metadata_value));
+	pub async fn get_space(&self) -> Result<Size> {
+		if !&self.is_dir {
+			return Err(SolarError::NotADirectory("This is not a directory!".to_string()));
 		}
 
-		return Ok(None);
+		let available_space = self.parse_attr::<u64>("user.available_space")?.unwrap_or(0);
+		let usage_space = match self.parse_attr::<u64>("user.usage_space")? {
+			Some(usage_space) => usage_space,
+			None => {
+				let size = Space::dir_size(&self.path).await?;
+				self.set_attr("user.usage_space", &size.to_string())?;
+				size
+			},
+		};
+
+		return Ok(Size {
+			available_space,
+			usage_space,
+		});
 	}
 
-	pub async fn set_available_space(&self, available_space: u64) -> Result<()> {
-		set(&self.path, "user.available_space", available_space.to_string().as_bytes());
-		return Ok(());
+	pub async fn is_delete(&self) -> Result<bool> {
+		const IS_DELETE: &str = "false";
+
+		let metadata_value = self.get_attr("user.is_delete")?;
+		return Ok(metadata_value.map(|value| value != IS_DELETE).unwrap_or(false));
 	}
 
-	pub async fn get_space(&self) -> Result<Size> {
-		const AVAILABLE_SPACE: u64 = 0;
-		const NONE_MSG: &str = "none_space";
+	pub async fn set_favorite(&self) -> Result<()> {
+		return self.set_attr("user.is_favorite", "true");
+	}
 
-		if !&self.is_dir {
-			return Err(Error::new(ErrorKind::NotADirectory, "This is not a directory!"));
-		}
+	pub async fn unset_favorite(&self) -> Result<()> {
+		return self.set_attr("user.is_favorite", "false");
+	}
 
-		let path = PathBuf::from(&self.path);
+	pub async fn is_favorite(&self) -> Result<bool> {
+		const IS_FAVORITE: &str = "false";
 
-		let available_space = get(&path, "user.available_space")
-			.unwrap_or(Some(AVAILABLE_SPACE.to_string().as_bytes().to_vec()))
-			.unwrap_or(AVAILABLE_SPACE.to_string().as_bytes().to_vec());
-		let available_space = str::from_utf8(&available_space).unwrap();
+		let metadata_value = self.get_attr("user.is_favorite")?;
+		return Ok(metadata_value.map(|value| value != IS_FAVORITE).unwrap_or(false));
+	}
 
+	pub async fn set_trash_retention(&self, retention: u64) -> Result<()> {
+		return self.set_attr("user.trash_retention", &retention.to_string());
+	}
 
-		let usage_space = get(&path, "user.usage_space")
-			.unwrap_or(Some(NONE_MSG.as_bytes().to_vec()))
-			.unwrap_or(NONE_MSG.as_bytes().to_vec());
-		let usage_space = str::from_utf8(&usage_space).unwrap();
+	pub async fn get_trash_retention(&self) -> Result<Option<u64>> {
+		return self.parse_attr::<u64>("user.trash_retention");
+	}
 
-		if usage_space == NONE_MSG {
-			let size = Space::dir_size(&path).await;
-			set(&path, "user.usage_space", size.to_string().as_bytes());
-			return Ok(Size {
-				available_space: available_space.parse::<u64>().unwrap(),
-				usage_space: size,
-			});
-		}
+	pub async fn set_versioning(&self, enabled: bool) -> Result<()> {
+		return self.set_attr("user.versioning", &enabled.to_string());
+	}
 
-		return Ok(Size {
-			available_space: available_space.parse::<u64>().unwrap(),
-			usage_space: usage_space.parse::<u64>().unwrap(),
-		});
+	pub async fn is_versioning(&self) -> Result<bool> {
+		return Ok(self.parse_attr::<bool>("user.versioning")?.unwrap_or(false));
 	}
 
-	pub async fn is_delete(&self) -> Result<bool> {
-		const IS_DELETE: &str = "false";
+	pub async fn set_content_index(&self, enabled: bool) -> Result<()> {
+		return self.set_attr("user.content_index", &enabled.to_string());
+	}
 
-		let metadata_vec_value = get(&self.path, "user.is_delete")
-			.unwrap_or(Some(IS_DELETE.as_bytes().to_vec()))
-			.unwrap_or(IS_DELETE.as_bytes().to_vec());
-		let metadata_value = str::from_utf8(&metadata_vec_value).unwrap();
+	pub async fn is_content_index(&self) -> Result<bool> {
+		return Ok(self.parse_attr::<bool>("user.content_index")?.unwrap_or(false));
+	}
 
-		return Ok(metadata_value != IS_DELETE);
+	pub async fn set_owner(&self, owner_uuid: &str) -> Result<()> {
+		return self.set_attr("user.owner", owner_uuid);
 	}
 
-	pub async fn set_favorite(&self) -> Result<()> {
-		set(&self.path, "user.is_favorite", "true".to_string().as_bytes());
+	pub async fn get_owner(&self) -> Result<Option<String>> {
+		return self.get_attr("user.owner");
+	}
 
-		return Ok(());
+	pub async fn set_etag(&self, etag: &str) -> Result<()> {
+		return self.set_attr("user.etag", etag);
 	}
 
-	pub async fn unset_favorite(&self) -> Result<()> {
-		set(&self.path, "user.is_favorite", "false".to_string().as_bytes());
+	pub async fn get_etag(&self) -> Result<Option<String>> {
+		return self.get_attr("user.etag");
+	}
+
+	pub async fn set_checksums(&self, checksums: &Checksums) -> Result<()> {
+		self.set_attr("user.etag", &checksums.md5)?;
+		if let Some(sha256) = &checksums.sha256 {
+			self.set_attr("user.checksum_sha256", sha256)?;
+		}
+		if let Some(crc32c) = &checksums.crc32c {
+			self.set_attr("user.checksum_crc32c", crc32c)?;
+		}
+
 		return Ok(());
 	}
 
-	pub async fn is_favorite(&self) -> Result<bool> {
-		const IS_FAVORITE: &str = "false";
+	pub async fn get_checksums(&self) -> Result<Option<Checksums>> {
+		let md5 = match self.get_etag().await? {
+			None => return Ok(None),
+			Some(md5) => md5,
+		};
+
+		return Ok(Some(Checksums {
+			md5,
+			sha256: self.get_attr("user.checksum_sha256")?,
+			crc32c: self.get_attr("user.checksum_crc32c")?,
+		}));
+	}
 
-		let metadata_vec_value = get(&self.path, "user.is_favorite")
-			.unwrap_or(Some(IS_FAVORITE.as_bytes().to_vec()))
-			.unwrap_or(IS_FAVORITE.as_bytes().to_vec());
-		let metadata_value = str::from_utf8(&metadata_vec_value).unwrap();
+	pub async fn set_checksum_algorithms(&self, algorithms: &[ChecksumAlgorithm]) -> Result<()> {
+		let value = algorithms.iter().map(|algorithm| algorithm.as_str()).collect::<Vec<&str>>().join(",");
+		return self.set_attr("user.checksum_algorithms", &value);
+	}
 
-		return Ok(metadata_value != IS_FAVORITE);
+	pub async fn get_checksum_algorithms(&self) -> Result<Vec<ChecksumAlgorithm>> {
+		return match self.get_attr("user.checksum_algorithms")? {
+			None => Ok(Vec::new()),
+			Some(value) => value.split(',')
+				.filter(|name| !name.is_empty())
+				.map(ChecksumAlgorithm::from_str)
+				.collect(),
+		};
 	}
 
 	pub async fn info(&self) -> Result<FsItem> {
@@ -213,44 +300,62 @@ impl FsMetadata {
 			.unwrap_or(mime::TEXT_PLAIN)
 			.to_string();
 
-		let delete_at = self.get_delete_time().await.unwrap().unwrap_or(0);
+		let delete_at = self.get_delete_time().await?.unwrap_or(0);
+		let checksums = self.get_checksums().await?;
 		return Ok(FsItem {
-			name: self.path.file_name().unwrap().to_str().unwrap().to_string(),
+			name: self.name(),
 			hash: FsMetadata::calculate_hash(self.base_path.as_ref()),
 			size: size.to_string(),
 			file_type: ext.to_string(),
 			mime_type: file_type,
 			is_dir: metadata.is_dir(),
-			is_delete: self.is_delete().await.unwrap(),
+			is_delete: self.is_delete().await?,
 			is_favorite: self.is_favorite().await?,
 			see_time: metadata.atime() as u64,
 			delete_at: Some(delete_at as u64),
+			etag: checksums.as_ref().map(|checksums| checksums.md5.clone()),
+			sha256: checksums.as_ref().and_then(|checksums| checksums.sha256.clone()),
+			crc32c: checksums.and_then(|checksums| checksums.crc32c),
 			buffer: None,
 		});
 	}
 
-	fn move_dir<'a>(path_from: &'a PathBuf, path_to: &'a PathBuf) -> BoxFuture<'a, ()> {
+	fn move_dir<'a>(path_from: &'a PathBuf, path_to: &'a PathBuf) -> BoxFuture<'a, Result<()>> {
 		async move {
-			let mut dir = fs::read_dir(&path_from).await.unwrap();
+			let mut dir = fs::read_dir(&path_from).await?;
 
-			while let Some(item) = dir.next_entry().await.unwrap() {
-				let item_path_to = Path::new(path_to).join(&item.file_name());
-				if item.metadata().await.unwrap().is_dir() {
-					fs::create_dir_all(&item_path_to).await.unwrap();
-					FsMetadata::move_dir(&item.path(), &item_path_to).await;
+			while let Some(item) = dir.next_entry().await? {
+				let item_path_to = Path::new(path_to).join(item.file_name());
+				if item.metadata().await?.is_dir() {
+					fs::create_dir_all(&item_path_to).await?;
+					FsMetadata::move_dir(&item.path(), &item_path_to).await?;
 					continue;
 				}
-				fs::copy(&item.path(), &item_path_to).await.unwrap();
+				FsMetadata::copy_file(&item.path(), &item_path_to).await?;
 			}
+
+			return Ok(());
 		}.boxed()
 	}
 
+	// Copies a file with its ETag and checksums, which `fs::copy` leaves behind.
+	async fn copy_file(path_from: &Path, path_to: &Path) -> Result<()> {
+		fs::copy(path_from, path_to).await?;
+		for name in CONTENT_ATTRS {
+			if let Some(value) = get(path_from, name).map_err(SolarError::Xattr)? {
+				set(path_to, name, &value).map_err(SolarError::Xattr)?;
+			}
+		}
+
+		return Ok(());
+	}
+
 	pub async fn move_path(&self, path_to: impl AsRef<Path>, is_delete: bool) -> Result<()> {
 		if self.is_dir {
 			fs::create_dir_all(&path_to).await?;
-			FsMetadata::move_dir(&self.path, &path_to.as_ref().to_path_buf()).await;
+			FsMetadata::move_dir(&self.path, &path_to.as_ref().to_path_buf()).await?;
 		} else {
-			fs::copy(&self.path, &path_to).await?;
+			FsMetadata::copy_file(&self.path, path_to.as_ref()).await?;
 		}
 
 		if is_delete {
@@ -273,38 +378,42 @@ impl FsMetadata {
 	}
 
 	pub async fn get_properties(&self) -> Result<PropertiesItem> {
-		let path_metadata = fs::metadata(&self.path).await.unwrap();
-		let create_at = path_metadata
-			.created()
-			.unwrap()
-			.duration_since(UNIX_EPOCH).unwrap()
-			.as_secs();
-		let update_at = path_metadata
-			.modified()
-			.unwrap()
-			.duration_since(UNIX_EPOCH)
-			.unwrap()
-			.as_secs();
+		let path_metadata = fs::metadata(&self.path).await?;
+		let update_at = path_metadata.modified()?;
+		// Not every filesystem records the creation time.
+		let create_at = path_metadata.created().unwrap_or(update_at);
+		let checksums = self.get_checksums().await?;
 
 		return Ok(PropertiesItem {
-			name: self.path.file_name().unwrap().to_str().unwrap().to_string(),
+			name: self.name(),
 			hash: FsMetadata::calculate_hash(self.base_path.as_ref()),
 			is_dir: self.is_dir,
-			owner: 0,
+			owner: self.get_owner().await?,
 			size: path_metadata.size(),
-			create_at,
-			update_at,
-			is_delete: self.is_delete().await.unwrap(),
-			is_favorite: self.is_favorite().await.unwrap(),
+			create_at: FsMetadata::timestamp(create_at),
+			update_at: FsMetadata::timestamp(update_at),
+			is_delete: self.is_delete().await?,
+			is_favorite: self.is_favorite().await?,
 			see_time: path_metadata.atime() as u64,
 			description: "".to_string(),
+			etag: checksums.as_ref().map(|checksums| checksums.md5.clone()),
+			sha256: checksums.as_ref().and_then(|checksums| checksums.sha256.clone()),
+			crc32c: checksums.and_then(|checksums| checksums.crc32c),
 		})
 	}
 
+	fn name(&self) -> String {
+		return self.path.file_name().unwrap_or_default().to_string_lossy().to_string();
+	}
+
+	pub(crate) fn timestamp(time: SystemTime) -> u64 {
+		return time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+	}
+
+	/// The key of a path inside `files/` is the SHA-256 of the path, as 64 uppercase
+	/// hex characters. Directory paths end with `/`. It must never change, every key
+	/// stored in `user-paths.sqlite` is derived from it.
 	pub fn calculate_hash(key: &str) -> String {
-		let mut s = DefaultHasher::new();
-		key.hash(&mut s);
-		let hash = s.finish();
-		return format!("{hash:X}");
+		return hex::encode_upper(Sha256::digest(key.as_bytes()));
 	}
 }
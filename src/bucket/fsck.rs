@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use futures::future::{BoxFuture, FutureExt};
+use tokio::fs;
+
+use crate::bucket::access::Permission;
+use crate::bucket::bucket::Bucket;
+use crate::bucket::bucket_db::{BucketDB, KeyPath};
+use crate::bucket::fs_metadata::FsMetadata;
+use crate::error::Result;
+use crate::storage::space::Space;
+use crate::storage::store::Storage;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency {
+	// A row of the `paths` table without a file or directory on disk.
+	OrphanedRow { key: String, path: String },
+	// A file or directory in `files/` without a row in the `paths` table.
+	UntrackedPath { key: String, path: String },
+	// `is_favorite` is the state in the database, the xattr says the opposite.
+	Favorite { key: String, path: String, is_favorite: bool },
+	// `is_delete` is the state in the database, the xattr says the opposite.
+	Trash { key: String, path: String, is_delete: bool },
+	// The `user.usage_space` of the bucket and the size of its data on disk.
+	UsageSpace { recorded: u64, actual: u64 },
+}
+
+#[derive(Debug, Default)]
+pub struct CheckReport {
+	pub inconsistencies: Vec<Inconsistency>,
+	// Whether the inconsistencies were fixed.
+	pub repaired: bool,
+}
+
+/// The state of a bucket is split between the `paths` tables of the database, the
+/// xattrs of the files and the `files/` tree itself, and an operation interrupted
+/// halfway leaves them apart. When they disagree the database wins for the favorite
+/// and trash flags, because it is committed at once while the xattrs of an interrupted
+/// operation may already be changed. Rows without a file are dropped and files
+/// without a row are added, so no data is lost.
+impl Bucket {
+	/// Reports the inconsistencies of the bucket and, with `repair`, fixes them.
+	pub async fn check(&mut self, repair: bool) -> Result<CheckReport> {
+		self.authorize("", Permission::Admin).await?;
+		let mut report = CheckReport { inconsistencies: Vec::new(), repaired: repair };
+		let files_path = Path::new(&self.path).join("files");
+
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let mut disk_paths = HashSet::new();
+		collect_paths(&files_path, "", &mut disk_paths).await?;
+
+		let mut key_paths = Vec::new();
+		let mut tracked = HashSet::new();
+		for key_path in BucketDB::get_all_paths(&transaction).await? {
+			tracked.insert(key_path.path.clone());
+			if disk_paths.contains(&key_path.path) {
+				key_paths.push(key_path);
+				continue;
+			}
+
+			if repair {
+				self.remove_versions(&key_path.path, &transaction).await?;
+				BucketDB::remove_key(&key_path.key, &transaction).await?;
+			}
+			report.inconsistencies.push(Inconsistency::OrphanedRow { key: key_path.key, path: key_path.path });
+		}
+
+		let mut untracked = disk_paths.into_iter().filter(|path| !tracked.contains(path)).collect::<Vec<String>>();
+		untracked.sort();
+		for path in untracked {
+			let key_path = KeyPath { key: FsMetadata::calculate_hash(&path), is_dir: path.ends_with('/'), path };
+			report.inconsistencies.push(Inconsistency::UntrackedPath { key: key_path.key.clone(), path: key_path.path.clone() });
+			if repair {
+				BucketDB::add_key(&key_path, &transaction).await?;
+				self.index_path(&key_path, &transaction).await?;
+				if !key_path.is_dir {
+					self.index_content(&key_path.key, &key_path.path, &transaction).await?;
+				}
+				key_paths.push(key_path);
+			}
+		}
+
+		let favorites = BucketDB::get_favorites(&transaction).await?.into_iter().collect::<HashSet<String>>();
+		let deletes = BucketDB::get_deletes(&transaction).await?.into_iter().collect::<HashSet<String>>();
+		key_paths.sort_by(|a, b| a.path.cmp(&b.path));
+		for key_path in key_paths {
+			let fs_metadata = FsMetadata::new(files_path.join(&key_path.path)).await?;
+
+			let is_favorite = favorites.contains(&key_path.path);
+			if is_favorite != fs_metadata.is_favorite().await? {
+				if repair {
+					match is_favorite {
+						true => fs_metadata.set_favorite().await?,
+						false => fs_metadata.unset_favorite().await?,
+					}
+				}
+				report.inconsistencies.push(Inconsistency::Favorite { key: key_path.key.clone(), path: key_path.path.clone(), is_favorite });
+			}
+
+			let is_delete = deletes.contains(&key_path.path);
+			if is_delete != fs_metadata.is_delete().await? {
+				if repair {
+					match is_delete {
+						true => { fs_metadata.set_delete().await?; },
+						false => fs_metadata.restore_delete().await?,
+					}
+				}
+				report.inconsistencies.push(Inconsistency::Trash { key: key_path.key, path: key_path.path, is_delete });
+			}
+		}
+
+		transaction.commit()?;
+
+		// The usage is counted last, the repairs above may drop versions.
+		if let Some(inconsistency) = self.count_usage_space(repair).await? {
+			report.inconsistencies.push(inconsistency);
+		}
+
+		return Ok(report);
+	}
+
+	// Compares the recorded usage of the bucket with the size of its data on disk and,
+	// with `repair`, records the size on disk, in the store as well. The parts of the
+	// uploads are in both counts, so the difference is the one of the store too.
+	pub(crate) async fn count_usage_space(&mut self, repair: bool) -> Result<Option<Inconsistency>> {
+		let bucket = FsMetadata::new(&self.path).await?;
+		let recorded = bucket.get_space().await?.usage_space;
+		let actual = data_size(Path::new(&self.path)).await?.bucket_size();
+		if recorded == actual {
+			return Ok(None);
+		}
+
+		if repair {
+			bucket.set_usage_space(actual).await?;
+			match actual > recorded {
+				true => self.store.update_space(actual - recorded).await?,
+				false => self.store.release_space(recorded - actual).await?,
+			};
+			self.usage_space = actual;
+		}
+
+		return Ok(Some(Inconsistency::UsageSpace { recorded, actual }));
+	}
+}
+
+/// The data of a bucket on disk. The store is charged for `files/` and `versions/`,
+/// the parts of the uploads only take the quota of the bucket. Anything else, like
+/// the files half written to `tmp/`, isn't counted.
+pub(crate) struct DataSize {
+	pub(crate) stored_size: u64,
+	pub(crate) parts_size: u64,
+}
+
+impl DataSize {
+	pub(crate) fn bucket_size(&self) -> u64 {
+		return self.stored_size + self.parts_size;
+	}
+}
+
+pub(crate) async fn data_size(bucket_path: &Path) -> Result<DataSize> {
+	let mut sizes = [0; 3];
+	for (size, dir) in sizes.iter_mut().zip(["files", "versions", "multipart"]) {
+		let dir_path = bucket_path.join(dir);
+		if dir_path.exists() {
+			*size = Space::dir_size(&dir_path).await?;
+		}
+	}
+
+	return Ok(DataSize { stored_size: sizes[0] + sizes[1], parts_size: sizes[2] });
+}
+
+// Adds the paths under `dir_path` to `paths`, relative to `files/`, with a trailing
+// slash for directories like in the `paths` table.
+fn collect_paths<'a>(dir_path: &'a Path, prefix: &'a str, paths: &'a mut HashSet<String>) -> BoxFuture<'a, Result<()>> {
+	async move {
+		let mut dir = fs::read_dir(dir_path).await?;
+		let mut sub_dirs = Vec::new();
+		while let Some(item) = dir.next_entry().await? {
+			let path = format!("{}{}", prefix, item.file_name().to_string_lossy());
+			if item.metadata().await?.is_dir() {
+				sub_dirs.push((format!("{}/", path), item.path()));
+				continue;
+			}
+			paths.insert(path);
+		}
+
+		for (path, item_path) in sub_dirs {
+			collect_paths(&item_path, &path, paths).await?;
+			paths.insert(path);
+		}
+
+		return Ok(());
+	}.boxed()
+}
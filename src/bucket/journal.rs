@@ -0,0 +1,155 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
+use chrono::Utc;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, PidExt, ProcessRefreshKind, System, SystemExt};
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::bucket::bucket::Bucket;
+use crate::bucket::bucket_db::BucketDB;
+use crate::bucket::fs_metadata::FsMetadata;
+use crate::error::Result;
+
+static PROCESS_ID: OnceLock<String> = OnceLock::new();
+
+/// An operation that changes both the database and the `files/` tree. The paths are
+/// relative to `files/`, like in the `paths` table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Intent {
+	AddDir { path: String },
+	// The file at `temp_path` replaces the one at `path`. With versioning on, the
+	// replaced file is moved to `versions/<version_id>` first.
+	AddFile { path: String, temp_path: String, version_id: Option<String> },
+	// Both `rename` and `move_items`.
+	Move { from: String, to: String },
+	Copy { to: String },
+}
+
+/// Every operation logs its intent in the `intents` table and commits it before the
+/// filesystem step. The intent is removed in the transaction of the operation, which
+/// is committed only after the filesystem step, so an intent left behind belongs to an
+/// operation whose database changes were lost. Such operations are rolled back: their
+/// filesystem step is undone, so the files match the database again.
+impl Bucket {
+	pub(crate) async fn begin_intent(&self, connection: &Connection, intent: &Intent) -> Result<String> {
+		let id = Uuid::new_v4().simple().to_string();
+		BucketDB::add_intent(&id, process_id(), &serde_json::to_string(intent)?, Utc::now().timestamp(), connection).await?;
+		return Ok(id);
+	}
+
+	// Passes on the `result` of the operation of the intent `id`, rolling the operation
+	// back when it failed.
+	pub(crate) async fn finish_intent<T>(&self, id: &str, intent: &Intent, result: Result<T>) -> Result<T> {
+		if result.is_err() {
+			// The error of the operation matters more than the one of the rollback.
+			self.rollback_intent(intent, false).await.is_err();
+			BucketDB::remove_intent(id, &BucketDB::open(self.path.as_str()).await?).await?;
+		}
+
+		return result;
+	}
+
+	/// Rolls back the operations left unfinished by processes that are gone, newest
+	/// first, and returns them. The intents of a process that still runs, like another
+	/// server or a command line tool on the same bucket, are left to it. The usage of
+	/// the bucket is counted again afterwards, the space reserved for an interrupted
+	/// upload can't be told apart otherwise.
+	pub(crate) async fn recover_intents(&mut self) -> Result<Vec<Intent>> {
+		let connection = BucketDB::open(self.path.as_str()).await?;
+		let mut intents = BucketDB::get_intents(process_id(), &connection).await?;
+		intents.retain(|(_, process, _)| !is_running(process));
+		if intents.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		for (id, _, intent) in intents.iter() {
+			self.rollback_intent(intent, true).await?;
+			BucketDB::remove_intent(id, &connection).await?;
+		}
+		self.count_usage_space(true).await?;
+
+		return Ok(intents.into_iter().map(|(_, _, intent)| intent).collect());
+	}
+
+	// Undoes the filesystem step of `intent`, whether it was done in full, in part or
+	// not at all. Paths that have a row in the database are left alone. The staged file
+	// of a failed `AddFile` is given back to its caller, after a crash (`is_recovery`)
+	// nobody owns it anymore and it is removed.
+	async fn rollback_intent(&self, intent: &Intent, is_recovery: bool) -> Result<()> {
+		let files_path = Path::new(&self.path).join("files");
+		match intent {
+			Intent::AddDir { path } => {
+				if !self.exists(&FsMetadata::calculate_hash(path)).await? {
+					// A directory that got files since is left to `check`.
+					fs::remove_dir(files_path.join(path)).await.is_err();
+				}
+			},
+			Intent::AddFile { path, temp_path, version_id } => {
+				let (temp_path, save_path) = (Path::new(temp_path), files_path.join(path));
+				let version_path = version_id.as_ref().map(|version_id| self.version_path(version_id)).filter(|version_path| version_path.exists());
+				// Without versioning the replaced content is gone, the new one is kept.
+				let is_restored = version_path.is_some() || !self.exists(&FsMetadata::calculate_hash(path)).await?;
+				if is_restored {
+					if !is_recovery && !temp_path.exists() && save_path.exists() {
+						fs::rename(&save_path, temp_path).await?;
+					}
+					match version_path {
+						Some(version_path) => fs::rename(version_path, &save_path).await?,
+						None => remove_path(&save_path).await?,
+					}
+				}
+				if is_recovery {
+					remove_path(temp_path).await?;
+				}
+			},
+			Intent::Move { from, to } => {
+				if files_path.join(to).exists() && !files_path.join(from).exists() {
+					fs::rename(files_path.join(to), files_path.join(from)).await?;
+				}
+			},
+			Intent::Copy { to } => {
+				if !self.exists(&FsMetadata::calculate_hash(to)).await? {
+					remove_path(&files_path.join(to)).await?;
+				}
+			},
+		}
+
+		return Ok(());
+	}
+}
+
+// `<pid>:<uuid>`: the pid tells whether the process still runs, the uuid tells this
+// process apart from an earlier one that had the same pid.
+fn process_id() -> &'static str {
+	return PROCESS_ID.get_or_init(|| format!("{}:{}", std::process::id(), Uuid::new_v4().simple()));
+}
+
+// Whether the process that logged an intent still runs. An intent of this process may
+// still be running too. An earlier process with the pid of this one, like the server
+// of a restarted container, is gone. A pid taken over by another process only delays
+// the rollback.
+fn is_running(process: &str) -> bool {
+	if process == process_id() {
+		return true;
+	}
+
+	let pid = match process.split_once(':').and_then(|(pid, _)| pid.parse::<u32>().ok()) {
+		None => return false,
+		Some(pid) => pid,
+	};
+	if pid == std::process::id() {
+		return false;
+	}
+	return System::new().refresh_process_specifics(Pid::from_u32(pid), ProcessRefreshKind::new());
+}
+
+async fn remove_path(path: &Path) -> Result<()> {
+	if !path.exists() {
+		return Ok(());
+	}
+
+	return FsMetadata::new(path).await?.remove().await;
+}
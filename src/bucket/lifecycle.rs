@@ -0,0 +1,145 @@
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use tokio::fs;
+
+use crate::bucket::access::Permission;
+use crate::bucket::bucket::Bucket;
+use crate::bucket::bucket_db::BucketDB;
+use crate::error::{Result, SolarError};
+
+const DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LifecycleRule {
+	pub id: String,
+	// Only the keys that start with the prefix, all of them when it is empty.
+	pub prefix: String,
+	// Only the objects with this tag name and value.
+	pub tag: Option<(String, String)>,
+	// Moves objects to the trash this many days after their last change.
+	pub expiration_days: Option<u32>,
+	// Deletes versions this many days after they stopped being current.
+	pub noncurrent_days: Option<u32>,
+	// Aborts multipart uploads this many days after they were started.
+	pub abort_multipart_days: Option<u32>,
+	pub enabled: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct LifecycleResult {
+	pub expired: Vec<String>,
+	pub versions: Vec<String>,
+	pub uploads: Vec<String>,
+}
+
+/// Lifecycle rules are kept in the `lifecycle_rules` table of the bucket database and
+/// evaluated by `apply_lifecycle`, either on demand or by the server maintenance task.
+impl Bucket {
+	/// Replaces the lifecycle configuration of the bucket.
+	pub async fn set_lifecycle(&self, rules: &[LifecycleRule]) -> Result<()> {
+		self.authorize("", Permission::Admin).await?;
+		for (index, rule) in rules.iter().enumerate() {
+			validate_rule(rule)?;
+			if rules[..index].iter().any(|other| other.id == rule.id) {
+				return Err(SolarError::InvalidInput(format!("Lifecycle rule {} is defined twice", rule.id)));
+			}
+		}
+
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		BucketDB::set_lifecycle_rules(rules, &transaction).await?;
+		transaction.commit()?;
+
+		return Ok(());
+	}
+
+	pub async fn get_lifecycle(&self) -> Result<Vec<LifecycleRule>> {
+		self.authorize("", Permission::Read).await?;
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		let rules = BucketDB::get_lifecycle_rules(&transaction).await?;
+		transaction.commit()?;
+
+		return Ok(rules);
+	}
+
+	/// Runs the enabled rules as of `date` (a unix timestamp) and returns the keys moved
+	/// to the trash, the deleted version ids and the aborted upload ids.
+	pub async fn apply_lifecycle(&self, date: i64) -> Result<LifecycleResult> {
+		self.authorize("", Permission::Admin).await?;
+		let mut result = LifecycleResult::default();
+
+		for rule in self.get_lifecycle().await?.into_iter().filter(|rule| rule.enabled) {
+			if let Some(days) = rule.expiration_days {
+				let keys = self.expired_keys(&rule, date - days as i64 * DAY).await?;
+				self.set_delete(keys.clone()).await?;
+				result.expired.extend(keys);
+			}
+
+			if let Some(days) = rule.noncurrent_days {
+				let versions = {
+					let mut connection = BucketDB::open(self.path.as_str()).await?;
+					let transaction = connection.transaction()?;
+					let versions = BucketDB::get_rule_versions(&rule.prefix, rule.tag.as_ref(), date - days as i64 * DAY, &transaction).await?;
+					transaction.commit()?;
+					versions
+				};
+
+				for version in versions {
+					self.delete_version(&version.key, &version.version_id).await?;
+					result.versions.push(version.version_id);
+				}
+			}
+
+			if let Some(days) = rule.abort_multipart_days {
+				for upload in self.get_multiparts().await? {
+					let is_expired = upload.path.starts_with(&rule.prefix) && upload.create_at <= date - days as i64 * DAY;
+					if is_expired && !self.is_writing_part(&upload.upload_id).await? {
+						self.abort_multipart(&upload.upload_id).await?;
+						result.uploads.push(upload.upload_id);
+					}
+				}
+			}
+		}
+
+		return Ok(result);
+	}
+
+	// Keys matched by `rule` that weren't changed since `date`.
+	async fn expired_keys(&self, rule: &LifecycleRule, date: i64) -> Result<Vec<String>> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		let key_paths = BucketDB::get_rule_paths(&rule.prefix, rule.tag.as_ref(), &transaction).await?;
+		transaction.commit()?;
+
+		let mut keys: Vec<String> = Vec::new();
+		for key_path in key_paths {
+			let metadata = fs::metadata(Path::new(&self.path).join("files").join(&key_path.path)).await?;
+			let update_at = metadata.modified()?.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs() as i64).unwrap_or(0);
+			if update_at <= date {
+				keys.push(key_path.key);
+			}
+		}
+
+		return Ok(keys);
+	}
+}
+
+fn validate_rule(rule: &LifecycleRule) -> Result<()> {
+	if rule.id.is_empty() || rule.id.len() > 255 {
+		return Err(SolarError::InvalidInput("A lifecycle rule id must have between 1 and 255 characters".to_string()));
+	}
+	if rule.expiration_days.is_none() && rule.noncurrent_days.is_none() && rule.abort_multipart_days.is_none() {
+		return Err(SolarError::InvalidInput(format!("Lifecycle rule {} has no action", rule.id)));
+	}
+	if [rule.expiration_days, rule.noncurrent_days, rule.abort_multipart_days].contains(&Some(0)) {
+		return Err(SolarError::InvalidInput(format!("Lifecycle rule {} must use a positive number of days", rule.id)));
+	}
+	// Uploads have no tags yet, so a tag filter can't select them.
+	if rule.tag.is_some() && rule.abort_multipart_days.is_some() {
+		return Err(SolarError::InvalidInput(format!("Lifecycle rule {} can't abort uploads with a tag filter", rule.id)));
+	}
+
+	return Ok(());
+}
@@ -0,0 +1,385 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::Path;
+
+use mime_guess::mime;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::bucket::access::Permission;
+use crate::bucket::bucket::Bucket;
+use crate::bucket::bucket_db::BucketDB;
+use crate::bucket::fs_metadata::{FsItem, FsMetadata};
+use crate::error::{Result, SolarError};
+
+pub const DEFAULT_PAGE_SIZE: usize = 1000;
+pub const MAX_PAGE_SIZE: usize = 10000;
+pub const MAX_KEYS: usize = 1000;
+// How many paths `list_objects` reads from the database at once.
+const OBJECT_BATCH_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ListSort {
+	#[default]
+	Name,
+	Size,
+	UpdateAt,
+	// By extension, the same as `FsItem::file_type`.
+	Type,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListKind {
+	#[default]
+	All,
+	Directories,
+	Files,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListOptions {
+	pub page_size: usize,
+	// The `next_token` of the previous page, `None` for the first page.
+	pub continuation_token: Option<String>,
+	pub sort: ListSort,
+	pub descending: bool,
+	pub kind: ListKind,
+	// Only files whose mime type starts with it, e.g. `image/`.
+	pub mime_prefix: Option<String>,
+	// Only items whose name matches it, `*` and `?` are wildcards.
+	pub name_glob: Option<String>,
+}
+
+pub struct ListPage {
+	pub items: Vec<FsItem>,
+	// Set when there are more items, pass it as `continuation_token` to get them.
+	pub next_token: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListObjectsOptions {
+	pub prefix: String,
+	// Groups the keys that contain it after the prefix into common prefixes.
+	pub delimiter: Option<String>,
+	// Only the keys after it, ignored with a continuation token.
+	pub start_after: Option<String>,
+	pub continuation_token: Option<String>,
+	// The objects and common prefixes of a page, at most `MAX_KEYS`.
+	pub max_keys: usize,
+}
+
+pub struct ObjectItem {
+	pub key: String,
+	pub path: String,
+	pub size: u64,
+	pub update_at: u64,
+	pub etag: Option<String>,
+}
+
+pub struct ObjectListing {
+	pub objects: Vec<ObjectItem>,
+	pub common_prefixes: Vec<String>,
+	// Set when the listing was truncated, pass it as `continuation_token` to go on.
+	pub next_continuation_token: Option<String>,
+}
+
+// Where a listing of objects stopped: after a key, or after every key of a common prefix.
+enum ListMarker {
+	Key(String),
+	Prefix(String),
+}
+
+// What the entries are sorted and filtered by, read with a single `stat` per entry.
+#[derive(Serialize, Deserialize)]
+struct ListEntry {
+	name: String,
+	is_dir: bool,
+	size: u64,
+	update_at: u64,
+	file_type: String,
+}
+
+// The position after the last item of a page. It keeps the sort, so a token can't
+// be reused with another order.
+#[derive(Serialize, Deserialize)]
+struct ListToken {
+	sort: ListSort,
+	descending: bool,
+	last: ListEntry,
+}
+
+impl ListMarker {
+	fn parse(token: &str) -> Option<ListMarker> {
+		return match token.split_at_checked(2) {
+			Some(("k:", key)) => Some(ListMarker::Key(key.to_string())),
+			Some(("p:", prefix)) => Some(ListMarker::Prefix(prefix.to_string())),
+			_ => None,
+		};
+	}
+
+	fn token(&self) -> String {
+		return match self {
+			ListMarker::Key(key) => format!("k:{key}"),
+			ListMarker::Prefix(prefix) => format!("p:{prefix}"),
+		};
+	}
+}
+
+impl Default for ListObjectsOptions {
+	fn default() -> ListObjectsOptions {
+		return ListObjectsOptions {
+			prefix: "".to_string(),
+			delimiter: None,
+			start_after: None,
+			continuation_token: None,
+			max_keys: MAX_KEYS,
+		};
+	}
+}
+
+impl Default for ListOptions {
+	fn default() -> ListOptions {
+		return ListOptions {
+			page_size: DEFAULT_PAGE_SIZE,
+			continuation_token: None,
+			sort: ListSort::default(),
+			descending: false,
+			kind: ListKind::default(),
+			mime_prefix: None,
+			name_glob: None,
+		};
+	}
+}
+
+/// A paginated alternative to `get_items`. The directory is scanned without reading
+/// the xattrs of its entries, only the items of the returned page get a full `FsItem`.
+/// Pages continue after the last item of the previous one, so items added or removed
+/// meanwhile don't shift the following pages.
+impl Bucket {
+	pub async fn list_items(&self, key: Option<&String>, options: &ListOptions) -> Result<ListPage> {
+		if !(1..=MAX_PAGE_SIZE).contains(&options.page_size) {
+			return Err(SolarError::InvalidInput(format!("The page size must be between 1 and {}", MAX_PAGE_SIZE)));
+		}
+		let token = match &options.continuation_token {
+			None => None,
+			Some(token) => Some(parse_token(token, options)?),
+		};
+		let name_regex = options.name_glob.as_deref().map(glob_regex).transpose()?;
+
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+		let dir_path = match key {
+			None => "".to_string(),
+			Some(key) => BucketDB::get_path(key.as_ref(), &transaction).await?,
+		};
+		let deletes: HashSet<String> = BucketDB::get_deletes(&transaction).await?.into_iter().collect();
+		transaction.commit()?;
+
+		let access = self.access().await?;
+		access.check_see(&dir_path)?;
+
+		if !dir_path.is_empty() && !dir_path.ends_with('/') {
+			return Err(SolarError::NotADirectory("This is not a directory!".to_string()));
+		}
+
+		let path = Path::new(&self.path).join("files").join(&dir_path);
+		let mut entries = tokio::task::spawn_blocking(move || read_entries(&path)).await
+			.map_err(|error| SolarError::Io(error.into()))??;
+
+		entries.retain(|entry| {
+			let entry_path = match entry.is_dir {
+				true => format!("{}{}/", dir_path, entry.name),
+				false => format!("{}{}", dir_path, entry.name),
+			};
+			return !deletes.contains(&entry_path) && access.can_see(&entry_path) && matches_filters(entry, options, name_regex.as_ref());
+		});
+		entries.sort_by(|a, b| compare(a, b, options));
+		if let Some(token) = &token {
+			entries.retain(|entry| compare(entry, &token.last, options) == Ordering::Greater);
+		}
+
+		let has_more = entries.len() > options.page_size;
+		entries.truncate(options.page_size);
+
+		let mut items: Vec<FsItem> = Vec::new();
+		for entry in entries.iter() {
+			let item_path = Path::new(&self.path).join("files").join(&dir_path).join(&entry.name);
+			items.push(FsMetadata::new(&item_path).await?.info().await?);
+		}
+
+		let next_token = match (has_more, entries.pop()) {
+			(true, Some(last)) => Some(hex::encode(serde_json::to_vec(&ListToken { sort: options.sort, descending: options.descending, last })?)),
+			_ => None,
+		};
+
+		return Ok(ListPage { items, next_token });
+	}
+}
+
+/// A ListObjectsV2 view of the bucket: the files are listed by their path in the
+/// flat key space, answered from the `paths` table in path order. Directories are
+/// not objects, only the files inside them are.
+impl Bucket {
+	pub async fn list_objects(&self, options: &ListObjectsOptions) -> Result<ObjectListing> {
+		if options.max_keys > MAX_KEYS {
+			return Err(SolarError::InvalidInput(format!("max-keys can't be more than {}", MAX_KEYS)));
+		}
+		let marker = match &options.continuation_token {
+			Some(token) => Some(ListMarker::parse(token)
+				.ok_or_else(|| SolarError::InvalidInput("The continuation token provided is incorrect".to_string()))?),
+			None => options.start_after.clone().map(ListMarker::Key),
+		};
+		let delimiter = options.delimiter.as_deref().filter(|delimiter| !delimiter.is_empty());
+
+		let mut after = match marker {
+			None => "".to_string(),
+			Some(ListMarker::Key(key)) => key,
+			Some(ListMarker::Prefix(prefix)) => past_prefix(&prefix),
+		};
+
+		let access = self.access().await?;
+		access.check_see("")?;
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let mut key_paths = Vec::new();
+		let mut common_prefixes: Vec<String> = Vec::new();
+		let mut last_marker: Option<ListMarker> = None;
+		let mut is_truncated = false;
+
+		'batches: loop {
+			let batch = BucketDB::get_object_paths(&options.prefix, &after, OBJECT_BATCH_SIZE, &transaction).await?;
+			if batch.is_empty() {
+				break;
+			}
+
+			for key_path in batch {
+				after = key_path.path.clone();
+				if !access.allows(&key_path.path, Permission::Read) {
+					continue;
+				}
+
+				let common_prefix = delimiter.and_then(|delimiter| {
+					key_path.path[options.prefix.len()..]
+						.find(delimiter)
+						.map(|index| key_path.path[..options.prefix.len() + index + delimiter.len()].to_string())
+				});
+				if key_paths.len() + common_prefixes.len() >= options.max_keys {
+					is_truncated = true;
+					break 'batches;
+				}
+
+				match common_prefix {
+					Some(common_prefix) => {
+						// The next batch starts after the objects under the prefix, however many.
+						after = past_prefix(&common_prefix);
+						last_marker = Some(ListMarker::Prefix(common_prefix.clone()));
+						common_prefixes.push(common_prefix);
+						continue 'batches;
+					},
+					None => {
+						last_marker = Some(ListMarker::Key(key_path.path.clone()));
+						key_paths.push(key_path);
+					},
+				}
+			}
+		}
+		transaction.commit()?;
+
+		let mut objects: Vec<ObjectItem> = Vec::new();
+		for key_path in key_paths {
+			let properties = FsMetadata::new(&Path::new(&self.path).join("files").join(&key_path.path)).await?.get_properties().await?;
+			objects.push(ObjectItem {
+				key: key_path.key,
+				path: key_path.path,
+				size: properties.size,
+				update_at: properties.update_at,
+				etag: properties.etag,
+			});
+		}
+
+		return Ok(ObjectListing {
+			objects,
+			common_prefixes,
+			next_continuation_token: match is_truncated {
+				true => last_marker.map(|marker| marker.token()),
+				false => None,
+			},
+		});
+	}
+}
+
+// A path that sorts after every path starting with `prefix`, but for the ones that
+// go on with the noncharacter U+10FFFF.
+fn past_prefix(prefix: &str) -> String {
+	return format!("{}\u{10FFFF}", prefix);
+}
+
+fn read_entries(path: &Path) -> Result<Vec<ListEntry>> {
+	let mut entries: Vec<ListEntry> = Vec::new();
+	for item in std::fs::read_dir(path)? {
+		let item = item?;
+		let metadata = item.metadata()?;
+		let name = item.file_name().to_string_lossy().to_string();
+		entries.push(ListEntry {
+			file_type: Path::new(&name).extension().map(|ext| ext.to_string_lossy().to_string()).unwrap_or_default(),
+			name,
+			is_dir: metadata.is_dir(),
+			size: metadata.len(),
+			update_at: metadata.modified().map(FsMetadata::timestamp)?,
+		});
+	}
+
+	return Ok(entries);
+}
+
+fn matches_filters(entry: &ListEntry, options: &ListOptions, name_regex: Option<&Regex>) -> bool {
+	let kind = match options.kind {
+		ListKind::All => true,
+		ListKind::Directories => entry.is_dir,
+		ListKind::Files => !entry.is_dir,
+	};
+	let mime = match &options.mime_prefix {
+		None => true,
+		Some(prefix) => !entry.is_dir && mime_guess::from_path(&entry.name)
+			.first()
+			.unwrap_or(mime::TEXT_PLAIN)
+			.essence_str()
+			.starts_with(prefix.as_str()),
+	};
+	let name = name_regex.map(|regex| regex.is_match(&entry.name)).unwrap_or(true);
+
+	return kind && mime && name;
+}
+
+// Orders by the sort field, then by name, so that every entry has one position.
+fn compare(a: &ListEntry, b: &ListEntry, options: &ListOptions) -> Ordering {
+	let ordering = match options.sort {
+		ListSort::Name => Ordering::Equal,
+		ListSort::Size => a.size.cmp(&b.size),
+		ListSort::UpdateAt => a.update_at.cmp(&b.update_at),
+		ListSort::Type => a.file_type.cmp(&b.file_type),
+	}.then_with(|| a.name.cmp(&b.name));
+
+	return if options.descending { ordering.reverse() } else { ordering };
+}
+
+fn parse_token(token: &str, options: &ListOptions) -> Result<ListToken> {
+	let invalid = || SolarError::InvalidInput("The continuation token is not valid".to_string());
+	let token: ListToken = hex::decode(token).ok()
+		.and_then(|token| serde_json::from_slice(&token).ok())
+		.ok_or_else(invalid)?;
+
+	if token.sort != options.sort || token.descending != options.descending {
+		return Err(invalid());
+	}
+
+	return Ok(token);
+}
+
+/// Turns a glob with the `*` and `?` wildcards into a regex that matches whole names.
+pub(crate) fn glob_regex(glob: &str) -> Result<Regex> {
+	let pattern = regex::escape(glob).replace(r"\*", ".*").replace(r"\?", ".");
+	return Regex::new(&format!("^{}$", pattern))
+		.map_err(|_| SolarError::InvalidInput(format!("Invalid name pattern {}", glob)));
+}
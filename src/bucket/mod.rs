@@ -1,6 +1,19 @@
+pub mod access;
+pub mod audit;
 pub mod bucket;
 mod bucket_db;
-mod fs_metadata;
+pub mod checksum;
+pub mod content;
+pub mod fs_metadata;
+pub mod fsck;
+mod journal;
+pub mod lifecycle;
+pub mod listing;
+pub mod multipart;
+pub mod precondition;
+pub mod range;
+pub mod search;
+pub mod versioning;
 
 #[cfg(test)]
 mod test;
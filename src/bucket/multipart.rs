@@ -0,0 +1,313 @@
+use std::path::{Path, PathBuf};
+use std::str;
+use std::time::UNIX_EPOCH;
+
+use chrono::Utc;
+use md5::{Digest, Md5};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use uuid::Uuid;
+use xattr::{get, set};
+
+use crate::bucket::access::Permission;
+use crate::bucket::bucket::Bucket;
+use crate::bucket::bucket_db::BucketDB;
+use crate::bucket::fs_metadata::FsMetadata;
+use crate::error::{Result, SolarError};
+use crate::storage::space::Space;
+
+pub const MIN_PART_NUMBER: u32 = 1;
+pub const MAX_PART_NUMBER: u32 = 10000;
+pub const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+// Uploads older than this are considered abandoned and can be cleared.
+pub const STALE_UPLOAD_AGE: i64 = 7 * 24 * 60 * 60;
+// A part file staged by `upload_part` and changed this recently, in seconds, is still
+// being written. An older one was left behind by a write that broke off.
+const PART_WRITE_AGE: i64 = 60 * 60;
+
+pub struct MultipartUpload {
+	pub upload_id: String,
+	pub path: String,
+	pub create_at: i64,
+}
+
+pub struct PartItem {
+	pub part_number: u32,
+	pub size: u64,
+	pub etag: String,
+	pub update_at: i64,
+}
+
+pub struct CompletedPart {
+	pub part_number: u32,
+	pub etag: String,
+}
+
+/// Multipart uploads are staged in `<bucket>/multipart/<upload_id>/`, one file per
+/// part number. The target path and the creation time are kept in the xattrs of the
+/// upload directory and the MD5 of each part in the xattrs of the part file. Staged
+/// parts are counted in the bucket usage until the upload is completed or aborted.
+impl Bucket {
+	pub async fn create_multipart(&self, key: Option<&String>, name: &str) -> Result<String> {
+		let path = match key {
+			None => name.to_string(),
+			Some(key) => {
+				let mut connection = BucketDB::open(self.path.as_str()).await?;
+				let transaction = connection.transaction()?;
+				let dir_path = BucketDB::get_path(key.as_ref(), &transaction).await?;
+				transaction.commit()?;
+
+				if !dir_path.ends_with('/') {
+					return Err(SolarError::NotADirectory("Can't upload into a file!".to_string()));
+				}
+				Path::new(&dir_path).join(name).to_string_lossy().to_string()
+			},
+		};
+		self.authorize(&path, Permission::Write).await?;
+
+		let upload_id = Uuid::new_v4().simple().to_string();
+		let upload_path = self.upload_path(&upload_id);
+		fs::create_dir_all(&upload_path).await?;
+
+		set(&upload_path, "user.path", path.as_bytes()).map_err(SolarError::Xattr)?;
+		set(&upload_path, "user.create_at", Utc::now().timestamp().to_string().as_bytes()).map_err(SolarError::Xattr)?;
+
+		return Ok(upload_id);
+	}
+
+	pub async fn get_multipart(&self, upload_id: &str) -> Result<MultipartUpload> {
+		let upload_path = self.upload_path(upload_id);
+		if !is_upload_id(upload_id) || !fs::metadata(&upload_path).await.map(|metadata| metadata.is_dir()).unwrap_or(false) {
+			return Err(SolarError::NoSuchUpload(upload_id.to_string()));
+		}
+
+		let path = get(&upload_path, "user.path").map_err(SolarError::Xattr)?.unwrap_or_default();
+		let path = String::from_utf8_lossy(&path).to_string();
+		let create_at = get(&upload_path, "user.create_at").map_err(SolarError::Xattr)?.unwrap_or_default();
+		self.authorize(&path, Permission::Read).await?;
+
+		return Ok(MultipartUpload {
+			upload_id: upload_id.to_string(),
+			path,
+			create_at: str::from_utf8(&create_at).unwrap_or("0").parse::<i64>().unwrap_or(0),
+		});
+	}
+
+	pub async fn get_multiparts(&self) -> Result<Vec<MultipartUpload>> {
+		let mut uploads: Vec<MultipartUpload> = Vec::new();
+		let multipart_path = Path::new(&self.path).join("multipart");
+		if !multipart_path.exists() {
+			return Ok(uploads);
+		}
+
+		let mut dir = fs::read_dir(&multipart_path).await?;
+		while let Some(item) = dir.next_entry().await? {
+			let upload_id = item.file_name().to_str().unwrap_or("").to_string();
+			if let Ok(upload) = self.get_multipart(&upload_id).await {
+				uploads.push(upload);
+			}
+		}
+		uploads.sort_by(|a, b| a.create_at.cmp(&b.create_at).then_with(|| a.upload_id.cmp(&b.upload_id)));
+
+		return Ok(uploads);
+	}
+
+	/// Stores part `part_number` of the upload, replacing a previous part with the same number.
+	pub async fn upload_part<R: AsyncRead + Unpin>(&self, upload_id: &str, part_number: u32, reader: &mut R) -> Result<PartItem> {
+		if !(MIN_PART_NUMBER..=MAX_PART_NUMBER).contains(&part_number) {
+			return Err(SolarError::InvalidInput(format!("Part number must be an integer between {} and {}", MIN_PART_NUMBER, MAX_PART_NUMBER)));
+		}
+		let upload = self.get_multipart(upload_id).await?;
+		self.authorize(&upload.path, Permission::Write).await?;
+
+		let upload_path = self.upload_path(upload_id);
+		let part_path = upload_path.join(part_number.to_string());
+		let temp_path = upload_path.join(format!("{}.{}", part_number, Uuid::new_v4().simple()));
+
+		let (size, checksums) = self.write_file(reader, &temp_path).await?;
+		let etag = checksums.md5;
+
+		let bucket = FsMetadata::new(&self.path).await?;
+		let old_size = fs::metadata(&part_path).await.map(|metadata| metadata.len()).ok();
+
+		let result: Result<()> = async {
+			set(&temp_path, "user.etag", etag.as_bytes()).map_err(SolarError::Xattr)?;
+			fs::rename(&temp_path, &part_path).await?;
+			return Ok(());
+		}.await;
+
+		if let Err(error) = result {
+			fs::remove_file(&temp_path).await.is_err();
+			bucket.decrease_size(size).await?;
+			return Err(error);
+		}
+
+		if let Some(old_size) = old_size {
+			bucket.decrease_size(old_size).await?;
+		}
+
+		return Ok(PartItem {
+			part_number,
+			size,
+			etag,
+			update_at: Utc::now().timestamp(),
+		});
+	}
+
+	pub async fn list_parts(&self, upload_id: &str) -> Result<Vec<PartItem>> {
+		self.get_multipart(upload_id).await?;
+
+		let mut parts: Vec<PartItem> = Vec::new();
+		let mut dir = fs::read_dir(self.upload_path(upload_id)).await?;
+		while let Some(item) = dir.next_entry().await? {
+			let part_number = match item.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) {
+				Some(part_number) => part_number,
+				None => continue,
+			};
+
+			let metadata = item.metadata().await?;
+			let etag = get(item.path(), "user.etag").map_err(SolarError::Xattr)?.unwrap_or_default();
+			parts.push(PartItem {
+				part_number,
+				size: metadata.len(),
+				etag: String::from_utf8_lossy(&etag).to_string(),
+				update_at: metadata.modified()?.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs() as i64).unwrap_or(0),
+			});
+		}
+		parts.sort_by_key(|part| part.part_number);
+
+		return Ok(parts);
+	}
+
+	/// Joins `parts` into the target file of the upload and returns its key and the
+	/// multipart ETag, the MD5 of the concatenated part digests suffixed with `-<count>`.
+	/// Staged parts that are not listed are dropped. A failed completion leaves the
+	/// upload as it was, so it can be retried or aborted.
+	pub async fn complete_multipart(&mut self, upload_id: &str, parts: &[CompletedPart]) -> Result<(String, String)> {
+		let upload = self.get_multipart(upload_id).await?;
+		self.authorize(&upload.path, Permission::Write).await?;
+		let staged = self.list_parts(upload_id).await?;
+
+		if parts.is_empty() {
+			return Err(SolarError::InvalidInput("You must specify at least one part".to_string()));
+		}
+		if parts.windows(2).any(|pair| pair[0].part_number >= pair[1].part_number) {
+			return Err(SolarError::InvalidPartOrder);
+		}
+
+		let mut selected: Vec<&PartItem> = Vec::new();
+		for part in parts {
+			let etag = part.etag.trim_matches('"');
+			let staged_part = staged.iter()
+				.find(|staged_part| staged_part.part_number == part.part_number && staged_part.etag == etag)
+				.ok_or(SolarError::InvalidPart)?;
+			selected.push(staged_part);
+		}
+
+		if selected[..selected.len() - 1].iter().any(|part| part.size < MIN_PART_SIZE) {
+			return Err(SolarError::EntityTooSmall);
+		}
+
+		let mut hasher = Md5::new();
+		for part in &selected {
+			hasher.update(hex::decode(&part.etag).map_err(|_| SolarError::InvalidPart)?);
+		}
+		let etag = format!("{}-{}", hex::encode(hasher.finalize()), selected.len());
+
+		let (hash, is_replace) = self.prepare_file(&upload.path).await?;
+		let parent = Path::new(&upload.path).parent().map(|parent| parent.to_string_lossy().to_string()).unwrap_or_default();
+		if !parent.is_empty() && !self.exists(&FsMetadata::calculate_hash(&format!("{}/", parent))).await? {
+			return Err(SolarError::KeyNotFound(format!("{}/", parent)));
+		}
+
+		// The first part becomes the object: the other parts are appended to it, so
+		// the data is never held twice on disk or in the quota.
+		let upload_path = self.upload_path(upload_id);
+		let first_path = upload_path.join(selected[0].part_number.to_string());
+		let file_size: u64 = selected.iter().map(|part| part.size).sum();
+
+		let result: Result<()> = async {
+			let mut file = OpenOptions::new().append(true).open(&first_path).await?;
+			for part in &selected[1..] {
+				let mut part_file = fs::File::open(upload_path.join(part.part_number.to_string())).await?;
+				tokio::io::copy(&mut part_file, &mut file).await?;
+			}
+			file.sync_all().await?;
+
+			FsMetadata::new(&first_path).await?.set_etag(&etag).await?;
+			return self.commit_file(&first_path, file_size, &upload.path, &hash, is_replace).await;
+		}.await;
+
+		if let Err(error) = result {
+			if let Ok(file) = std::fs::OpenOptions::new().write(true).open(&first_path) {
+				file.set_len(selected[0].size)?;
+				set(&first_path, "user.etag", selected[0].etag.as_bytes()).map_err(SolarError::Xattr)?;
+			}
+			return Err(error);
+		}
+
+		// The listed parts now live in the object, only the others are released.
+		let unused_size: u64 = staged.iter()
+			.filter(|part| !selected.iter().any(|selected| selected.part_number == part.part_number))
+			.map(|part| part.size)
+			.sum();
+		fs::remove_dir_all(&upload_path).await?;
+		FsMetadata::new(&self.path).await?.decrease_size(unused_size).await?;
+
+		return Ok((hash, etag));
+	}
+
+	pub async fn abort_multipart(&self, upload_id: &str) -> Result<()> {
+		let upload = self.get_multipart(upload_id).await?;
+		self.authorize(&upload.path, Permission::Write).await?;
+
+		let upload_path = self.upload_path(upload_id);
+		let size = Space::dir_size(&upload_path).await?;
+		fs::remove_dir_all(&upload_path).await?;
+		FsMetadata::new(&self.path).await?.decrease_size(size).await?;
+
+		return Ok(());
+	}
+
+	/// Aborts the uploads created before `date` (a unix timestamp) and returns their ids.
+	/// Uploads with a part still being written are left alone.
+	pub async fn clear_multiparts(&self, date: i64) -> Result<Vec<String>> {
+		self.authorize("", Permission::Admin).await?;
+		let mut upload_ids: Vec<String> = Vec::new();
+		for upload in self.get_multiparts().await? {
+			if upload.create_at < date && !self.is_writing_part(&upload.upload_id).await? {
+				self.abort_multipart(&upload.upload_id).await?;
+				upload_ids.push(upload.upload_id);
+			}
+		}
+
+		return Ok(upload_ids);
+	}
+
+	// Whether a part of the upload is being written: its temporary file, named
+	// `<part_number>.<uuid>`, changed within `PART_WRITE_AGE`.
+	pub(crate) async fn is_writing_part(&self, upload_id: &str) -> Result<bool> {
+		let mut dir = fs::read_dir(self.upload_path(upload_id)).await?;
+		while let Some(item) = dir.next_entry().await? {
+			if !item.file_name().to_string_lossy().contains('.') {
+				continue;
+			}
+
+			let modified = item.metadata().await?.modified()?;
+			let modified = modified.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs() as i64).unwrap_or(0);
+			if modified > Utc::now().timestamp() - PART_WRITE_AGE {
+				return Ok(true);
+			}
+		}
+
+		return Ok(false);
+	}
+
+	fn upload_path(&self, upload_id: &str) -> PathBuf {
+		return Path::new(&self.path).join("multipart").join(upload_id);
+	}
+}
+
+fn is_upload_id(upload_id: &str) -> bool {
+	return upload_id.len() == 32 && upload_id.chars().all(|char| char.is_ascii_hexdigit());
+}
@@ -0,0 +1,80 @@
+use crate::bucket::fs_metadata::PropertiesItem;
+use crate::error::{Result, SolarError};
+
+/// Conditions an object must meet for an operation to go ahead. ETags are compared
+/// without quotes, `*` matches any existing object and a list of ETags may be given
+/// separated by commas, like in the HTTP headers. Times are unix timestamps.
+#[derive(Debug, Clone, Default)]
+pub struct Preconditions {
+	pub if_match: Option<String>,
+	pub if_none_match: Option<String>,
+	pub if_modified_since: Option<u64>,
+	pub if_unmodified_since: Option<u64>,
+}
+
+impl Preconditions {
+	pub fn is_empty(&self) -> bool {
+		return self.if_match.is_none() && self.if_none_match.is_none()
+			&& self.if_modified_since.is_none() && self.if_unmodified_since.is_none();
+	}
+
+	/// Checks the conditions of a read. A matching `if_none_match` or an object not
+	/// changed since `if_modified_since` gives `NotModified`, the other failures give
+	/// `PreconditionFailed`. As in RFC 7232, a time condition is ignored when the
+	/// matching ETag condition is set.
+	pub fn check_read(&self, key: &str, object: &PropertiesItem) -> Result<()> {
+		self.check_match(key, Some(object))?;
+
+		if let Some(if_none_match) = &self.if_none_match {
+			if matches_etag(if_none_match, object.etag.as_deref()) {
+				return Err(SolarError::NotModified(key.to_string()));
+			}
+		} else if let Some(date) = self.if_modified_since {
+			if object.update_at <= date {
+				return Err(SolarError::NotModified(key.to_string()));
+			}
+		}
+
+		return Ok(());
+	}
+
+	/// Checks the conditions of a write to `key`, `object` is its current state or
+	/// `None` when the key doesn't exist. Every failure gives `PreconditionFailed`.
+	pub fn check_write(&self, key: &str, object: Option<&PropertiesItem>) -> Result<()> {
+		self.check_match(key, object)?;
+
+		if let Some(if_none_match) = &self.if_none_match {
+			if object.is_some_and(|object| matches_etag(if_none_match, object.etag.as_deref())) {
+				return Err(SolarError::PreconditionFailed(key.to_string()));
+			}
+		} else if let (Some(date), Some(object)) = (self.if_modified_since, object) {
+			if object.update_at <= date {
+				return Err(SolarError::PreconditionFailed(key.to_string()));
+			}
+		}
+
+		return Ok(());
+	}
+
+	fn check_match(&self, key: &str, object: Option<&PropertiesItem>) -> Result<()> {
+		if let Some(if_match) = &self.if_match {
+			if !object.is_some_and(|object| matches_etag(if_match, object.etag.as_deref())) {
+				return Err(SolarError::PreconditionFailed(key.to_string()));
+			}
+		} else if let (Some(date), Some(object)) = (self.if_unmodified_since, object) {
+			if object.update_at > date {
+				return Err(SolarError::PreconditionFailed(key.to_string()));
+			}
+		}
+
+		return Ok(());
+	}
+}
+
+// Whether `etag` is one of the comma separated ETags of `condition`. Weak ETags
+// compare like strong ones, the stored ETags are always strong.
+fn matches_etag(condition: &str, etag: Option<&str>) -> bool {
+	return condition.split(',')
+		.map(|value| value.trim())
+		.any(|value| value == "*" || Some(value.trim_start_matches("W/").trim_matches('"')) == etag);
+}
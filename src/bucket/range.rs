@@ -0,0 +1,52 @@
+use crate::error::{Result, SolarError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+	// bytes=100-199
+	Between(u64, u64),
+	// bytes=100-
+	From(u64),
+	// bytes=-100, the last 100 bytes
+	Suffix(u64),
+}
+
+impl ByteRange {
+	pub fn parse(value: &str) -> Result<ByteRange> {
+		let invalid = || SolarError::InvalidInput(format!("Invalid range: {}", value));
+
+		let spec = value.trim().strip_prefix("bytes=").ok_or_else(invalid)?.trim();
+		if spec.contains(',') {
+			return Err(SolarError::InvalidInput("Multiple ranges are not supported".to_string()));
+		}
+
+		let (start, end) = spec.split_once('-').ok_or_else(invalid)?;
+		let parse_number = |number: &str| number.trim().parse::<u64>().map_err(|_| invalid());
+
+		return match (start.trim().is_empty(), end.trim().is_empty()) {
+			(true, true) => Err(invalid()),
+			(true, false) => Ok(ByteRange::Suffix(parse_number(end)?)),
+			(false, true) => Ok(ByteRange::From(parse_number(start)?)),
+			(false, false) => {
+				let start = parse_number(start)?;
+				let end = parse_number(end)?;
+				if end < start {
+					return Err(invalid());
+				}
+				Ok(ByteRange::Between(start, end))
+			}
+		};
+	}
+
+	// Returns the first byte and the length of the range inside an object of `size` bytes.
+	pub fn resolve(&self, size: u64) -> Result<(u64, u64)> {
+		return match *self {
+			ByteRange::Between(start, end) if start < size => Ok((start, end.min(size - 1) - start + 1)),
+			ByteRange::From(start) if start < size => Ok((start, size - start)),
+			ByteRange::Suffix(length) if length > 0 && size > 0 => {
+				let length = length.min(size);
+				Ok((size - length, length))
+			}
+			_ => Err(SolarError::RangeNotSatisfiable(size)),
+		};
+	}
+}
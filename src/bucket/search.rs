@@ -0,0 +1,206 @@
+use std::path::Path;
+
+use rusqlite::types::Value;
+use rusqlite::Transaction;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::bucket::access::Permission;
+use crate::bucket::bucket::Bucket;
+use crate::bucket::bucket_db::{BucketDB, KeyPath, PathMetadata};
+use crate::bucket::fs_metadata::FsMetadata;
+use crate::bucket::listing::{ListKind, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::error::{Result, SolarError};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchSort {
+	#[default]
+	Name,
+	Path,
+	Size,
+	CreateAt,
+	UpdateAt,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+	// A case-insensitive part of the name.
+	pub name: Option<String>,
+	// A pattern for the whole name with the `*`, `?` and `[...]` wildcards of SQLite GLOB.
+	pub name_glob: Option<String>,
+	// Only files whose mime type starts with it, e.g. `image/`.
+	pub mime_prefix: Option<String>,
+	// The extension of the name without the dot, compared case-insensitively.
+	pub extension: Option<String>,
+	pub kind: ListKind,
+	pub min_size: Option<u64>,
+	pub max_size: Option<u64>,
+	// The time ranges are unix timestamps, both ends included.
+	pub created_after: Option<u64>,
+	pub created_before: Option<u64>,
+	pub modified_after: Option<u64>,
+	pub modified_before: Option<u64>,
+	pub favorite: Option<bool>,
+	// Items in the trash or inside a trashed directory. Only the others by default.
+	pub trashed: Option<bool>,
+	pub sort: SearchSort,
+	pub descending: bool,
+	pub page_size: usize,
+	// The `next_token` of the previous page, `None` for the first page.
+	pub continuation_token: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchItem {
+	pub key: String,
+	pub path: String,
+	pub name: String,
+	pub is_dir: bool,
+	pub size: u64,
+	pub mime_type: Option<String>,
+	pub create_at: u64,
+	pub update_at: u64,
+	pub is_favorite: bool,
+	pub is_delete: bool,
+}
+
+pub struct SearchPage {
+	pub items: Vec<SearchItem>,
+	// Set when there are more items, pass it as `continuation_token` to get them.
+	pub next_token: Option<String>,
+}
+
+// The sort value and the path of the last item of a page.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SearchToken {
+	sort: SearchSort,
+	descending: bool,
+	text: Option<String>,
+	number: Option<i64>,
+	pub(crate) path: String,
+}
+
+impl Default for SearchOptions {
+	fn default() -> SearchOptions {
+		return SearchOptions {
+			name: None,
+			name_glob: None,
+			mime_prefix: None,
+			extension: None,
+			kind: ListKind::default(),
+			min_size: None,
+			max_size: None,
+			created_after: None,
+			created_before: None,
+			modified_after: None,
+			modified_before: None,
+			favorite: None,
+			trashed: Some(false),
+			sort: SearchSort::default(),
+			descending: false,
+			page_size: DEFAULT_PAGE_SIZE,
+			continuation_token: None,
+		};
+	}
+}
+
+impl SearchToken {
+	pub(crate) fn value(&self) -> Value {
+		return match (&self.text, self.number) {
+			(Some(text), _) => Value::Text(text.clone()),
+			(None, Some(number)) => Value::Integer(number),
+			(None, None) => Value::Null,
+		};
+	}
+
+	fn from_item(item: &SearchItem, options: &SearchOptions) -> SearchToken {
+		let (text, number) = match options.sort {
+			SearchSort::Name => (Some(item.name.clone()), None),
+			SearchSort::Path => (Some(item.path.clone()), None),
+			SearchSort::Size => (None, Some(item.size as i64)),
+			SearchSort::CreateAt => (None, Some(item.create_at as i64)),
+			SearchSort::UpdateAt => (None, Some(item.update_at as i64)),
+		};
+
+		return SearchToken {
+			sort: options.sort,
+			descending: options.descending,
+			text,
+			number,
+			path: item.path.clone(),
+		};
+	}
+}
+
+/// Search runs on the `path_metadata` table, which holds the name, type, size and
+/// times of every path. Files are indexed when they are written and the name columns
+/// follow renames and moves; paths without a row, such as new directories, copies or
+/// the paths of an older database, are indexed from the filesystem before a search.
+impl Bucket {
+	pub async fn search(&self, options: &SearchOptions) -> Result<SearchPage> {
+		if !(1..=MAX_PAGE_SIZE).contains(&options.page_size) {
+			return Err(SolarError::InvalidInput(format!("The page size must be between 1 and {}", MAX_PAGE_SIZE)));
+		}
+		let token = match &options.continuation_token {
+			None => None,
+			Some(token) => Some(parse_token(token, options)?),
+		};
+
+		let access = self.access().await?;
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		for key_path in BucketDB::get_unindexed_paths(&transaction).await? {
+			self.index_path(&key_path, &transaction).await?;
+		}
+		// The items the user can't read are left out before the page is cut, so every
+		// page but the last is full.
+		let is_visible = |path: &str| access.allows(path, Permission::Read);
+		let mut items = BucketDB::search_paths(options, token.as_ref(), options.page_size + 1, is_visible, &transaction).await?;
+		transaction.commit()?;
+
+		let next_token = match items.len() > options.page_size {
+			false => None,
+			true => {
+				items.truncate(options.page_size);
+				let token = SearchToken::from_item(&items[options.page_size - 1], options);
+				Some(hex::encode(serde_json::to_vec(&token)?))
+			},
+		};
+
+		return Ok(SearchPage { items, next_token });
+	}
+
+	// Records the size and times of `key_path` as they are on disk.
+	pub(crate) async fn index_path(&self, key_path: &KeyPath, transaction: &Transaction<'_>) -> Result<()> {
+		let metadata = match fs::metadata(Path::new(&self.path).join("files").join(&key_path.path)).await {
+			Ok(metadata) => metadata,
+			// A path missing on disk is left out of the search.
+			Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+			Err(error) => return Err(error.into()),
+		};
+		let update_at = metadata.modified()?;
+
+		return BucketDB::set_metadata(&PathMetadata {
+			key: key_path.key.clone(),
+			path: key_path.path.clone(),
+			is_dir: key_path.is_dir,
+			size: if key_path.is_dir { 0 } else { metadata.len() },
+			create_at: FsMetadata::timestamp(metadata.created().unwrap_or(update_at)) as i64,
+			update_at: FsMetadata::timestamp(update_at) as i64,
+		}, transaction).await;
+	}
+}
+
+fn parse_token(token: &str, options: &SearchOptions) -> Result<SearchToken> {
+	let invalid = || SolarError::InvalidInput("The continuation token is not valid".to_string());
+	let token: SearchToken = hex::decode(token).ok()
+		.and_then(|token| serde_json::from_slice(&token).ok())
+		.ok_or_else(invalid)?;
+
+	if token.sort != options.sort || token.descending != options.descending {
+		return Err(invalid());
+	}
+
+	return Ok(token);
+}
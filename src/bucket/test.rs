@@ -1,12 +1,17 @@
 #[cfg(test)]
 mod tests_bucket {
-	use std::io::Result;
 	use std::path::{Path, PathBuf};
 
+	use chrono::Utc;
+	use mocktopus::mocking::*;
 	use tokio::fs;
+	use tokio::io::AsyncReadExt;
 	use uuid::Uuid;
 
-	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::bucket::{Bucket, KeyValue, DEFAULT_TRASH_RETENTION};
+	use crate::bucket::range::ByteRange;
+	use crate::error::{Result, SolarError};
+	use crate::storage::space::{Disc, Space};
 	use crate::storage::store::{Storage, Store};
 
 	async fn prepare_test() -> Result<PathBuf> {
@@ -67,6 +72,70 @@ mod tests_bucket {
 		fs::remove_dir_all(path).await.is_err();
 	}
 
+	#[tokio::test]
+	async fn test_delete() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let mut bucket = res.create_bucket("videos", 500, None).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"console.log".to_vec()) }).await.unwrap();
+		let bucket_path = bucket.path.clone();
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 11);
+
+		let error = Bucket::open(res.clone(), &bucket.uuid).await.unwrap().delete(false).await.err().unwrap();
+		assert!(matches!(error, SolarError::BucketNotEmpty(_)));
+
+		bucket.delete(true).await.unwrap();
+		assert!(!Path::new(&bucket_path).exists());
+		assert!(!res.has_bucket("videos").await.unwrap());
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 0);
+
+		let bucket = res.create_bucket("photos", 500, None).await.unwrap();
+		bucket.delete(false).await.unwrap();
+
+		fs::remove_dir_all(&path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_resize() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(res, &bucket_uuid, 500).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"console.log".to_vec()) }).await.unwrap();
+
+		assert!(bucket.resize(10).await.is_err());
+		assert!(bucket.resize(1001).await.is_err());
+
+		bucket.resize(1000).await.unwrap();
+		assert_eq!(bucket.available_space, 1000);
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket_uuid).await.unwrap().available_space, 1000);
+
+		bucket.resize(11).await.unwrap();
+		let err = bucket.add(&KeyValue { key: None, name: "main.js".to_string(), value: Some(b"!".to_vec()) }).await.is_err();
+		assert!(err);
+
+		fs::remove_dir_all(&path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_remove_release_space() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(res, &bucket_uuid, 500).await.unwrap();
+		let key = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"console.log".to_vec()) }).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"log".to_vec()) }).await.unwrap();
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 3);
+
+		bucket.remove(vec![key]).await.unwrap();
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 0);
+
+		fs::remove_dir_all(&path).await.is_err();
+	}
+
 	#[tokio::test]
 	async fn test_add_file() {
 		let path = self::prepare_test().await.unwrap();
@@ -232,6 +301,20 @@ mod tests_bucket {
 		fs::remove_dir_all(path).await.is_err();
 	}
 
+	#[tokio::test]
+	async fn test_get_missing_key() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let bucket = Bucket::create(res, &bucket_uuid, 999).await.unwrap();
+
+		let error = bucket.get(&"MISSING".to_string(), true).await.err().unwrap();
+		assert!(matches!(error, SolarError::KeyNotFound(_)));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
 	#[tokio::test]
 	async fn test_get_dir_items() {
 		let path = self::prepare_test().await.unwrap();
@@ -256,7 +339,7 @@ mod tests_bucket {
 
 		let data = bucket.get_items(None).await.unwrap();
 		assert_eq!(data.len(), 1);
-		assert_eq!(data[0].hash, "FD1EA89060210A4E");
+		assert_eq!(data[0].hash, "1BC04B5291C26A46D918139138B992D2DE976D6851D0893B0476B85BFBDFC6E6");
 
 		let data = bucket.get_items(Some(&key)).await.unwrap();
 		assert_eq!(data.len(), 2);
@@ -289,6 +372,29 @@ mod tests_bucket {
 		fs::remove_dir_all(path).await.is_err();
 	}
 
+	#[tokio::test]
+	async fn test_rename_prefix_of_sibling() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let mut bucket = Bucket::create(res, &Uuid::new_v4().to_string(), 999).await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "notes".to_string(), value: Some(b"1".to_vec()) }).await.unwrap();
+		let sibling_key = bucket.add(&KeyValue { key: None, name: "notes.txt".to_string(), value: Some(b"2".to_vec()) }).await.unwrap();
+		let dir_key = bucket.add(&KeyValue { key: None, name: "copies".to_string(), value: None }).await.unwrap();
+
+		// Only the file itself is copied and renamed, not the one its name is a prefix of.
+		assert_eq!(bucket.copy(&key, &dir_key).await.unwrap().len(), 1);
+		assert!(!bucket.exists(&Bucket::calculate_key("copies/notes.txt")).await.unwrap());
+
+		let hashes = bucket.rename(&key, &"z".to_string()).await.unwrap();
+		assert_eq!(hashes, vec![Bucket::calculate_key("z")]);
+		assert!(bucket.exists(&sibling_key).await.unwrap());
+		assert_eq!(bucket.get(&sibling_key, false).await.unwrap().buffer.unwrap(), b"2");
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
 	#[tokio::test]
 	async fn test_rename_dir() {
 		let path = self::prepare_test().await.unwrap();
@@ -401,6 +507,32 @@ mod tests_bucket {
 		// fs::remove_dir_all(path).await.is_err();
 	}
 
+	#[tokio::test]
+	async fn test_copy_usage() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let mut bucket = Bucket::create(res, &Uuid::new_v4().to_string(), 10).await.unwrap();
+
+		let dir_key = bucket.add(&KeyValue { key: None, name: "copies".to_string(), value: None }).await.unwrap();
+		let key = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"data".to_vec()) }).await.unwrap();
+		bucket.copy(&key, &dir_key).await.unwrap();
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space, 8);
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 8);
+
+		// A copy beyond the bucket quota fails and reserves nothing.
+		let more_key = bucket.add(&KeyValue { key: None, name: "more".to_string(), value: None }).await.unwrap();
+		let error = bucket.copy(&key, &more_key).await.err().unwrap();
+		assert!(matches!(error, SolarError::QuotaExceeded(_)));
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space, 8);
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 8);
+
+		bucket.remove(vec![Bucket::calculate_key("copies/index.js")]).await.unwrap();
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 4);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
 	#[tokio::test]
 	async fn test_get_properties() {
 		let path = self::prepare_test().await.unwrap();
@@ -419,7 +551,7 @@ mod tests_bucket {
 
 		assert_eq!(data.name, "index.js");
 		assert_eq!(data.is_dir, false);
-		assert_eq!(data.owner, 0);
+		assert_eq!(data.owner, None);
 		fs::remove_dir_all(path).await.is_err();
 	}
 
@@ -644,6 +776,28 @@ mod tests_bucket {
 		fs::remove_dir_all(path).await.is_err();
 	}
 
+	#[tokio::test]
+	async fn test_remove_dir_exact_prefix() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let mut bucket = Bucket::create(res, &Uuid::new_v4().to_string(), 999).await.unwrap();
+
+		let mut keys = Vec::new();
+		for name in ["a_b", "axb", "A_B"] {
+			let dir_key = bucket.add(&KeyValue { key: None, name: name.to_string(), value: None }).await.unwrap();
+			keys.push(bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "keep.txt".to_string(), value: Some(b"data".to_vec()) }).await.unwrap());
+		}
+
+		bucket.remove(vec![Bucket::calculate_key("a_b/")]).await.unwrap();
+		assert!(!bucket.exists(&keys[0]).await.unwrap());
+		assert!(bucket.exists(&keys[1]).await.unwrap());
+		assert!(bucket.exists(&keys[2]).await.unwrap());
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 8);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
 	#[tokio::test]
 	async fn test_clear_trash_deletes() {
 		let path = self::prepare_test().await.unwrap();
@@ -671,4 +825,1927 @@ mod tests_bucket {
 
 		fs::remove_dir_all(path).await.is_err();
 	}
+
+	#[tokio::test]
+	async fn test_purge_trash() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(res, &bucket_uuid, 999).await.unwrap();
+		assert_eq!(bucket.get_trash_retention().await.unwrap(), DEFAULT_TRASH_RETENTION);
+		bucket.set_trash_retention(60).await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"console.log".to_vec()) }).await.unwrap();
+		let dir_key = bucket.add(&KeyValue { key: None, name: "src".to_string(), value: None }).await.unwrap();
+		bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "main.js".to_string(), value: Some(b"main".to_vec()) }).await.unwrap();
+		let key_2 = bucket.add(&KeyValue { key: None, name: "index.html".to_string(), value: Some(b"<html>".to_vec()) }).await.unwrap();
+
+		bucket.set_delete(vec![key.clone(), dir_key.clone()]).await.unwrap();
+		let now = Utc::now().timestamp();
+		assert!(bucket.purge_trash(now).await.unwrap().is_empty());
+
+		let paths = bucket.purge_trash(now + 60).await.unwrap();
+		assert_eq!(paths.len(), 2);
+		assert!(!bucket.exists(&key).await.unwrap());
+		assert!(!path.join(&bucket_uuid).join("files").join("src").exists());
+		assert!(bucket.exists(&key_2).await.unwrap());
+
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket_uuid).await.unwrap().usage_space, 6);
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 6);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_purge_trash_exact_prefix() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(res, &bucket_uuid, 999).await.unwrap();
+		bucket.set_trash_retention(60).await.unwrap();
+
+		// `_` is a wildcard for LIKE, which also ignores the case.
+		let mut keys = Vec::new();
+		for name in ["a_b", "axb", "A_B"] {
+			let dir_key = bucket.add(&KeyValue { key: None, name: name.to_string(), value: None }).await.unwrap();
+			keys.push(bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "keep.txt".to_string(), value: Some(b"data".to_vec()) }).await.unwrap());
+		}
+
+		bucket.set_delete(vec![Bucket::calculate_key("a_b/")]).await.unwrap();
+		let paths = bucket.purge_trash(Utc::now().timestamp() + 60).await.unwrap();
+		assert_eq!(paths, vec!["a_b/".to_string()]);
+		assert!(!bucket.exists(&keys[0]).await.unwrap());
+		assert!(bucket.exists(&Bucket::calculate_key("axb/")).await.unwrap());
+		assert!(bucket.exists(&keys[1]).await.unwrap());
+		assert!(bucket.exists(&Bucket::calculate_key("A_B/")).await.unwrap());
+		assert!(bucket.exists(&keys[2]).await.unwrap());
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 8);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+	#[tokio::test]
+	async fn test_add_stream() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 100000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(res.clone(), &bucket_uuid, 99999).await.unwrap();
+
+		let data = vec![7u8; 70000];
+		let key = bucket.add_stream(None, "video.mp4", &mut data.as_slice()).await.unwrap();
+		assert_eq!(fs::read(path.join(&bucket_uuid).join("files").join("video.mp4")).await.unwrap(), data);
+
+		let bucket = Bucket::open(res, &bucket_uuid).await.unwrap();
+		assert_eq!(bucket.usage_space, 70000);
+		assert_eq!(bucket.get(&key, true).await.unwrap().name, "video.mp4");
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_add_stream_replace() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(res.clone(), &bucket_uuid, 999).await.unwrap();
+
+		let key = bucket.add_stream(None, "index.js", &mut b"console.log(1)".as_slice()).await.unwrap();
+		bucket.set_favorites(vec![key.clone()]).await.unwrap();
+		let new_key = bucket.add_stream(None, "index.js", &mut b"1".as_slice()).await.unwrap();
+		assert_eq!(key, new_key);
+
+		let data = bucket.get(&key, false).await.unwrap();
+		assert_eq!(data.buffer.unwrap(), b"1");
+		assert!(data.is_favorite);
+
+		let bucket = Bucket::open(res, &bucket_uuid).await.unwrap();
+		assert_eq!(bucket.usage_space, 1);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_add_stream_quota() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 100000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(res.clone(), &bucket_uuid, 70000).await.unwrap();
+
+		let data = vec![7u8; 70001];
+		let err = bucket.add_stream(None, "video.mp4", &mut data.as_slice()).await.is_err();
+		assert!(err);
+		assert!(!path.join(&bucket_uuid).join("files").join("video.mp4").exists());
+
+		let mut temp_files = fs::read_dir(path.join(&bucket_uuid).join("tmp")).await.unwrap();
+		assert!(temp_files.next_entry().await.unwrap().is_none());
+
+		let bucket = Bucket::open(res, &bucket_uuid).await.unwrap();
+		assert_eq!(bucket.usage_space, 0);
+		assert_eq!(bucket.get_items(None).await.unwrap().len(), 0);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_add_stream_disk_full() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 100000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(res, &bucket_uuid, 70000).await.unwrap();
+
+		Space::get_disc.mock_safe(|_| MockResult::Return(Ok(Disc { total_space: 100000, free_space: 100 })));
+		let data = vec![7u8; 1000];
+		let error = bucket.add_stream(None, "video.mp4", &mut data.as_slice()).await.err().unwrap();
+		assert!(matches!(error, SolarError::QuotaExceeded(_)));
+		assert!(!path.join(&bucket_uuid).join("files").join("video.mp4").exists());
+
+		let err = Bucket::create(bucket.store.clone(), &Uuid::new_v4().to_string(), 1000).await.is_err();
+		assert!(err);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+	#[tokio::test]
+	async fn test_read() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(res, &bucket_uuid, 999).await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"console.log(\"Hello world!\")".to_vec()) }).await.unwrap();
+
+		let mut data = Vec::new();
+		let mut reader = bucket.read(&key, None).await.unwrap();
+		reader.read_to_end(&mut data).await.unwrap();
+		assert_eq!(data, b"console.log(\"Hello world!\")");
+		assert_eq!(reader.item.name, "index.js");
+		assert_eq!((reader.size, reader.start, reader.length), (27, 0, 27));
+
+		let mut data = Vec::new();
+		let mut reader = bucket.read(&key, Some(&ByteRange::Between(0, 10))).await.unwrap();
+		reader.read_to_end(&mut data).await.unwrap();
+		assert_eq!(data, b"console.log");
+
+		let mut data = Vec::new();
+		let mut reader = bucket.read(&key, Some(&ByteRange::Suffix(8))).await.unwrap();
+		reader.read_to_end(&mut data).await.unwrap();
+		assert_eq!(data, b"world!\")");
+		assert_eq!((reader.start, reader.length), (19, 8));
+
+		let err = bucket.read(&key, Some(&ByteRange::From(27))).await.is_err();
+		assert!(err);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_read_dir_err() {
+		let path = self::prepare_test().await.unwrap();
+
+		let res = Store::create(&path, 1000, None).await.unwrap();
+		let bucket_uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(res, &bucket_uuid, 999).await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "index".to_string(), value: None }).await.unwrap();
+		let err = bucket.read(&key, None).await.is_err();
+		assert!(err);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_bucket_db {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::bucket_db::{BucketDB, KeyPath, SCHEMA_VERSION};
+	use crate::error::{Result, SolarError};
+	use crate::storage::store::{Storage, Store};
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await?;
+		let bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+		return Ok((path, bucket));
+	}
+
+	#[tokio::test]
+	async fn test_add_key_collision() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+
+		let mut connection = BucketDB::open(&bucket.path).await.unwrap();
+		let transaction = connection.transaction().unwrap();
+
+		let key_path = KeyPath { key: "A1".to_string(), path: "index.js".to_string(), is_dir: false };
+		BucketDB::add_key(&key_path, &transaction).await.unwrap();
+
+		let key_path = KeyPath { key: "A1".to_string(), path: "main.js".to_string(), is_dir: false };
+		let error = BucketDB::add_key(&key_path, &transaction).await.err().unwrap();
+		assert!(error.to_string().contains("Key collision"));
+		assert_eq!(BucketDB::get_path("A1", &transaction).await.unwrap(), "index.js");
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_migrate_keys() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let dir_key = bucket.add(&KeyValue { key: None, name: "index".to_string(), value: None }).await.unwrap();
+		let key = bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "index.js".to_string(), value: Some(b"console.log(1)".to_vec()) }).await.unwrap();
+		bucket.set_favorites(vec![key.clone()]).await.unwrap();
+		assert_eq!(key, Bucket::calculate_key("index/index.js"));
+
+		// Put the database back in the state left by the `DefaultHasher` keys.
+		let connection = rusqlite::Connection::open(Path::new(&bucket.path).join("user-paths.sqlite")).unwrap();
+		connection.execute("PRAGMA foreign_keys = ON", ()).unwrap();
+		connection.execute("UPDATE paths SET hash = 'FD1EA89060210A4E' WHERE hash = ?1", [&dir_key]).unwrap();
+		connection.execute("UPDATE paths SET hash = '6C1E0C4D4C1FBD21' WHERE hash = ?1", [&key]).unwrap();
+		connection.execute_batch("DROP TABLE versions; DROP TABLE tags; DROP TABLE lifecycle_rules; DROP TABLE path_metadata; DROP TABLE content_keys; DROP TABLE content_index; DROP TABLE intents; DROP TABLE grants; DROP TABLE schema_version;").unwrap();
+		connection.pragma_update(None, "user_version", 0).unwrap();
+		drop(connection);
+
+		let bucket = Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap();
+		let items = bucket.get_items(Some(&dir_key)).await.unwrap();
+		assert_eq!(items.len(), 1);
+		assert_eq!(items[0].hash, key);
+
+		let favorites = bucket.get_favorites().await.unwrap();
+		assert_eq!(favorites.len(), 1);
+		assert_eq!(bucket.get(&key, false).await.unwrap().buffer.unwrap(), b"console.log(1)");
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_schema_version() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+
+		let connection = BucketDB::open(&bucket.path).await.unwrap();
+		assert_eq!(BucketDB::schema_version(&connection).unwrap(), SCHEMA_VERSION);
+		let versions: Vec<i32> = connection.prepare("SELECT version FROM schema_version ORDER BY version").unwrap()
+			.query_map([], |row| row.get(0)).unwrap()
+			.collect::<rusqlite::Result<Vec<i32>>>().unwrap();
+		assert_eq!(versions, (1..=SCHEMA_VERSION).collect::<Vec<i32>>());
+		assert_eq!(BucketDB::migrate(&bucket.path).await.unwrap(), SCHEMA_VERSION);
+
+		// A database from before `schema_version`, at the third version.
+		connection.execute_batch("DROP TABLE path_metadata; DROP TABLE content_keys; DROP TABLE content_index; DROP TABLE intents; DROP TABLE grants; DROP TABLE schema_version;").unwrap();
+		connection.pragma_update(None, "user_version", 3).unwrap();
+		drop(connection);
+
+		let mut bucket = Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap();
+		let connection = BucketDB::open(&bucket.path).await.unwrap();
+		assert_eq!(BucketDB::schema_version(&connection).unwrap(), SCHEMA_VERSION);
+		let user_version: i32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+		assert_eq!(user_version, SCHEMA_VERSION);
+		bucket.add(&KeyValue { key: None, name: "a.txt".to_string(), value: Some(b"1".to_vec()) }).await.unwrap();
+
+		connection.execute("INSERT INTO schema_version (version, applied_at) VALUES (?1, 0)", [SCHEMA_VERSION + 1]).unwrap();
+		assert!(matches!(Bucket::open(bucket.store.clone(), &bucket.uuid).await.err().unwrap(), SolarError::InvalidInput(_)));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_multipart {
+	use std::path::{Path, PathBuf};
+	use std::time::{Duration, SystemTime};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::multipart::{CompletedPart, MIN_PART_SIZE};
+	use crate::error::{Result, SolarError};
+	use crate::storage::store::{Storage, Store};
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 3 * MIN_PART_SIZE, None).await?;
+		let bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 2 * MIN_PART_SIZE).await?;
+		return Ok((path, bucket));
+	}
+
+	#[tokio::test]
+	async fn test_complete() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let upload_id = bucket.create_multipart(None, "video.mp4").await.unwrap();
+		let first = vec![1u8; MIN_PART_SIZE as usize];
+		let part_1 = bucket.upload_part(&upload_id, 1, &mut first.as_slice()).await.unwrap();
+		let part_2 = bucket.upload_part(&upload_id, 2, &mut b"tail".as_slice()).await.unwrap();
+		bucket.upload_part(&upload_id, 3, &mut b"unused".as_slice()).await.unwrap();
+		assert_eq!(part_2.etag, "7aea2552dfe7eb84b9443b6fc9ba6e01");
+		assert_eq!(bucket.list_parts(&upload_id).await.unwrap().len(), 3);
+
+		let usage = Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space;
+		assert_eq!(usage, MIN_PART_SIZE + 10);
+
+		let parts = vec![
+			CompletedPart { part_number: 1, etag: part_1.etag.clone() },
+			CompletedPart { part_number: 2, etag: format!("\"{}\"", part_2.etag) },
+		];
+		let (key, etag) = bucket.complete_multipart(&upload_id, &parts).await.unwrap();
+		assert!(etag.ends_with("-2"));
+
+		let data = fs::read(Path::new(&bucket.path).join("files").join("video.mp4")).await.unwrap();
+		assert_eq!(data.len() as u64, MIN_PART_SIZE + 4);
+		assert_eq!(&data[data.len() - 4..], b"tail");
+		assert_eq!(bucket.properties(&key).await.unwrap().etag, Some(etag));
+		assert!(bucket.get_multipart(&upload_id).await.is_err());
+
+		let usage = Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space;
+		assert_eq!(usage, MIN_PART_SIZE + 4);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_complete_invalid_parts() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let upload_id = bucket.create_multipart(None, "index.js").await.unwrap();
+		let part_1 = bucket.upload_part(&upload_id, 1, &mut b"console".as_slice()).await.unwrap();
+		let part_2 = bucket.upload_part(&upload_id, 2, &mut b".log".as_slice()).await.unwrap();
+
+		let parts = vec![CompletedPart { part_number: 1, etag: part_2.etag.clone() }];
+		let error = bucket.complete_multipart(&upload_id, &parts).await.err().unwrap();
+		assert!(matches!(error, SolarError::InvalidPart));
+
+		let parts = vec![
+			CompletedPart { part_number: 2, etag: part_2.etag.clone() },
+			CompletedPart { part_number: 1, etag: part_1.etag.clone() },
+		];
+		let error = bucket.complete_multipart(&upload_id, &parts).await.err().unwrap();
+		assert!(matches!(error, SolarError::InvalidPartOrder));
+
+		let parts = vec![
+			CompletedPart { part_number: 1, etag: part_1.etag.clone() },
+			CompletedPart { part_number: 2, etag: part_2.etag.clone() },
+		];
+		let error = bucket.complete_multipart(&upload_id, &parts).await.err().unwrap();
+		assert!(matches!(error, SolarError::EntityTooSmall));
+
+		let error = bucket.complete_multipart("ffffffffffffffffffffffffffffffff", &parts).await.err().unwrap();
+		assert!(matches!(error, SolarError::NoSuchUpload(_)));
+
+		assert_eq!(bucket.list_parts(&upload_id).await.unwrap().len(), 2);
+		let (key, _) = bucket.complete_multipart(&upload_id, &parts[1..]).await.unwrap();
+		assert_eq!(bucket.get(&key, false).await.unwrap().buffer.unwrap(), b".log");
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_complete_failed_commit() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let dir_key = bucket.add(&KeyValue { key: None, name: "docs".to_string(), value: None }).await.unwrap();
+		let upload_id = bucket.create_multipart(Some(&dir_key), "index.js").await.unwrap();
+		let part_1 = bucket.upload_part(&upload_id, 1, &mut b"console".as_slice()).await.unwrap();
+
+		// The directory is gone from the disk only, so the commit fails after the intent.
+		let dir_path = Path::new(&bucket.path).join("files").join("docs");
+		fs::remove_dir(&dir_path).await.unwrap();
+		let parts = vec![CompletedPart { part_number: 1, etag: part_1.etag.clone() }];
+		assert!(bucket.complete_multipart(&upload_id, &parts).await.is_err());
+
+		// The part is still there to complete the upload with.
+		assert_eq!(bucket.list_parts(&upload_id).await.unwrap().len(), 1);
+		fs::create_dir(&dir_path).await.unwrap();
+		let (key, _) = bucket.complete_multipart(&upload_id, &parts).await.unwrap();
+		assert_eq!(bucket.get(&key, false).await.unwrap().buffer.unwrap(), b"console");
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_upload_part_quota() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+
+		let upload_id = bucket.create_multipart(None, "video.mp4").await.unwrap();
+		let data = vec![1u8; MIN_PART_SIZE as usize];
+		bucket.upload_part(&upload_id, 1, &mut data.as_slice()).await.unwrap();
+		bucket.upload_part(&upload_id, 1, &mut data.as_slice()).await.unwrap();
+		bucket.upload_part(&upload_id, 2, &mut data.as_slice()).await.unwrap();
+
+		let err = bucket.upload_part(&upload_id, 3, &mut b"1".as_slice()).await.is_err();
+		assert!(err);
+		assert_eq!(bucket.list_parts(&upload_id).await.unwrap().len(), 2);
+
+		let err = bucket.upload_part(&upload_id, 0, &mut b"1".as_slice()).await.is_err();
+		assert!(err);
+
+		let usage = Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space;
+		assert_eq!(usage, 2 * MIN_PART_SIZE);
+
+		bucket.abort_multipart(&upload_id).await.unwrap();
+		let usage = Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space;
+		assert_eq!(usage, 0);
+		assert!(bucket.list_parts(&upload_id).await.is_err());
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_clear_multiparts() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+
+		let upload_id = bucket.create_multipart(None, "index.js").await.unwrap();
+		bucket.upload_part(&upload_id, 1, &mut b"console.log".as_slice()).await.unwrap();
+		assert_eq!(bucket.get_multiparts().await.unwrap().len(), 1);
+
+		let cleared = bucket.clear_multiparts(chrono::Utc::now().timestamp() - 60).await.unwrap();
+		assert!(cleared.is_empty());
+
+		let cleared = bucket.clear_multiparts(chrono::Utc::now().timestamp() + 1).await.unwrap();
+		assert_eq!(cleared, vec![upload_id]);
+		assert!(bucket.get_multiparts().await.unwrap().is_empty());
+
+		let usage = Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space;
+		assert_eq!(usage, 0);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[test]
+	fn test_concurrent_parts() {
+		let runtime = tokio::runtime::Runtime::new().unwrap();
+		let (path, bucket) = runtime.block_on(self::prepare_test()).unwrap();
+		let upload_id = runtime.block_on(bucket.create_multipart(None, "index.js")).unwrap();
+
+		// Every thread writes its own part again and again, reserving the quota side by side.
+		let threads: Vec<std::thread::JoinHandle<()>> = (1..=16).map(|part_number| {
+			let (store, uuid, upload_id) = (bucket.store.clone(), bucket.uuid.clone(), upload_id.clone());
+			return std::thread::spawn(move || {
+				let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+				runtime.block_on(async {
+					let bucket = Bucket::open(store, &uuid).await.unwrap();
+					for _ in 0..40 {
+						bucket.upload_part(&upload_id, part_number, &mut [0u8; 10].as_slice()).await.unwrap();
+					}
+				});
+			});
+		}).collect();
+		for thread in threads {
+			thread.join().unwrap();
+		}
+
+		let usage = runtime.block_on(Bucket::open(bucket.store.clone(), &bucket.uuid)).unwrap().usage_space;
+		assert_eq!(usage, 160);
+
+		runtime.block_on(fs::remove_dir_all(path)).is_err();
+	}
+
+	#[tokio::test]
+	async fn test_clear_multiparts_writing_part() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+
+		let upload_id = bucket.create_multipart(None, "index.js").await.unwrap();
+		let part_path = Path::new(&bucket.path).join("multipart").join(&upload_id).join(format!("1.{}", Uuid::new_v4().simple()));
+		fs::write(&part_path, b"console").await.unwrap();
+
+		// A part on its way keeps the upload.
+		assert!(bucket.clear_multiparts(chrono::Utc::now().timestamp() + 1).await.unwrap().is_empty());
+
+		// A part left behind long ago doesn't.
+		let file = std::fs::File::options().write(true).open(&part_path).unwrap();
+		file.set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60)).unwrap();
+		assert_eq!(bucket.clear_multiparts(chrono::Utc::now().timestamp() + 1).await.unwrap(), vec![upload_id]);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_range {
+	use crate::bucket::range::ByteRange;
+
+	#[test]
+	fn test_parse() {
+		assert_eq!(ByteRange::parse("bytes=0-99").unwrap(), ByteRange::Between(0, 99));
+		assert_eq!(ByteRange::parse("bytes=100-").unwrap(), ByteRange::From(100));
+		assert_eq!(ByteRange::parse("bytes=-500").unwrap(), ByteRange::Suffix(500));
+
+		assert!(ByteRange::parse("bytes=9-1").is_err());
+		assert!(ByteRange::parse("bytes=-").is_err());
+		assert!(ByteRange::parse("bytes=0-1,5-6").is_err());
+		assert!(ByteRange::parse("0-99").is_err());
+	}
+
+	#[test]
+	fn test_resolve() {
+		assert_eq!(ByteRange::Between(0, 99).resolve(1000).unwrap(), (0, 100));
+		assert_eq!(ByteRange::Between(900, 2000).resolve(1000).unwrap(), (900, 100));
+		assert_eq!(ByteRange::From(100).resolve(1000).unwrap(), (100, 900));
+		assert_eq!(ByteRange::Suffix(500).resolve(1000).unwrap(), (500, 500));
+		assert_eq!(ByteRange::Suffix(5000).resolve(1000).unwrap(), (0, 1000));
+
+		assert!(ByteRange::From(1000).resolve(1000).is_err());
+		assert!(ByteRange::Suffix(0).resolve(1000).is_err());
+		assert!(ByteRange::Between(0, 0).resolve(0).is_err());
+	}
+}
+
+#[cfg(test)]
+mod tests_versioning {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use tokio::io::AsyncReadExt;
+	use uuid::Uuid;
+
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::error::{Result, SolarError};
+	use crate::storage::store::{Storage, Store};
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await?;
+		let bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+		bucket.set_versioning(true).await?;
+		return Ok((path, bucket));
+	}
+
+	async fn read_version(bucket: &Bucket, key: &String, version_id: &str) -> Vec<u8> {
+		let mut data = Vec::new();
+		let mut reader = bucket.read_version(key, version_id, None).await.unwrap();
+		reader.read_to_end(&mut data).await.unwrap();
+		return data;
+	}
+
+	#[tokio::test]
+	async fn test_versions() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+		assert!(bucket.is_versioning().await.unwrap());
+
+		let key = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v1".to_vec()) }).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v22".to_vec()) }).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v333".to_vec()) }).await.unwrap();
+
+		let versions = bucket.get_versions(&key).await.unwrap();
+		assert_eq!(versions.len(), 2);
+		assert_eq!(read_version(&bucket, &key, &versions[0].version_id).await, b"v22");
+		assert_eq!(read_version(&bucket, &key, &versions[1].version_id).await, b"v1");
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space, 9);
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 9);
+
+		bucket.restore_version(&key, &versions[1].version_id).await.unwrap();
+		assert_eq!(bucket.get(&key, false).await.unwrap().buffer.unwrap(), b"v1");
+		assert_eq!(bucket.get_versions(&key).await.unwrap().len(), 3);
+
+		bucket.delete_version(&key, &versions[0].version_id).await.unwrap();
+		assert_eq!(bucket.get_versions(&key).await.unwrap().len(), 2);
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space, 8);
+
+		let error = bucket.read_version(&key, &versions[0].version_id, None).await.err().unwrap();
+		assert!(matches!(error, SolarError::NoSuchVersion(_)));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_failed_commit_keeps_usage() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v1".to_vec()) }).await.unwrap();
+		// Keeping the old content as a version fails, so the commit does.
+		fs::write(Path::new(&bucket.path).join("versions"), b"").await.unwrap();
+		assert!(bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v22".to_vec()) }).await.is_err());
+
+		assert_eq!(bucket.get(&key, false).await.unwrap().buffer.unwrap(), b"v1");
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space, 2);
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 2);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_versioning_off() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+		bucket.set_versioning(false).await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v1".to_vec()) }).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v22".to_vec()) }).await.unwrap();
+
+		assert!(bucket.get_versions(&key).await.unwrap().is_empty());
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space, 3);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_rename_and_remove() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v1".to_vec()) }).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v22".to_vec()) }).await.unwrap();
+
+		let keys = bucket.rename(&key, &"main.js".to_string()).await.unwrap();
+		let versions = bucket.get_versions(&keys[0]).await.unwrap();
+		assert_eq!(versions.len(), 1);
+		assert_eq!(read_version(&bucket, &keys[0], &versions[0].version_id).await, b"v1");
+
+		bucket.remove(keys).await.unwrap();
+		assert!(!Path::new(&bucket.path).join("versions").join(&versions[0].version_id).exists());
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space, 0);
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 0);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_remove_dir_keeps_other_versions() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let mut keys = Vec::new();
+		for name in ["a_b", "axb"] {
+			let dir_key = bucket.add(&KeyValue { key: None, name: name.to_string(), value: None }).await.unwrap();
+			bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "index.js".to_string(), value: Some(b"v1".to_vec()) }).await.unwrap();
+			keys.push(bucket.add(&KeyValue { key: Some(dir_key), name: "index.js".to_string(), value: Some(b"v22".to_vec()) }).await.unwrap());
+		}
+
+		bucket.remove(vec![Bucket::calculate_key("a_b/")]).await.unwrap();
+		assert_eq!(bucket.get_versions(&keys[1]).await.unwrap().len(), 1);
+		assert_eq!(Store::open(&path).await.unwrap().usage_space, 5);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_lifecycle {
+	use std::path::{Path, PathBuf};
+
+	use chrono::Utc;
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::lifecycle::LifecycleRule;
+	use crate::error::{Result, SolarError};
+	use crate::storage::store::{Storage, Store};
+
+	const DAY: i64 = 24 * 60 * 60;
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await?;
+		let bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+		return Ok((path, bucket));
+	}
+
+	fn rule(id: &str, prefix: &str) -> LifecycleRule {
+		return LifecycleRule {
+			id: id.to_string(),
+			prefix: prefix.to_string(),
+			tag: None,
+			expiration_days: None,
+			noncurrent_days: None,
+			abort_multipart_days: None,
+			enabled: true,
+		};
+	}
+
+	#[tokio::test]
+	async fn test_tags() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"console.log".to_vec()) }).await.unwrap();
+		let tags = vec![("team".to_string(), "web".to_string()), ("env".to_string(), "dev".to_string())];
+		bucket.set_tags(&key, &tags).await.unwrap();
+		assert_eq!(bucket.get_tags(&key).await.unwrap(), vec![("env".to_string(), "dev".to_string()), ("team".to_string(), "web".to_string())]);
+
+		let tags = vec![("env".to_string(), "dev".to_string()), ("env".to_string(), "prod".to_string())];
+		assert!(bucket.set_tags(&key, &tags).await.is_err());
+
+		let error = bucket.get_tags(&"MISSING".to_string()).await.err().unwrap();
+		assert!(matches!(error, SolarError::KeyNotFound(_)));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_set_lifecycle() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+
+		assert!(bucket.set_lifecycle(&[rule("empty", "")]).await.is_err());
+		assert!(bucket.set_lifecycle(&[LifecycleRule { expiration_days: Some(0), ..rule("zero", "") }]).await.is_err());
+
+		let rules = vec![
+			LifecycleRule { expiration_days: Some(30), tag: Some(("env".to_string(), "dev".to_string())), ..rule("logs", "logs/") },
+			LifecycleRule { noncurrent_days: Some(7), abort_multipart_days: Some(1), enabled: false, ..rule("all", "") },
+		];
+		bucket.set_lifecycle(&rules).await.unwrap();
+		assert_eq!(bucket.get_lifecycle().await.unwrap(), vec![rules[1].clone(), rules[0].clone()]);
+
+		bucket.set_lifecycle(&[]).await.unwrap();
+		assert!(bucket.get_lifecycle().await.unwrap().is_empty());
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_apply_expiration() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let dir_key = bucket.add(&KeyValue { key: None, name: "logs".to_string(), value: None }).await.unwrap();
+		let key_1 = bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "a.log".to_string(), value: Some(b"a".to_vec()) }).await.unwrap();
+		let key_2 = bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "b.log".to_string(), value: Some(b"b".to_vec()) }).await.unwrap();
+		let key_3 = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"console.log".to_vec()) }).await.unwrap();
+		bucket.set_tags(&key_2, &[("keep".to_string(), "true".to_string())]).await.unwrap();
+		bucket.set_tags(&key_3, &[("keep".to_string(), "false".to_string())]).await.unwrap();
+
+		bucket.set_lifecycle(&[
+			LifecycleRule { expiration_days: Some(1), ..rule("logs", "logs/") },
+			LifecycleRule { expiration_days: Some(3), tag: Some(("keep".to_string(), "false".to_string())), ..rule("tagged", "") },
+		]).await.unwrap();
+
+		let now = Utc::now().timestamp();
+		assert!(bucket.apply_lifecycle(now).await.unwrap().expired.is_empty());
+
+		let result = bucket.apply_lifecycle(now + 2 * DAY).await.unwrap();
+		assert_eq!(result.expired.len(), 2);
+		assert!(result.expired.contains(&key_1) && result.expired.contains(&key_2));
+		assert_eq!(bucket.get_deletes().await.unwrap().len(), 2);
+
+		let result = bucket.apply_lifecycle(now + 4 * DAY).await.unwrap();
+		assert_eq!(result.expired, vec![key_3]);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_apply_versions_and_uploads() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+		bucket.set_versioning(true).await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v1".to_vec()) }).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"v22".to_vec()) }).await.unwrap();
+		let upload_id = bucket.create_multipart(None, "video.mp4").await.unwrap();
+		bucket.upload_part(&upload_id, 1, &mut b"part".as_slice()).await.unwrap();
+
+		bucket.set_lifecycle(&[LifecycleRule { noncurrent_days: Some(1), abort_multipart_days: Some(2), ..rule("cleanup", "") }]).await.unwrap();
+
+		let now = Utc::now().timestamp();
+		let result = bucket.apply_lifecycle(now + DAY + 1).await.unwrap();
+		assert_eq!(result.versions.len(), 1);
+		assert!(result.uploads.is_empty());
+		assert!(bucket.get_versions(&key).await.unwrap().is_empty());
+
+		let result = bucket.apply_lifecycle(now + 2 * DAY + 1).await.unwrap();
+		assert_eq!(result.uploads, vec![upload_id]);
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space, 3);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_checksum {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::checksum::ChecksumAlgorithm;
+	use crate::error::Result;
+	use crate::storage::store::{Storage, Store};
+
+	const HELLO_MD5: &str = "5d41402abc4b2a76b9719d911017c592";
+	const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+	const HELLO_CRC32C: &str = "9a71bb4c";
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await?;
+		let bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+		return Ok((path, bucket));
+	}
+
+	#[tokio::test]
+	async fn test_md5_etag() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "hello.txt".to_string(), value: Some(b"hello".to_vec()) }).await.unwrap();
+		let item = bucket.get(&key, true).await.unwrap();
+		assert_eq!(item.etag.as_deref(), Some(HELLO_MD5));
+		assert_eq!(item.sha256, None);
+		assert_eq!(item.crc32c, None);
+
+		let properties = bucket.properties(&key).await.unwrap();
+		assert_eq!(properties.etag.as_deref(), Some(HELLO_MD5));
+
+		let dir_key = bucket.add(&KeyValue { key: None, name: "docs".to_string(), value: None }).await.unwrap();
+		assert_eq!(bucket.get(&dir_key, true).await.unwrap().etag, None);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_checksum_algorithms() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		assert!(bucket.get_checksum_algorithms().await.unwrap().is_empty());
+		bucket.set_checksum_algorithms(&[ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Crc32c]).await.unwrap();
+		assert_eq!(bucket.get_checksum_algorithms().await.unwrap(), vec![ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Crc32c]);
+
+		let key = bucket.add(&KeyValue { key: None, name: "hello.txt".to_string(), value: Some(b"hello".to_vec()) }).await.unwrap();
+		let properties = bucket.properties(&key).await.unwrap();
+		assert_eq!(properties.etag.as_deref(), Some(HELLO_MD5));
+		assert_eq!(properties.sha256.as_deref(), Some(HELLO_SHA256));
+		assert_eq!(properties.crc32c.as_deref(), Some(HELLO_CRC32C));
+
+		// Replacing the file replaces its checksums.
+		bucket.set_checksum_algorithms(&[ChecksumAlgorithm::Crc32c]).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "hello.txt".to_string(), value: Some(b"world".to_vec()) }).await.unwrap();
+		let item = bucket.get(&key, true).await.unwrap();
+		assert_eq!(item.etag.as_deref(), Some("7d793037a0760186574b0282f2f435e7"));
+		assert_eq!(item.sha256, None);
+		assert_eq!(item.crc32c.as_deref(), Some("31aa814e"));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_copy_keeps_checksums() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+		bucket.set_checksum_algorithms(&[ChecksumAlgorithm::Sha256]).await.unwrap();
+
+		let docs_key = bucket.add(&KeyValue { key: None, name: "docs".to_string(), value: None }).await.unwrap();
+		let copies_key = bucket.add(&KeyValue { key: None, name: "copies".to_string(), value: None }).await.unwrap();
+		let key = bucket.add(&KeyValue { key: Some(docs_key.clone()), name: "hello.txt".to_string(), value: Some(b"hello".to_vec()) }).await.unwrap();
+
+		bucket.copy(&key, &copies_key).await.unwrap();
+		bucket.copy(&docs_key, &copies_key).await.unwrap();
+		for copy_path in ["copies/hello.txt", "copies/docs/hello.txt"] {
+			let properties = bucket.properties(&Bucket::calculate_key(copy_path)).await.unwrap();
+			assert_eq!(properties.etag.as_deref(), Some(HELLO_MD5));
+			assert_eq!(properties.sha256.as_deref(), Some(HELLO_SHA256));
+		}
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_precondition {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::precondition::Preconditions;
+	use crate::error::{Result, SolarError};
+	use crate::storage::store::{Storage, Store};
+
+	const HELLO_MD5: &str = "5d41402abc4b2a76b9719d911017c592";
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await?;
+		let bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+		return Ok((path, bucket));
+	}
+
+	fn hello(name: &str, value: &[u8]) -> KeyValue {
+		return KeyValue { key: None, name: name.to_string(), value: Some(value.to_vec()) };
+	}
+
+	#[tokio::test]
+	async fn test_add_if() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		let if_absent = Preconditions { if_none_match: Some("*".to_string()), ..Default::default() };
+		let key = bucket.add_if(&hello("hello.txt", b"hello"), &if_absent).await.unwrap();
+
+		let error = bucket.add_if(&hello("hello.txt", b"world"), &if_absent).await.err().unwrap();
+		assert!(matches!(error, SolarError::PreconditionFailed(_)));
+		assert_eq!(bucket.get(&key, false).await.unwrap().buffer.unwrap(), b"hello");
+		assert_eq!(Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap().usage_space, 5);
+
+		let if_other = Preconditions { if_match: Some("\"0123\"".to_string()), ..Default::default() };
+		let error = bucket.add_if(&hello("hello.txt", b"world"), &if_other).await.err().unwrap();
+		assert!(matches!(error, SolarError::PreconditionFailed(_)));
+		let error = bucket.add_if(&hello("missing.txt", b"world"), &if_other).await.err().unwrap();
+		assert!(matches!(error, SolarError::PreconditionFailed(_)));
+
+		let if_hello = Preconditions { if_match: Some(format!("\"0123\", \"{}\"", HELLO_MD5)), ..Default::default() };
+		bucket.add_if(&hello("hello.txt", b"world"), &if_hello).await.unwrap();
+		assert_eq!(bucket.get(&key, false).await.unwrap().buffer.unwrap(), b"world");
+
+		let dir = KeyValue { key: None, name: "docs".to_string(), value: None };
+		bucket.add_if(&dir, &if_absent).await.unwrap();
+		assert!(matches!(bucket.add_if(&dir, &if_absent).await.err().unwrap(), SolarError::PreconditionFailed(_)));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_get_if() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+		let key = bucket.add(&hello("hello.txt", b"hello")).await.unwrap();
+		let update_at = bucket.properties(&key).await.unwrap().update_at;
+
+		let if_none_match = Preconditions { if_none_match: Some(HELLO_MD5.to_string()), ..Default::default() };
+		assert!(matches!(bucket.get_if(&key, false, &if_none_match).await.err().unwrap(), SolarError::NotModified(_)));
+
+		let if_modified = Preconditions { if_modified_since: Some(update_at), ..Default::default() };
+		assert!(matches!(bucket.get_if(&key, false, &if_modified).await.err().unwrap(), SolarError::NotModified(_)));
+		let if_modified = Preconditions { if_modified_since: Some(update_at - 1), ..Default::default() };
+		assert_eq!(bucket.get_if(&key, false, &if_modified).await.unwrap().buffer.unwrap(), b"hello");
+
+		// The time condition doesn't count next to an ETag condition.
+		let both = Preconditions { if_none_match: Some("\"0123\"".to_string()), if_modified_since: Some(update_at), ..Default::default() };
+		assert!(bucket.get_if(&key, true, &both).await.is_ok());
+
+		let if_unmodified = Preconditions { if_unmodified_since: Some(update_at - 1), ..Default::default() };
+		assert!(matches!(bucket.get_if(&key, true, &if_unmodified).await.err().unwrap(), SolarError::PreconditionFailed(_)));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_set_delete_and_copy_if() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+		let key = bucket.add(&hello("hello.txt", b"hello")).await.unwrap();
+		let dir_key = bucket.add(&KeyValue { key: None, name: "docs".to_string(), value: None }).await.unwrap();
+
+		let if_other = Preconditions { if_match: Some("0123".to_string()), ..Default::default() };
+		let error = bucket.set_delete_if(vec![key.clone()], &if_other).await.err().unwrap();
+		assert!(matches!(error, SolarError::PreconditionFailed(_)));
+		assert!(!bucket.properties(&key).await.unwrap().is_delete);
+
+		let error = bucket.copy_if(&key, &dir_key, &if_other).await.err().unwrap();
+		assert!(matches!(error, SolarError::PreconditionFailed(_)));
+		assert!(bucket.get_items(Some(&dir_key)).await.unwrap().is_empty());
+
+		let if_hello = Preconditions { if_match: Some(HELLO_MD5.to_string()), ..Default::default() };
+		assert_eq!(bucket.copy_if(&key, &dir_key, &if_hello).await.unwrap().len(), 1);
+		bucket.set_delete_if(vec![key.clone()], &if_hello).await.unwrap();
+		assert!(bucket.properties(&key).await.unwrap().is_delete);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_listing {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::listing::{ListKind, ListObjectsOptions, ListOptions, ListSort};
+	use crate::error::{Result, SolarError};
+	use crate::storage::store::{Storage, Store};
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket, String)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await?;
+		let mut bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+
+		let dir_key = bucket.add(&KeyValue { key: None, name: "photos".to_string(), value: None }).await?;
+		let files: [(&str, &[u8]); 4] = [("b.png", b"12345"), ("a.txt", b"1"), ("d.jpg", b"123"), ("c.txt", b"12")];
+		for (name, value) in files {
+			bucket.add(&KeyValue { key: Some(dir_key.clone()), name: name.to_string(), value: Some(value.to_vec()) }).await?;
+		}
+		bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "albums".to_string(), value: None }).await?;
+
+		return Ok((path, bucket, dir_key));
+	}
+
+	fn names(items: &[crate::bucket::fs_metadata::FsItem]) -> Vec<&str> {
+		return items.iter().map(|item| item.name.as_str()).collect();
+	}
+
+	#[tokio::test]
+	async fn test_list_pages() {
+		let (path, bucket, dir_key) = self::prepare_test().await.unwrap();
+
+		let mut options = ListOptions { page_size: 2, ..Default::default() };
+		let page = bucket.list_items(Some(&dir_key), &options).await.unwrap();
+		assert_eq!(names(&page.items), vec!["a.txt", "albums"]);
+
+		options.continuation_token = page.next_token;
+		let page = bucket.list_items(Some(&dir_key), &options).await.unwrap();
+		assert_eq!(names(&page.items), vec!["b.png", "c.txt"]);
+
+		options.continuation_token = page.next_token;
+		let page = bucket.list_items(Some(&dir_key), &options).await.unwrap();
+		assert_eq!(names(&page.items), vec!["d.jpg"]);
+		assert!(page.next_token.is_none());
+
+		let page = bucket.list_items(None, &ListOptions::default()).await.unwrap();
+		assert_eq!(names(&page.items), vec!["photos"]);
+
+		let error = bucket.list_items(None, &ListOptions { page_size: 0, ..Default::default() }).await.err().unwrap();
+		assert!(matches!(error, SolarError::InvalidInput(_)));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_list_sort() {
+		let (path, bucket, dir_key) = self::prepare_test().await.unwrap();
+
+		let options = ListOptions { sort: ListSort::Size, kind: ListKind::Files, descending: true, page_size: 3, ..Default::default() };
+		let page = bucket.list_items(Some(&dir_key), &options).await.unwrap();
+		assert_eq!(names(&page.items), vec!["b.png", "d.jpg", "c.txt"]);
+
+		// A token only continues the listing it comes from.
+		let other = ListOptions { continuation_token: page.next_token.clone(), ..Default::default() };
+		assert!(bucket.list_items(Some(&dir_key), &other).await.is_err());
+
+		let options = ListOptions { continuation_token: page.next_token, ..options };
+		let page = bucket.list_items(Some(&dir_key), &options).await.unwrap();
+		assert_eq!(names(&page.items), vec!["a.txt"]);
+
+		let options = ListOptions { sort: ListSort::Type, ..Default::default() };
+		let page = bucket.list_items(Some(&dir_key), &options).await.unwrap();
+		assert_eq!(names(&page.items), vec!["albums", "d.jpg", "b.png", "a.txt", "c.txt"]);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_list_filters() {
+		let (path, bucket, dir_key) = self::prepare_test().await.unwrap();
+
+		let options = ListOptions { kind: ListKind::Directories, ..Default::default() };
+		assert_eq!(names(&bucket.list_items(Some(&dir_key), &options).await.unwrap().items), vec!["albums"]);
+
+		let options = ListOptions { mime_prefix: Some("image/".to_string()), ..Default::default() };
+		assert_eq!(names(&bucket.list_items(Some(&dir_key), &options).await.unwrap().items), vec!["b.png", "d.jpg"]);
+
+		let options = ListOptions { name_glob: Some("?.txt".to_string()), ..Default::default() };
+		assert_eq!(names(&bucket.list_items(Some(&dir_key), &options).await.unwrap().items), vec!["a.txt", "c.txt"]);
+
+		let a_key = Bucket::calculate_key("photos/a.txt");
+		bucket.set_delete(vec![a_key.clone()]).await.unwrap();
+		assert_eq!(names(&bucket.list_items(Some(&dir_key), &options).await.unwrap().items), vec!["c.txt"]);
+
+		let error = bucket.list_items(Some(&a_key), &ListOptions::default()).await.err().unwrap();
+		assert!(matches!(error, SolarError::NotADirectory(_)));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_list_objects() {
+		let (path, mut bucket, _) = self::prepare_test().await.unwrap();
+		let albums_key = Bucket::calculate_key("photos/albums/");
+		bucket.add(&KeyValue { key: Some(albums_key.clone()), name: "cover.png".to_string(), value: Some(b"1".to_vec()) }).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "readme.md".to_string(), value: Some(b"1".to_vec()) }).await.unwrap();
+		let paths = |listing: &crate::bucket::listing::ObjectListing| listing.objects.iter().map(|object| object.path.clone()).collect::<Vec<String>>();
+
+		let listing = bucket.list_objects(&ListObjectsOptions::default()).await.unwrap();
+		assert_eq!(paths(&listing), vec!["photos/a.txt", "photos/albums/cover.png", "photos/b.png", "photos/c.txt", "photos/d.jpg", "readme.md"]);
+		assert_eq!(listing.objects[5].etag.as_deref(), Some("c4ca4238a0b923820dcc509a6f75849b"));
+		assert!(listing.next_continuation_token.is_none());
+
+		let mut options = ListObjectsOptions { prefix: "photos/".to_string(), delimiter: Some("/".to_string()), max_keys: 2, ..Default::default() };
+		let listing = bucket.list_objects(&options).await.unwrap();
+		assert_eq!(paths(&listing), vec!["photos/a.txt"]);
+		assert_eq!(listing.common_prefixes, vec!["photos/albums/"]);
+
+		options.continuation_token = listing.next_continuation_token;
+		let listing = bucket.list_objects(&options).await.unwrap();
+		assert_eq!(paths(&listing), vec!["photos/b.png", "photos/c.txt"]);
+
+		options.continuation_token = listing.next_continuation_token;
+		let listing = bucket.list_objects(&options).await.unwrap();
+		assert_eq!(paths(&listing), vec!["photos/d.jpg"]);
+		assert!(listing.next_continuation_token.is_none());
+
+		// Trashed files and the files of trashed directories are left out.
+		bucket.set_delete(vec![albums_key, Bucket::calculate_key("photos/c.txt")]).await.unwrap();
+		let options = ListObjectsOptions { prefix: "photos/".to_string(), start_after: Some("photos/a.txt".to_string()), ..Default::default() };
+		assert_eq!(paths(&bucket.list_objects(&options).await.unwrap()), vec!["photos/b.png", "photos/d.jpg"]);
+
+		let options = ListObjectsOptions { continuation_token: Some("bad".to_string()), ..Default::default() };
+		assert!(matches!(bucket.list_objects(&options).await.err().unwrap(), SolarError::InvalidInput(_)));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_search {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::access::Permission;
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::listing::ListKind;
+	use crate::bucket::search::{SearchItem, SearchOptions, SearchSort};
+	use crate::error::{Result, SolarError};
+	use crate::storage::store::{Storage, Store};
+	use crate::storage::users::Users;
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await?;
+		let mut bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+
+		let dir_key = bucket.add(&KeyValue { key: None, name: "photos".to_string(), value: None }).await?;
+		let files: [(&str, &[u8]); 3] = [("Beach.PNG", b"12345"), ("notes.txt", b"1"), ("city.jpg", b"123")];
+		for (name, value) in files {
+			bucket.add(&KeyValue { key: Some(dir_key.clone()), name: name.to_string(), value: Some(value.to_vec()) }).await?;
+		}
+		bucket.add(&KeyValue { key: None, name: "beach-notes.txt".to_string(), value: Some(b"12".to_vec()) }).await?;
+
+		return Ok((path, bucket));
+	}
+
+	fn paths(items: &[SearchItem]) -> Vec<&str> {
+		return items.iter().map(|item| item.path.as_str()).collect();
+	}
+
+	#[tokio::test]
+	async fn test_search_filters() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+
+		let page = bucket.search(&SearchOptions { name: Some("BEACH".to_string()), ..Default::default() }).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/Beach.PNG", "beach-notes.txt"]);
+		assert_eq!(page.items[0].mime_type.as_deref(), Some("image/png"));
+		assert_eq!(page.items[0].size, 5);
+
+		let page = bucket.search(&SearchOptions { name_glob: Some("*.txt".to_string()), ..Default::default() }).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["beach-notes.txt", "photos/notes.txt"]);
+
+		let page = bucket.search(&SearchOptions { mime_prefix: Some("image/".to_string()), ..Default::default() }).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/Beach.PNG", "photos/city.jpg"]);
+
+		let page = bucket.search(&SearchOptions { extension: Some(".png".to_string()), ..Default::default() }).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/Beach.PNG"]);
+
+		let page = bucket.search(&SearchOptions { min_size: Some(2), max_size: Some(3), ..Default::default() }).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["beach-notes.txt", "photos/city.jpg"]);
+
+		let page = bucket.search(&SearchOptions { kind: ListKind::Directories, ..Default::default() }).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/"]);
+
+		let now = chrono::Utc::now().timestamp() as u64;
+		let page = bucket.search(&SearchOptions { modified_after: Some(now + 60), ..Default::default() }).await.unwrap();
+		assert!(page.items.is_empty());
+		let page = bucket.search(&SearchOptions { created_before: Some(now + 60), kind: ListKind::Files, ..Default::default() }).await.unwrap();
+		assert_eq!(page.items.len(), 4);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_search_flags_and_renames() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+		let city_key = Bucket::calculate_key("photos/city.jpg");
+		let dir_key = Bucket::calculate_key("photos/");
+
+		bucket.set_favorites(vec![city_key.clone()]).await.unwrap();
+		let page = bucket.search(&SearchOptions { favorite: Some(true), ..Default::default() }).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/city.jpg"]);
+		assert!(page.items[0].is_favorite);
+
+		bucket.rename(&city_key, &"town.png".to_string()).await.unwrap();
+		let page = bucket.search(&SearchOptions { name: Some("town".to_string()), ..Default::default() }).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/town.png"]);
+		assert_eq!(page.items[0].mime_type.as_deref(), Some("image/png"));
+
+		// Trashing the directory hides the files in it.
+		bucket.set_delete(vec![dir_key]).await.unwrap();
+		let page = bucket.search(&SearchOptions::default()).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["beach-notes.txt"]);
+		let page = bucket.search(&SearchOptions { trashed: Some(true), kind: ListKind::Files, ..Default::default() }).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/Beach.PNG", "photos/notes.txt", "photos/town.png"]);
+		assert!(page.items.iter().all(|item| item.is_delete));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_search_pages() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+
+		let mut options = SearchOptions { sort: SearchSort::Size, descending: true, page_size: 3, kind: ListKind::Files, ..Default::default() };
+		let page = bucket.search(&options).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/Beach.PNG", "photos/city.jpg", "beach-notes.txt"]);
+
+		options.continuation_token = page.next_token;
+		let page = bucket.search(&options).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/notes.txt"]);
+		assert!(page.next_token.is_none());
+
+		let options = SearchOptions { sort: SearchSort::Path, page_size: 2, ..Default::default() };
+		let page = bucket.search(&options).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["beach-notes.txt", "photos/"]);
+
+		let other = SearchOptions { continuation_token: page.next_token, ..Default::default() };
+		assert!(matches!(bucket.search(&other).await.err().unwrap(), SolarError::InvalidInput(_)));
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_search_access() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+		let bob = Users::create(&path, "bob", false).await.unwrap();
+		bucket.grant(&bob.uuid, "photos/", Permission::Read).await.unwrap();
+		bucket.set_user(Some(&bob));
+
+		// `beach-notes.txt` comes first but is left out, the page still fills up.
+		let mut options = SearchOptions { sort: SearchSort::Path, page_size: 2, kind: ListKind::Files, ..Default::default() };
+		let page = bucket.search(&options).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/Beach.PNG", "photos/city.jpg"]);
+
+		options.continuation_token = page.next_token;
+		let page = bucket.search(&options).await.unwrap();
+		assert_eq!(paths(&page.items), vec!["photos/notes.txt"]);
+		assert!(page.next_token.is_none());
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_content {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::access::Permission;
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::content::{ContentMatch, CONTENT_SEARCH_LIMIT};
+	use crate::bucket::fs_metadata::FsMetadata;
+	use crate::error::Result;
+	use crate::storage::store::{Storage, Store};
+	use crate::storage::users::Users;
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket, String)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await?;
+		let mut bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+
+		let dir_key = bucket.add(&KeyValue { key: None, name: "docs".to_string(), value: None }).await?;
+		let files: [(&str, &[u8]); 3] = [
+			("readme.md", b"The quick brown fox jumps over the lazy dog"),
+			("main.rs", b"fn main() { println!(\"brown bear\"); }"),
+			("photo.png", b"brown fox"),
+		];
+		for (name, value) in files {
+			bucket.add(&KeyValue { key: Some(dir_key.clone()), name: name.to_string(), value: Some(value.to_vec()) }).await?;
+		}
+
+		return Ok((path, bucket, dir_key));
+	}
+
+	fn paths(matches: &[ContentMatch]) -> Vec<&str> {
+		let mut paths: Vec<&str> = matches.iter().map(|item| item.path.as_str()).collect();
+		paths.sort();
+		return paths;
+	}
+
+	#[tokio::test]
+	async fn test_content_index() {
+		let (path, mut bucket, dir_key) = self::prepare_test().await.unwrap();
+
+		assert!(!bucket.is_content_index().await.unwrap());
+		assert!(bucket.search_content("brown").await.unwrap().is_empty());
+
+		bucket.set_content_index(true).await.unwrap();
+		assert!(bucket.is_content_index().await.unwrap());
+		let matches = bucket.search_content("brown").await.unwrap();
+		assert_eq!(paths(&matches), vec!["docs/main.rs", "docs/readme.md"]);
+
+		let matches = bucket.search_content("LAZY fox").await.unwrap();
+		assert_eq!(paths(&matches), vec!["docs/readme.md"]);
+		assert!(matches[0].snippet.contains("<mark>lazy</mark>"));
+		assert!(matches[0].snippet.contains("<mark>fox</mark>"));
+
+		bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "notes.txt".to_string(), value: Some(b"a lazy afternoon".to_vec()) }).await.unwrap();
+		assert_eq!(paths(&bucket.search_content("lazy").await.unwrap()), vec!["docs/notes.txt", "docs/readme.md"]);
+
+		bucket.add(&KeyValue { key: Some(dir_key), name: "notes.txt".to_string(), value: Some(b"a busy morning".to_vec()) }).await.unwrap();
+		assert_eq!(paths(&bucket.search_content("lazy").await.unwrap()), vec!["docs/readme.md"]);
+		assert_eq!(paths(&bucket.search_content("\"busy").await.unwrap()), vec!["docs/notes.txt"]);
+		assert_eq!(paths(&bucket.search_content("busy").await.unwrap()), vec!["docs/notes.txt"]);
+
+		bucket.set_content_index(false).await.unwrap();
+		assert!(bucket.search_content("brown").await.unwrap().is_empty());
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_content_follows_paths() {
+		let (path, mut bucket, dir_key) = self::prepare_test().await.unwrap();
+		bucket.set_content_index(true).await.unwrap();
+
+		let other_key = bucket.add(&KeyValue { key: None, name: "other".to_string(), value: None }).await.unwrap();
+		bucket.rename(&dir_key, &"notes".to_string()).await.unwrap();
+		assert_eq!(paths(&bucket.search_content("brown").await.unwrap()), vec!["notes/main.rs", "notes/readme.md"]);
+
+		let dir_key = FsMetadata::calculate_hash("notes/");
+		bucket.copy(&dir_key, &other_key).await.unwrap();
+		assert_eq!(paths(&bucket.search_content("quick").await.unwrap()), vec!["notes/readme.md", "other/notes/readme.md"]);
+
+		let readme_key = FsMetadata::calculate_hash("notes/readme.md");
+		bucket.move_items(&readme_key, &other_key).await.unwrap();
+		assert_eq!(paths(&bucket.search_content("quick").await.unwrap()), vec!["other/notes/readme.md", "other/readme.md"]);
+
+		let copy_key = FsMetadata::calculate_hash("other/notes/");
+		bucket.set_delete(vec![copy_key.clone()]).await.unwrap();
+		assert_eq!(paths(&bucket.search_content("quick").await.unwrap()), vec!["other/readme.md"]);
+
+		bucket.remove(vec![copy_key]).await.unwrap();
+		bucket.remove(vec![FsMetadata::calculate_hash("other/readme.md")]).await.unwrap();
+		assert!(bucket.search_content("quick").await.unwrap().is_empty());
+		assert_eq!(paths(&bucket.search_content("bear").await.unwrap()), vec!["notes/main.rs"]);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_content_search_access() {
+		let (path, mut bucket, _) = self::prepare_test().await.unwrap();
+		bucket.set_content_index(true).await.unwrap();
+
+		// Better matches than the ones in `docs/`, more than a search returns.
+		let private_key = bucket.add(&KeyValue { key: None, name: "private".to_string(), value: None }).await.unwrap();
+		for index in 0..CONTENT_SEARCH_LIMIT {
+			bucket.add(&KeyValue { key: Some(private_key.clone()), name: format!("{index}.txt"), value: Some(b"brown".to_vec()) }).await.unwrap();
+		}
+		assert_eq!(bucket.search_content("brown").await.unwrap().len(), CONTENT_SEARCH_LIMIT);
+
+		let bob = Users::create(&path, "bob", false).await.unwrap();
+		bucket.grant(&bob.uuid, "docs/", Permission::Read).await.unwrap();
+		bucket.set_user(Some(&bob));
+		assert_eq!(paths(&bucket.search_content("brown").await.unwrap()), vec!["docs/main.rs", "docs/readme.md"]);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_fsck {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::fs_metadata::FsMetadata;
+	use crate::bucket::fsck::Inconsistency;
+	use crate::error::Result;
+	use crate::storage::store::{Storage, Store};
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await?;
+		let mut bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+
+		let dir_key = bucket.add(&KeyValue { key: None, name: "docs".to_string(), value: None }).await?;
+		for name in ["a.txt", "b.txt", "c.txt"] {
+			bucket.add(&KeyValue { key: Some(dir_key.clone()), name: name.to_string(), value: Some(b"12345".to_vec()) }).await?;
+		}
+
+		return Ok((path, bucket));
+	}
+
+	#[tokio::test]
+	async fn test_check_clean_bucket() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		bucket.set_favorites(vec![FsMetadata::calculate_hash("docs/a.txt")]).await.unwrap();
+		bucket.set_delete(vec![FsMetadata::calculate_hash("docs/b.txt")]).await.unwrap();
+
+		let report = bucket.check(false).await.unwrap();
+		assert!(report.inconsistencies.is_empty());
+		assert!(!report.repaired);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_check_and_repair() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+		let files_path = Path::new(&bucket.path).join("files");
+
+		fs::remove_file(files_path.join("docs/a.txt")).await.unwrap();
+		fs::create_dir(files_path.join("notes")).await.unwrap();
+		fs::write(files_path.join("notes/new.txt"), b"123").await.unwrap();
+		FsMetadata::new(files_path.join("docs/b.txt")).await.unwrap().set_favorite().await.unwrap();
+		FsMetadata::new(files_path.join("docs/c.txt")).await.unwrap().set_delete().await.unwrap();
+		FsMetadata::new(&bucket.path).await.unwrap().set_usage_space(1).await.unwrap();
+
+		let expected = vec![
+			Inconsistency::OrphanedRow { key: FsMetadata::calculate_hash("docs/a.txt"), path: "docs/a.txt".to_string() },
+			Inconsistency::UntrackedPath { key: FsMetadata::calculate_hash("notes/"), path: "notes/".to_string() },
+			Inconsistency::UntrackedPath { key: FsMetadata::calculate_hash("notes/new.txt"), path: "notes/new.txt".to_string() },
+			Inconsistency::Favorite { key: FsMetadata::calculate_hash("docs/b.txt"), path: "docs/b.txt".to_string(), is_favorite: false },
+			Inconsistency::Trash { key: FsMetadata::calculate_hash("docs/c.txt"), path: "docs/c.txt".to_string(), is_delete: false },
+			Inconsistency::UsageSpace { recorded: 1, actual: 13 },
+		];
+		assert_eq!(bucket.check(false).await.unwrap().inconsistencies, expected);
+		assert_eq!(bucket.check(false).await.unwrap().inconsistencies, expected);
+
+		let report = bucket.check(true).await.unwrap();
+		assert!(report.repaired);
+		assert_eq!(report.inconsistencies, expected);
+		assert!(bucket.check(false).await.unwrap().inconsistencies.is_empty());
+
+		assert!(!bucket.exists(&FsMetadata::calculate_hash("docs/a.txt")).await.unwrap());
+		let item = bucket.get(&FsMetadata::calculate_hash("notes/new.txt"), false).await.unwrap();
+		assert_eq!(item.buffer.unwrap(), b"123");
+		assert!(!item.is_favorite);
+		assert!(!bucket.get(&FsMetadata::calculate_hash("docs/b.txt"), true).await.unwrap().is_favorite);
+		assert!(!bucket.get(&FsMetadata::calculate_hash("docs/c.txt"), true).await.unwrap().is_delete);
+		assert_eq!(bucket.usage_space, 13);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_count_usage_space() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+
+		// The parts of an upload take the quota of the bucket only, a file left in `tmp/` nothing.
+		let upload_id = bucket.create_multipart(None, "clip.mp4").await.unwrap();
+		bucket.upload_part(&upload_id, 1, &mut b"1234".as_slice()).await.unwrap();
+		fs::create_dir_all(Path::new(&bucket.path).join("tmp")).await.unwrap();
+		fs::write(Path::new(&bucket.path).join("tmp/leftover"), vec![0u8; 100]).await.unwrap();
+		assert!(bucket.check(false).await.unwrap().inconsistencies.is_empty());
+
+		FsMetadata::new(&bucket.path).await.unwrap().set_usage_space(1).await.unwrap();
+		let report = bucket.check(false).await.unwrap();
+		assert_eq!(report.inconsistencies, vec![Inconsistency::UsageSpace { recorded: 1, actual: 19 }]);
+
+		// The store counts the same way.
+		let mut store = Store::open(&path).await.unwrap();
+		assert_eq!(store.recalculation_usage_space().await.unwrap(), 15);
+		assert_eq!(Bucket::open(store, &bucket.uuid).await.unwrap().usage_space, 19);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_journal {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::bucket_db::BucketDB;
+	use crate::bucket::fs_metadata::FsMetadata;
+	use crate::bucket::journal::Intent;
+	use crate::error::Result;
+	use crate::storage::store::{Storage, Store};
+
+	async fn prepare_test() -> Result<(PathBuf, Bucket)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await?;
+		let mut bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+
+		let dir_key = bucket.add(&KeyValue { key: None, name: "docs".to_string(), value: None }).await?;
+		bucket.add(&KeyValue { key: Some(dir_key), name: "a.txt".to_string(), value: Some(b"12345".to_vec()) }).await?;
+		bucket.set_versioning(true).await?;
+
+		return Ok((path, bucket));
+	}
+
+	// Logs `intent` as if a process that is gone started it.
+	async fn add_intent(bucket: &Bucket, intent: &Intent) -> Result<()> {
+		let connection = BucketDB::open(bucket.path.as_str()).await?;
+		let id = Uuid::new_v4().simple().to_string();
+		return BucketDB::add_intent(&id, "gone", &serde_json::to_string(intent)?, 0, &connection).await;
+	}
+
+	async fn intent_count(bucket: &Bucket) -> i64 {
+		let connection = BucketDB::open(bucket.path.as_str()).await.unwrap();
+		return connection.query_row("SELECT count(*) FROM intents", [], |row| row.get(0)).unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_finished_operations() {
+		let (path, mut bucket) = self::prepare_test().await.unwrap();
+		let dir_key = FsMetadata::calculate_hash("docs/");
+
+		let other_key = bucket.add(&KeyValue { key: None, name: "other".to_string(), value: None }).await.unwrap();
+		bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "a.txt".to_string(), value: Some(b"123".to_vec()) }).await.unwrap();
+		bucket.copy(&dir_key, &other_key).await.unwrap();
+		bucket.rename(&FsMetadata::calculate_hash("docs/a.txt"), &"b.txt".to_string()).await.unwrap();
+		let moved_key = bucket.add(&KeyValue { key: None, name: "moved".to_string(), value: None }).await.unwrap();
+		bucket.move_items(&dir_key, &moved_key).await.unwrap();
+		assert_eq!(intent_count(&bucket).await, 0);
+
+		let moved = Path::new(&bucket.path).join("files/moved/docs/b.txt");
+		assert_eq!(fs::read(moved).await.unwrap(), b"123");
+		assert!(bucket.exists(&FsMetadata::calculate_hash("moved/docs/b.txt")).await.unwrap());
+		assert!(bucket.exists(&FsMetadata::calculate_hash("other/docs/a.txt")).await.unwrap());
+
+		// A failed operation leaves neither an intent nor a change behind.
+		assert!(bucket.copy(&FsMetadata::calculate_hash("moved/docs/"), &other_key).await.is_err());
+		assert!(bucket.add(&KeyValue { key: None, name: "other".to_string(), value: None }).await.is_err());
+		assert!(Path::new(&bucket.path).join("files/other/docs/a.txt").exists());
+		assert_eq!(intent_count(&bucket).await, 0);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_recover_intents() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+		let files_path = Path::new(&bucket.path).join("files");
+
+		// A rename done on disk but not in the database.
+		fs::rename(files_path.join("docs/a.txt"), files_path.join("docs/b.txt")).await.unwrap();
+		add_intent(&bucket, &Intent::Move { from: "docs/a.txt".to_string(), to: "docs/b.txt".to_string() }).await.unwrap();
+
+		// A copy, a directory and a new file on disk only.
+		fs::create_dir(files_path.join("copy")).await.unwrap();
+		fs::write(files_path.join("copy/a.txt"), b"12345").await.unwrap();
+		add_intent(&bucket, &Intent::Copy { to: "copy/".to_string() }).await.unwrap();
+		fs::create_dir(files_path.join("new")).await.unwrap();
+		add_intent(&bucket, &Intent::AddDir { path: "new/".to_string() }).await.unwrap();
+		fs::write(files_path.join("docs/c.txt"), b"123").await.unwrap();
+		fs::create_dir_all(Path::new(&bucket.path).join("tmp")).await.unwrap();
+		let temp_path = Path::new(&bucket.path).join("tmp/upload");
+		fs::write(&temp_path, b"123").await.unwrap();
+		add_intent(&bucket, &Intent::AddFile { path: "docs/c.txt".to_string(), temp_path: temp_path.to_string_lossy().to_string(), version_id: None }).await.unwrap();
+
+		let bucket = Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap();
+		assert_eq!(intent_count(&bucket).await, 0);
+		assert!(files_path.join("docs/a.txt").exists());
+		assert!(!files_path.join("docs/b.txt").exists());
+		assert!(!files_path.join("copy").exists());
+		assert!(!files_path.join("new").exists());
+		assert!(!files_path.join("docs/c.txt").exists());
+		assert!(!temp_path.exists());
+		assert_eq!(bucket.usage_space, 5);
+
+		let mut bucket = bucket;
+		assert!(bucket.check(false).await.unwrap().inconsistencies.is_empty());
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_recover_version() {
+		let (path, bucket) = self::prepare_test().await.unwrap();
+		let file_path = Path::new(&bucket.path).join("files/docs/a.txt");
+
+		// The replaced file went to `versions/`, the new one is in its place.
+		let version_id = Uuid::new_v4().simple().to_string();
+		fs::create_dir_all(Path::new(&bucket.path).join("versions")).await.unwrap();
+		fs::rename(&file_path, bucket.version_path(&version_id)).await.unwrap();
+		fs::write(&file_path, b"new").await.unwrap();
+		let intent = Intent::AddFile { path: "docs/a.txt".to_string(), temp_path: "tmp/gone".to_string(), version_id: Some(version_id.clone()) };
+		add_intent(&bucket, &intent).await.unwrap();
+
+		// The intents of the running processes are left alone: this one and another one,
+		// like a command line tool working on the same bucket.
+		let connection = BucketDB::open(bucket.path.as_str()).await.unwrap();
+		bucket.begin_intent(&connection, &Intent::AddDir { path: "docs/".to_string() }).await.unwrap();
+		let other = format!("{}:other", std::os::unix::process::parent_id());
+		let operation = serde_json::to_string(&Intent::AddDir { path: "docs/".to_string() }).unwrap();
+		BucketDB::add_intent("other", &other, &operation, 0, &connection).await.unwrap();
+
+		// An earlier process with the same pid, like the server before a container restart.
+		let new_path = Path::new(&bucket.path).join("files/new");
+		fs::create_dir(&new_path).await.unwrap();
+		let earlier = format!("{}:earlier", std::process::id());
+		let operation = serde_json::to_string(&Intent::AddDir { path: "new/".to_string() }).unwrap();
+		BucketDB::add_intent("earlier", &earlier, &operation, 0, &connection).await.unwrap();
+
+		let mut bucket = Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap();
+		assert_eq!(fs::read(&file_path).await.unwrap(), b"12345");
+		assert!(!new_path.exists());
+		assert!(!bucket.version_path(&version_id).exists());
+		assert!(bucket.get_versions(&FsMetadata::calculate_hash("docs/a.txt")).await.unwrap().is_empty());
+		assert_eq!(intent_count(&bucket).await, 2);
+		assert!(bucket.check(false).await.unwrap().inconsistencies.is_empty());
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_audit {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::audit::{AuditEntry, AuditFilter, AuditOperation};
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::fs_metadata::FsMetadata;
+	use crate::error::Result;
+	use crate::storage::store::{Storage, Store};
+
+	async fn prepare_test(logging: bool) -> Result<(PathBuf, Bucket)> {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, Some(logging)).await?;
+		let bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 999).await?;
+
+		return Ok((path, bucket));
+	}
+
+	fn operations(entries: &[AuditEntry]) -> Vec<(AuditOperation, &str)> {
+		return entries.iter().map(|entry| (entry.operation, entry.path.as_str())).collect();
+	}
+
+	#[tokio::test]
+	async fn test_audit_log() {
+		let (path, mut bucket) = self::prepare_test(true).await.unwrap();
+
+		bucket.set_author(Some("alice"), Some("first upload"));
+		let dir_key = bucket.add(&KeyValue { key: None, name: "docs".to_string(), value: None }).await.unwrap();
+		let key = bucket.add(&KeyValue { key: Some(dir_key.clone()), name: "a.txt".to_string(), value: Some(b"1".to_vec()) }).await.unwrap();
+
+		bucket.set_author(Some("bob"), None);
+		let other_key = bucket.add(&KeyValue { key: None, name: "other".to_string(), value: None }).await.unwrap();
+		let copies_key = bucket.add(&KeyValue { key: None, name: "copies".to_string(), value: None }).await.unwrap();
+		bucket.set_favorites(vec![key.clone()]).await.unwrap();
+		bucket.unset_favorites(vec![key.clone()]).await.unwrap();
+		bucket.rename(&key, &"b.txt".to_string()).await.unwrap();
+		let key = FsMetadata::calculate_hash("docs/b.txt");
+		bucket.copy(&key, &copies_key).await.unwrap();
+		bucket.move_items(&key, &other_key).await.unwrap();
+		let key = FsMetadata::calculate_hash("other/b.txt");
+		bucket.set_delete(vec![key.clone()]).await.unwrap();
+		bucket.restore_delete(vec![key.clone()]).await.unwrap();
+		bucket.set_delete(vec![key.clone()]).await.unwrap();
+		bucket.remove(vec![key.clone()]).await.unwrap();
+
+		// The history follows the renames and moves and outlives the file.
+		let entries = bucket.get_log(&key, &AuditFilter::default()).await.unwrap();
+		assert_eq!(operations(&entries), vec![
+			(AuditOperation::Remove, "other/b.txt"),
+			(AuditOperation::Delete, "other/b.txt"),
+			(AuditOperation::Restore, "other/b.txt"),
+			(AuditOperation::Delete, "other/b.txt"),
+			(AuditOperation::Move, "other/b.txt"),
+			(AuditOperation::Rename, "docs/b.txt"),
+			(AuditOperation::Unfavorite, "docs/a.txt"),
+			(AuditOperation::Favorite, "docs/a.txt"),
+			(AuditOperation::Add, "docs/a.txt"),
+		]);
+		assert_eq!(entries[0].author_uuid.as_deref(), Some("bob"));
+		assert_eq!(entries[0].comment, None);
+		assert_eq!(entries[8].author_uuid.as_deref(), Some("alice"));
+		assert_eq!(entries[8].comment.as_deref(), Some("first upload"));
+
+		let entries = bucket.get_bucket_log(&AuditFilter::default()).await.unwrap();
+		assert_eq!(entries.len(), 13);
+		assert!(entries.iter().any(|entry| entry.operation == AuditOperation::Copy && entry.path == "copies/b.txt"));
+		assert_eq!(operations(&entries[12..]), vec![(AuditOperation::Add, "docs/")]);
+
+		let entries = bucket.get_bucket_log(&AuditFilter { limit: Some(2), ..Default::default() }).await.unwrap();
+		assert_eq!(operations(&entries), vec![(AuditOperation::Remove, "other/b.txt"), (AuditOperation::Delete, "other/b.txt")]);
+
+		let now = chrono::Utc::now().timestamp();
+		assert!(bucket.get_bucket_log(&AuditFilter { since: Some(now + 60), ..Default::default() }).await.unwrap().is_empty());
+		assert!(bucket.get_bucket_log(&AuditFilter { until: Some(now - 60), ..Default::default() }).await.unwrap().is_empty());
+		let entries = bucket.get_log(&dir_key, &AuditFilter { since: Some(now - 60), until: Some(now + 60), limit: None }).await.unwrap();
+		assert_eq!(operations(&entries), vec![(AuditOperation::Add, "docs/")]);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_audit_log_off() {
+		let (path, mut bucket) = self::prepare_test(false).await.unwrap();
+
+		let key = bucket.add(&KeyValue { key: None, name: "a.txt".to_string(), value: Some(b"1".to_vec()) }).await.unwrap();
+		bucket.set_delete(vec![key.clone()]).await.unwrap();
+		assert!(bucket.get_log(&key, &AuditFilter::default()).await.unwrap().is_empty());
+		assert!(bucket.get_bucket_log(&AuditFilter::default()).await.unwrap().is_empty());
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+}
+
+#[cfg(test)]
+mod tests_access {
+	use std::path::{Path, PathBuf};
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::access::{Grant, Permission};
+	use crate::bucket::bucket::{Bucket, KeyValue};
+	use crate::bucket::listing::{ListObjectsOptions, ListOptions};
+	use crate::bucket::precondition::Preconditions;
+	use crate::error::SolarError;
+	use crate::storage::store::{Storage, Store};
+	use crate::storage::users::{User, Users};
+
+	struct Team {
+		alice: User,
+		bob: User,
+		carol: User,
+	}
+
+	// A bucket of alice with `docs/a.txt` and `private/b.txt`.
+	async fn prepare_test() -> (PathBuf, Team, Bucket) {
+		let path = Path::new("../storages");
+		fs::create_dir(&path).await.is_err();
+		let path = Path::new(&path).join(Uuid::new_v4().to_string());
+		fs::remove_dir_all(&path).await.is_err();
+
+		let store = Store::create(&path, 1000, None).await.unwrap();
+		let team = Team {
+			alice: Users::create(&path, "alice", false).await.unwrap(),
+			bob: Users::create(&path, "bob", false).await.unwrap(),
+			carol: Users::create(&path, "carol", true).await.unwrap(),
+		};
+
+		let mut bucket = store.create_bucket("team", 999, Some(&team.alice)).await.unwrap();
+		let docs_key = bucket.add(&KeyValue { key: None, name: "docs".to_string(), value: None }).await.unwrap();
+		let private_key = bucket.add(&KeyValue { key: None, name: "private".to_string(), value: None }).await.unwrap();
+		bucket.add(&KeyValue { key: Some(docs_key), name: "a.txt".to_string(), value: Some(b"a".to_vec()) }).await.unwrap();
+		bucket.add(&KeyValue { key: Some(private_key), name: "b.txt".to_string(), value: Some(b"b".to_vec()) }).await.unwrap();
+
+		return (path, team, bucket);
+	}
+
+	async fn open_as(bucket: &Bucket, user: &User) -> Bucket {
+		let mut bucket = Bucket::open(bucket.store.clone(), &bucket.uuid).await.unwrap();
+		bucket.set_user(Some(user));
+		return bucket;
+	}
+
+	fn is_denied<T>(result: crate::error::Result<T>) -> bool {
+		return matches!(result, Err(SolarError::AccessDenied(_)));
+	}
+
+	#[tokio::test]
+	async fn test_owners() {
+		let (path, team, bucket) = prepare_test().await;
+		let store = bucket.store.clone();
+
+		assert_eq!(bucket.get_owner().await.unwrap(), Some(team.alice.uuid.clone()));
+		assert_eq!(store.get_bucket("team").await.unwrap().owner_uuid, Some(team.alice.uuid.clone()));
+		let key = Bucket::calculate_key("docs/a.txt");
+		assert_eq!(bucket.properties(&key).await.unwrap().owner, Some(team.alice.uuid.clone()));
+		assert_eq!(bucket.properties(&Bucket::calculate_key("docs/")).await.unwrap().owner, Some(team.alice.uuid.clone()));
+		assert_eq!(bucket.get_permission(None).await.unwrap(), Some(Permission::Admin));
+
+		// Replacing a file and copying it make the acting user the owner.
+		let mut carol = open_as(&bucket, &team.carol).await;
+		carol.add(&KeyValue { key: Some(Bucket::calculate_key("docs/")), name: "a.txt".to_string(), value: Some(b"c".to_vec()) }).await.unwrap();
+		assert_eq!(carol.properties(&key).await.unwrap().owner, Some(team.carol.uuid.clone()));
+		carol.set_user(Some(&team.alice));
+		carol.copy(&key, &Bucket::calculate_key("private/")).await.unwrap();
+		assert_eq!(carol.properties(&Bucket::calculate_key("private/a.txt")).await.unwrap().owner, Some(team.alice.uuid.clone()));
+
+		// Without a user nothing is recorded.
+		let mut bucket = Bucket::open(store.clone(), &bucket.uuid).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "c.txt".to_string(), value: Some(b"c".to_vec()) }).await.unwrap();
+		assert_eq!(bucket.properties(&Bucket::calculate_key("c.txt")).await.unwrap().owner, None);
+
+		fs::remove_dir_all(path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_grants() {
+		let (path, team, bucket) = prepare_test().await;
+		let docs_key = Bucket::calculate_key("docs/");
+		let a_key = Bucket::calculate_key("docs/a.txt");
+		let b_key = Bucket::calculate_key("private/b.txt");
+
+		let mut bob = open_as(&bucket, &team.bob).await;
+		assert_eq!(bob.get_permission(None).await.unwrap(), None);
+		assert!(is_denied(bob.get(&a_key, false).await));
+		assert!(is_denied(bob.read(&a_key, None).await));
+		assert!(is_denied(bob.get_items(None).await));
+		assert!(is_denied(bob.list_objects(&ListObjectsOptions::default()).await));
+		assert!(is_denied(bob.grant(&team.bob.uuid, "", Permission::Admin).await));
+		assert!(is_denied(bob.set_trash_retention(10).await));
+		assert!(is_denied(bob.get_trash_retention().await));
+		assert!(is_denied(bob.get_lifecycle().await));
+		assert!(is_denied(bob.get_checksum_algorithms().await));
+		let if_none = Preconditions { if_none_match: Some("*".to_string()), ..Default::default() };
+		assert!(is_denied(bob.get_if(&a_key, false, &if_none).await));
+
+		let alice = open_as(&bucket, &team.alice).await;
+		alice.grant(&team.bob.uuid, "docs/", Permission::Read).await.unwrap();
+		assert!(matches!(alice.grant(&team.bob.uuid, "docs", Permission::Read).await, Err(SolarError::InvalidInput(_))));
+
+		// The root only shows the way to the granted folder.
+		let names: Vec<String> = bob.get_items(None).await.unwrap().into_iter().map(|item| item.name).collect();
+		assert_eq!(names, vec!["docs".to_string()]);
+		let page = bob.list_items(None, &ListOptions::default()).await.unwrap();
+		assert_eq!(page.items.len(), 1);
+		assert_eq!(bob.get_items(Some(&docs_key)).await.unwrap().len(), 1);
+		assert!(is_denied(bob.get_items(Some(&Bucket::calculate_key("private/"))).await));
+		let listing = bob.list_objects(&ListObjectsOptions::default()).await.unwrap();
+		assert_eq!(listing.objects.iter().map(|object| object.path.as_str()).collect::<Vec<&str>>(), vec!["docs/a.txt"]);
+
+		assert_eq!(bob.get(&a_key, false).await.unwrap().buffer.unwrap(), b"a");
+		assert!(is_denied(bob.get(&b_key, false).await));
+		assert!(is_denied(bob.add(&KeyValue { key: Some(docs_key.clone()), name: "c.txt".to_string(), value: Some(b"c".to_vec()) }).await));
+		assert!(is_denied(bob.set_delete(vec![a_key.clone()]).await));
+		// Access is checked before the preconditions, which tell about the items.
+		let if_other = Preconditions { if_match: Some("0123".to_string()), ..Default::default() };
+		assert!(is_denied(bob.set_delete_if(vec![a_key.clone()], &if_other).await));
+		assert!(is_denied(bob.copy_if(&a_key, &Bucket::calculate_key("private/"), &if_other).await));
+
+		alice.grant(&team.bob.uuid, "docs/", Permission::Write).await.unwrap();
+		bob.add(&KeyValue { key: Some(docs_key.clone()), name: "c.txt".to_string(), value: Some(b"c".to_vec()) }).await.unwrap();
+		assert_eq!(bob.properties(&Bucket::calculate_key("docs/c.txt")).await.unwrap().owner, Some(team.bob.uuid.clone()));
+		bob.set_delete(vec![a_key.clone()]).await.unwrap();
+		assert_eq!(bob.get_deletes().await.unwrap().len(), 1);
+		bob.restore_delete(vec![a_key.clone()]).await.unwrap();
+		assert!(is_denied(bob.move_items(&a_key, &Bucket::calculate_key("private/")).await));
+		assert!(is_denied(bob.grant(&team.carol.uuid, "docs/", Permission::Read).await));
+		assert!(bob.get_grants().await.unwrap().is_empty());
+
+		// A folder admin manages the grants of that folder only.
+		alice.grant(&team.bob.uuid, "docs/", Permission::Admin).await.unwrap();
+		bob.grant(&team.carol.uuid, "docs/", Permission::Read).await.unwrap();
+		assert_eq!(bob.get_grants().await.unwrap().len(), 2);
+		assert!(is_denied(bob.grant(&team.carol.uuid, "private/", Permission::Read).await));
+		assert!(is_denied(bob.set_versioning(true).await));
+		let grants = alice.get_grants().await.unwrap();
+		assert_eq!(grants.len(), 2);
+		assert!(grants.contains(&Grant { user_uuid: team.bob.uuid.clone(), prefix: "docs/".to_string(), permission: Permission::Admin }));
+		assert!(grants.contains(&Grant { user_uuid: team.carol.uuid.clone(), prefix: "docs/".to_string(), permission: Permission::Read }));
+
+		alice.revoke(&team.bob.uuid, "docs/").await.unwrap();
+		assert!(is_denied(bob.get(&a_key, false).await));
+
+		// A store admin needs no grant.
+		let carol = open_as(&bucket, &team.carol).await;
+		assert_eq!(carol.get(&b_key, false).await.unwrap().buffer.unwrap(), b"b");
+		carol.set_trash_retention(10).await.unwrap();
+
+		fs::remove_dir_all(path).await.is_err();
+	}
 }
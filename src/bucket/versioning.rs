@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use rusqlite::Transaction;
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::bucket::access::Permission;
+use crate::bucket::bucket::{Bucket, ObjectReader};
+use crate::bucket::bucket_db::{BucketDB, VersionRow};
+use crate::bucket::fs_metadata::FsMetadata;
+use crate::bucket::range::ByteRange;
+use crate::error::{Result, SolarError};
+use crate::storage::store::Storage;
+
+pub struct VersionItem {
+	pub version_id: String,
+	pub size: u64,
+	pub etag: Option<String>,
+	pub create_at: i64,
+	// When the version stopped being the current content.
+	pub noncurrent_at: i64,
+}
+
+/// With versioning turned on, a replaced file is moved to `<bucket>/versions/<version_id>`
+/// and recorded in the `versions` table under its key, so renames and moves keep the
+/// history. Versions count in the bucket usage until they are deleted or their key is
+/// removed.
+impl Bucket {
+	pub async fn set_versioning(&self, enabled: bool) -> Result<()> {
+		self.authorize("", Permission::Admin).await?;
+		return FsMetadata::new(&self.path).await?.set_versioning(enabled).await;
+	}
+
+	pub async fn is_versioning(&self) -> Result<bool> {
+		return FsMetadata::new(&self.path).await?.is_versioning().await;
+	}
+
+	/// Returns the previous versions of `key`, the newest first. The current content
+	/// is not one of them.
+	pub async fn get_versions(&self, key: &String) -> Result<Vec<VersionItem>> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let path = BucketDB::get_path(key, &transaction).await?;
+		self.authorize(&path, Permission::Read).await?;
+		let versions = BucketDB::get_versions(key, &transaction).await?;
+		transaction.commit()?;
+
+		return Ok(versions.into_iter().map(VersionItem::from).collect());
+	}
+
+	pub async fn read_version(&self, key: &String, version_id: &str, range: Option<&ByteRange>) -> Result<ObjectReader> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let path = BucketDB::get_path(key, &transaction).await?;
+		self.authorize(&path, Permission::Read).await?;
+		BucketDB::get_version(key, version_id, &transaction).await?;
+		transaction.commit()?;
+
+		let version_path = self.version_path(version_id);
+		let mut item = FsMetadata::new(&version_path).await?.info().await?;
+		item.name = Path::new(&path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+		item.hash = key.to_string();
+
+		return ObjectReader::open(item, &version_path, range).await;
+	}
+
+	/// Makes a copy of the version the current content of `key`. The replaced content
+	/// becomes a new version when versioning is on, the restored version is kept.
+	pub async fn restore_version(&mut self, key: &String, version_id: &str) -> Result<()> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let path = BucketDB::get_path(key, &transaction).await?;
+		self.authorize(&path, Permission::Write).await?;
+		let version = BucketDB::get_version(key, version_id, &transaction).await?;
+		transaction.commit()?;
+
+		let (hash, is_replace) = self.prepare_file(&path).await?;
+
+		let temp_dir = Path::new(&self.path).join("tmp");
+		fs::create_dir_all(&temp_dir).await?;
+
+		let temp_path = temp_dir.join(Uuid::new_v4().to_string());
+		let mut file = fs::File::open(self.version_path(version_id)).await?;
+		let (file_size, checksums) = self.write_file(&mut file, &temp_path).await?;
+
+		let result = async {
+			let file = FsMetadata::new(&temp_path).await?;
+			file.set_checksums(&checksums).await?;
+			// A multipart ETag is not the MD5 of the data, so the recorded one is kept.
+			if let Some(etag) = &version.etag {
+				file.set_etag(etag).await?;
+			}
+			return self.commit_file(&temp_path, file_size, &path, &hash, is_replace).await;
+		}.await;
+
+		if let Err(error) = result {
+			fs::remove_file(&temp_path).await.is_err();
+			FsMetadata::new(&self.path).await?.decrease_size(file_size).await?;
+			return Err(error);
+		}
+
+		return Ok(());
+	}
+
+	pub async fn delete_version(&self, key: &String, version_id: &str) -> Result<()> {
+		let mut connection = BucketDB::open(self.path.as_str()).await?;
+		let transaction = connection.transaction()?;
+
+		let path = BucketDB::get_path(key, &transaction).await?;
+		self.authorize(&path, Permission::Write).await?;
+		let version = BucketDB::get_version(key, version_id, &transaction).await?;
+		BucketDB::remove_version(version_id, &transaction).await?;
+		fs::remove_file(self.version_path(version_id)).await?;
+		transaction.commit()?;
+
+		FsMetadata::new(&self.path).await?.decrease_size(version.size).await?;
+		self.store.clone().release_space(version.size).await?;
+
+		return Ok(());
+	}
+
+	// Moves the current file of `key` into `versions/<version_id>` and returns its new path.
+	pub(crate) async fn keep_version(&self, key: &str, file_path: &Path, version_id: &str, transaction: &Transaction<'_>) -> Result<PathBuf> {
+		let metadata = fs::metadata(file_path).await?;
+		let version = VersionRow {
+			version_id: version_id.to_string(),
+			key: key.to_string(),
+			size: metadata.len(),
+			etag: FsMetadata::new(file_path).await?.get_etag().await?,
+			create_at: FsMetadata::timestamp(metadata.modified()?) as i64,
+			noncurrent_at: Utc::now().timestamp(),
+		};
+
+		let version_path = self.version_path(&version.version_id);
+		fs::create_dir_all(Path::new(&self.path).join("versions")).await?;
+
+		BucketDB::add_version(&version, transaction).await?;
+		fs::rename(file_path, &version_path).await?;
+
+		return Ok(version_path);
+	}
+
+	// Drops the versions of `path` and, for a directory, of everything inside it.
+	pub(crate) async fn remove_versions(&self, path: &str, transaction: &Transaction<'_>) -> Result<()> {
+		let versions = BucketDB::get_path_versions(path, transaction).await?;
+		if versions.is_empty() {
+			return Ok(());
+		}
+
+		let bucket = FsMetadata::new(&self.path).await?;
+		let mut store = self.store.clone();
+		for version in versions {
+			BucketDB::remove_version(&version.version_id, transaction).await?;
+			fs::remove_file(self.version_path(&version.version_id)).await?;
+			bucket.decrease_size(version.size).await?;
+			store.release_space(version.size).await?;
+		}
+
+		return Ok(());
+	}
+
+	pub(crate) fn version_path(&self, version_id: &str) -> PathBuf {
+		return Path::new(&self.path).join("versions").join(version_id);
+	}
+}
+
+impl From<VersionRow> for VersionItem {
+	fn from(version: VersionRow) -> VersionItem {
+		return VersionItem {
+			version_id: version.version_id,
+			size: version.size,
+			etag: version.etag,
+			create_at: version.create_at,
+			noncurrent_at: version.noncurrent_at,
+		};
+	}
+}
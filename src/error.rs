@@ -0,0 +1,86 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum SolarError {
+	KeyNotFound(String),
+	NoSuchBucket(String),
+	BucketNotEmpty(String),
+	QuotaExceeded(String),
+	AlreadyExists(String),
+	NotADirectory(String),
+	IsADirectory(String),
+	InvalidInput(String),
+	RangeNotSatisfiable(u64),
+	NoSuchUpload(String),
+	NoSuchVersion(String),
+	InvalidPart,
+	InvalidPartOrder,
+	EntityTooSmall,
+	PreconditionFailed(String),
+	NotModified(String),
+	AccessDenied(String),
+	Db(rusqlite::Error),
+	Io(io::Error),
+	Xattr(io::Error),
+	Json(serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, SolarError>;
+
+impl fmt::Display for SolarError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		return match self {
+			SolarError::KeyNotFound(key) => write!(f, "Key {} was not found", key),
+			SolarError::NoSuchBucket(name) => write!(f, "Bucket {} does not exist", name),
+			SolarError::BucketNotEmpty(uuid) => write!(f, "Bucket {} is not empty", uuid),
+			SolarError::QuotaExceeded(message) => write!(f, "{}", message),
+			SolarError::AlreadyExists(message) => write!(f, "{}", message),
+			SolarError::NotADirectory(message) => write!(f, "{}", message),
+			SolarError::IsADirectory(message) => write!(f, "{}", message),
+			SolarError::InvalidInput(message) => write!(f, "{}", message),
+			SolarError::RangeNotSatisfiable(size) => write!(f, "Range not satisfiable for {} bytes", size),
+			SolarError::NoSuchUpload(upload_id) => write!(f, "Multipart upload {} does not exist", upload_id),
+			SolarError::NoSuchVersion(version_id) => write!(f, "Version {} does not exist", version_id),
+			SolarError::InvalidPart => write!(f, "One or more of the specified parts could not be found"),
+			SolarError::InvalidPartOrder => write!(f, "The list of parts was not in ascending order"),
+			SolarError::EntityTooSmall => write!(f, "Your proposed upload is smaller than the minimum allowed object size"),
+			SolarError::PreconditionFailed(key) => write!(f, "A precondition on key {} did not hold", key),
+			SolarError::NotModified(key) => write!(f, "Key {} was not modified", key),
+			SolarError::AccessDenied(message) => write!(f, "{}", message),
+			SolarError::Db(error) => write!(f, "Database error: {}", error),
+			SolarError::Io(error) => write!(f, "{}", error),
+			SolarError::Xattr(error) => write!(f, "Extended attribute error: {}", error),
+			SolarError::Json(error) => write!(f, "JSON error: {}", error),
+		};
+	}
+}
+
+impl std::error::Error for SolarError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		return match self {
+			SolarError::Db(error) => Some(error),
+			SolarError::Io(error) | SolarError::Xattr(error) => Some(error),
+			SolarError::Json(error) => Some(error),
+			_ => None,
+		};
+	}
+}
+
+impl From<rusqlite::Error> for SolarError {
+	fn from(error: rusqlite::Error) -> SolarError {
+		return SolarError::Db(error);
+	}
+}
+
+impl From<io::Error> for SolarError {
+	fn from(error: io::Error) -> SolarError {
+		return SolarError::Io(error);
+	}
+}
+
+impl From<serde_json::Error> for SolarError {
+	fn from(error: serde_json::Error) -> SolarError {
+		return SolarError::Json(error);
+	}
+}
@@ -1,5 +1,8 @@
 #![feature(io_error_more)]
 #![allow(unused)]
+#![allow(clippy::needless_return, clippy::module_inception, clippy::ptr_arg, clippy::bool_assert_comparison)]
 
+pub mod error;
 pub mod storage;
 pub mod bucket;
+pub mod server;
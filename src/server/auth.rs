@@ -0,0 +1,428 @@
+use std::io;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use hmac::{Hmac, Mac};
+use hyper::body::{Bytes, HttpBody};
+use hyper::http::request::Parts;
+use hyper::{Body, HeaderMap};
+use percent_encoding::percent_decode_str;
+use sha2::{Digest, Sha256};
+
+use crate::server::error::S3Error;
+use crate::storage::credentials::Credential;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub type PayloadStream = BoxStream<'static, io::Result<Bytes>>;
+
+pub const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+pub const STREAMING_PAYLOAD: &str = "STREAMING-AWS4-HMAC-SHA256-PAYLOAD";
+pub const STREAMING_UNSIGNED_PAYLOAD: &str = "STREAMING-UNSIGNED-PAYLOAD-TRAILER";
+
+const MAX_SKEW_SECONDS: i64 = 15 * 60;
+const MAX_EXPIRES_SECONDS: i64 = 7 * 24 * 60 * 60;
+const AMZ_DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+const MAX_CHUNK_HEADER: usize = 4096;
+const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// The verified identity of a request and what is needed to check its payload.
+#[derive(Clone)]
+pub struct Authorization {
+	pub access_key: String,
+	pub content_sha256: String,
+	signing_key: Vec<u8>,
+	scope: String,
+	amz_date: String,
+	signature: String,
+}
+
+struct SignatureFields {
+	access_key: String,
+	scope_date: String,
+	region: String,
+	service: String,
+	terminator: String,
+	signed_headers: Vec<String>,
+	signature: String,
+	amz_date: String,
+	expires: Option<i64>,
+}
+
+impl Authorization {
+	/// Turns the request body into a stream of object data, checking it against
+	/// `x-amz-content-sha256` and stripping the `aws-chunked` framing of
+	/// streaming uploads on the way.
+	pub fn payload(&self, body: Body, headers: &HeaderMap) -> PayloadStream {
+		let decoded_length = header_value(headers, "x-amz-decoded-content-length").and_then(|length| length.parse::<u64>().ok());
+
+		return match self.content_sha256.as_str() {
+			UNSIGNED_PAYLOAD => TryStreamExt::map_err(body, io::Error::other).boxed(),
+			STREAMING_PAYLOAD => ChunkDecoder::new(body, Some(self.clone()), decoded_length).into_stream(),
+			STREAMING_UNSIGNED_PAYLOAD => ChunkDecoder::new(body, None, decoded_length).into_stream(),
+			content_sha256 if content_sha256.starts_with("STREAMING-") => {
+				stream::once(async { Err(payload_error(S3Error::not_implemented())) }).boxed()
+			}
+			content_sha256 => {
+				let state = (body, Sha256::new(), content_sha256.to_lowercase());
+				stream::try_unfold(state, |(mut body, mut hasher, expected)| async move {
+					match body.data().await {
+						Some(chunk) => {
+							let chunk = chunk.map_err(io::Error::other)?;
+							hasher.update(&chunk);
+							Ok(Some((chunk, (body, hasher, expected))))
+						}
+						None if hex::encode(hasher.finalize_reset()) != expected => Err(payload_error(S3Error::content_sha256_mismatch())),
+						None => Ok(None),
+					}
+				}).boxed()
+			}
+		};
+	}
+}
+
+struct ChunkDecoder {
+	body: Body,
+	buffer: Vec<u8>,
+	signing: Option<Authorization>,
+	previous_signature: String,
+	decoded_length: Option<u64>,
+	size: u64,
+}
+
+impl ChunkDecoder {
+	fn new(body: Body, signing: Option<Authorization>, decoded_length: Option<u64>) -> ChunkDecoder {
+		let previous_signature = signing.as_ref().map(|signing| signing.signature.clone()).unwrap_or_default();
+		return ChunkDecoder {
+			body,
+			buffer: Vec::new(),
+			signing,
+			previous_signature,
+			decoded_length,
+			size: 0,
+		};
+	}
+
+	fn into_stream(self) -> PayloadStream {
+		return stream::try_unfold(self, |mut decoder| async move {
+			return Ok(decoder.next_chunk().await?.map(|chunk| (chunk, decoder)));
+		}).boxed();
+	}
+
+	async fn fill(&mut self) -> io::Result<()> {
+		return match self.body.data().await {
+			Some(chunk) => {
+				self.buffer.extend_from_slice(&chunk.map_err(io::Error::other)?);
+				Ok(())
+			}
+			None => Err(payload_error(S3Error::incomplete_body())),
+		};
+	}
+
+	async fn next_chunk(&mut self) -> io::Result<Option<Bytes>> {
+		let line_end = loop {
+			if let Some(position) = self.buffer.windows(2).position(|window| window == b"\r\n") {
+				break position;
+			}
+			if self.buffer.len() > MAX_CHUNK_HEADER {
+				return Err(payload_error(S3Error::incomplete_body()));
+			}
+			self.fill().await?;
+		};
+
+		let header: Vec<u8> = self.buffer.drain(..line_end + 2).collect();
+		let header = std::str::from_utf8(&header[..line_end]).map_err(|_| payload_error(S3Error::incomplete_body()))?;
+		let (size, extension) = header.split_once(';').unwrap_or((header, ""));
+		let size = usize::from_str_radix(size.trim(), 16).map_err(|_| payload_error(S3Error::incomplete_body()))?;
+		if size > MAX_CHUNK_SIZE {
+			return Err(payload_error(S3Error::incomplete_body()));
+		}
+
+		let needed = if size == 0 { 0 } else { size + 2 };
+		while self.buffer.len() < needed {
+			self.fill().await?;
+		}
+		let chunk: Vec<u8> = self.buffer.drain(..size).collect();
+
+		if let Some(signing) = &self.signing {
+			let signature = extension.trim()
+				.strip_prefix("chunk-signature=")
+				.ok_or_else(|| payload_error(S3Error::signature_does_not_match()))?;
+			let string_to_sign = format!(
+				"{ALGORITHM}-PAYLOAD\n{}\n{}\n{}\n{}\n{}",
+				signing.amz_date, signing.scope, self.previous_signature, hex::encode(Sha256::digest(b"")), hex::encode(Sha256::digest(&chunk)),
+			);
+			verify_signature(&signing.signing_key, &string_to_sign, signature).map_err(payload_error)?;
+			self.previous_signature = signature.to_string();
+		}
+
+		if size == 0 {
+			if self.decoded_length.is_some_and(|length| length != self.size) {
+				return Err(payload_error(S3Error::incomplete_body()));
+			}
+			return Ok(None);
+		}
+
+		if !self.buffer.starts_with(b"\r\n") {
+			return Err(payload_error(S3Error::incomplete_body()));
+		}
+		self.buffer.drain(..2);
+		self.size += size as u64;
+
+		return Ok(Some(Bytes::from(chunk)));
+	}
+}
+
+fn payload_error(error: S3Error) -> io::Error {
+	return io::Error::new(io::ErrorKind::InvalidData, error);
+}
+
+/// Verifies the AWS Signature Version 4 of a request, either from the
+/// `Authorization` header or from presigned query parameters.
+pub fn authorize(parts: &Parts, credentials: &[Credential], now: DateTime<Utc>) -> Result<Authorization, S3Error> {
+	let query = parse_query_pairs(parts.uri.query());
+	let is_presigned = query.iter().any(|(name, _)| name == "X-Amz-Signature");
+
+	let fields = match header_value(&parts.headers, "authorization") {
+		Some(header) => parse_authorization_header(header, &parts.headers)?,
+		None if is_presigned => parse_presigned_query(&query)?,
+		None => return Err(S3Error::access_denied()),
+	};
+
+	let credential = credentials.iter()
+		.find(|credential| credential.access_key == fields.access_key)
+		.ok_or_else(S3Error::invalid_access_key_id)?;
+
+	if fields.service != "s3" || fields.terminator != "aws4_request" || !fields.signed_headers.iter().any(|name| name == "host") {
+		return Err(S3Error::authorization_header_malformed());
+	}
+
+	let request_time = NaiveDateTime::parse_from_str(&fields.amz_date, AMZ_DATE_FORMAT)
+		.map_err(|_| S3Error::access_denied())?
+		.and_utc();
+	if fields.amz_date[..8] != fields.scope_date {
+		return Err(S3Error::authorization_header_malformed());
+	}
+
+	match fields.expires {
+		None if (now - request_time).num_seconds().abs() > MAX_SKEW_SECONDS => return Err(S3Error::request_time_too_skewed()),
+		Some(expires) if !(1..=MAX_EXPIRES_SECONDS).contains(&expires) => return Err(S3Error::authorization_query_parameters_error()),
+		Some(_) if (request_time - now).num_seconds() > MAX_SKEW_SECONDS => return Err(S3Error::request_not_valid_yet()),
+		Some(expires) if (now - request_time).num_seconds() > expires => return Err(S3Error::request_expired()),
+		_ => {}
+	}
+
+	let content_sha256 = match fields.expires {
+		Some(_) => header_value(&parts.headers, "x-amz-content-sha256").unwrap_or(UNSIGNED_PAYLOAD).to_string(),
+		None => header_value(&parts.headers, "x-amz-content-sha256")
+			.ok_or_else(|| S3Error::invalid_request("Missing required header for this request: x-amz-content-sha256"))?
+			.to_string(),
+	};
+
+	let canonical_query: Vec<(String, String)> = query.into_iter()
+		.filter(|(name, _)| name != "X-Amz-Signature")
+		.collect();
+	let path = percent_decode_str(parts.uri.path()).decode_utf8().map_err(|_| S3Error::signature_does_not_match())?;
+	let canonical_request = canonical_request(
+		parts.method.as_str(), &path, &canonical_query, &parts.headers, &fields.signed_headers, &content_sha256,
+	)?;
+
+	let scope = format!("{}/{}/{}/{}", fields.scope_date, fields.region, fields.service, fields.terminator);
+	let string_to_sign = string_to_sign(&fields.amz_date, &scope, &canonical_request);
+	let signing_key = signing_key(&credential.secret_key, &fields.scope_date, &fields.region, &fields.service);
+	verify_signature(&signing_key, &string_to_sign, &fields.signature)?;
+
+	return Ok(Authorization {
+		access_key: credential.access_key.clone(),
+		content_sha256,
+		signing_key,
+		scope,
+		amz_date: fields.amz_date,
+		signature: fields.signature,
+	});
+}
+
+pub fn canonical_request(
+	method: &str,
+	path: &str,
+	query: &[(String, String)],
+	headers: &HeaderMap,
+	signed_headers: &[String],
+	payload_hash: &str,
+) -> Result<String, S3Error> {
+	let canonical_uri = match path.is_empty() {
+		true => "/".to_string(),
+		false => uri_encode(path, false),
+	};
+
+	let mut canonical_query: Vec<(String, String)> = query.iter()
+		.map(|(name, value)| (uri_encode(name, true), uri_encode(value, true)))
+		.collect();
+	canonical_query.sort();
+	let canonical_query = canonical_query.iter()
+		.map(|(name, value)| format!("{name}={value}"))
+		.collect::<Vec<String>>()
+		.join("&");
+
+	let mut canonical_headers = String::new();
+	for name in signed_headers {
+		let values = headers.get_all(name.as_str()).iter()
+			.map(|value| value.to_str().map(|value| value.split_whitespace().collect::<Vec<&str>>().join(" ")))
+			.collect::<Result<Vec<String>, _>>()
+			.map_err(|_| S3Error::signature_does_not_match())?;
+		if values.is_empty() {
+			return Err(S3Error::access_denied());
+		}
+		canonical_headers.push_str(&format!("{}:{}\n", name, values.join(",")));
+	}
+
+	return Ok(format!(
+		"{method}\n{canonical_uri}\n{canonical_query}\n{canonical_headers}\n{}\n{payload_hash}",
+		signed_headers.join(";"),
+	));
+}
+
+pub fn string_to_sign(amz_date: &str, scope: &str, canonical_request: &str) -> String {
+	return format!("{ALGORITHM}\n{amz_date}\n{scope}\n{}", hex::encode(Sha256::digest(canonical_request.as_bytes())));
+}
+
+pub fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+	let key = hmac(format!("AWS4{secret_key}").as_bytes(), date.as_bytes());
+	let key = hmac(&key, region.as_bytes());
+	let key = hmac(&key, service.as_bytes());
+	return hmac(&key, b"aws4_request");
+}
+
+pub fn sign(signing_key: &[u8], string_to_sign: &str) -> String {
+	return hex::encode(hmac(signing_key, string_to_sign.as_bytes()));
+}
+
+/// Percent-encodes everything except the unreserved characters of RFC 3986,
+/// the way SigV4 canonical requests expect.
+pub fn uri_encode(value: &str, encode_slash: bool) -> String {
+	let mut encoded = String::with_capacity(value.len());
+	for byte in value.bytes() {
+		match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+			b'/' if !encode_slash => encoded.push('/'),
+			_ => encoded.push_str(&format!("%{byte:02X}")),
+		}
+	}
+
+	return encoded;
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+	let mut mac = HmacSha256::new_from_slice(key).unwrap();
+	mac.update(data);
+	return mac.finalize().into_bytes().to_vec();
+}
+
+fn verify_signature(signing_key: &[u8], string_to_sign: &str, signature: &str) -> Result<(), S3Error> {
+	let signature = hex::decode(signature).map_err(|_| S3Error::signature_does_not_match())?;
+	let mut mac = HmacSha256::new_from_slice(signing_key).unwrap();
+	mac.update(string_to_sign.as_bytes());
+	return mac.verify_slice(&signature).map_err(|_| S3Error::signature_does_not_match());
+}
+
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+	return headers.get(name).and_then(|value| value.to_str().ok());
+}
+
+pub fn parse_query_pairs(query: Option<&str>) -> Vec<(String, String)> {
+	return query.unwrap_or("").split('&')
+		.filter(|pair| !pair.is_empty())
+		.map(|pair| {
+			let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+			(decode_component(name), decode_component(value))
+		})
+		.collect();
+}
+
+fn decode_component(value: &str) -> String {
+	return percent_decode_str(&value.replace('+', " ")).decode_utf8_lossy().to_string();
+}
+
+fn parse_credential(credential: &str) -> Result<(String, String, String, String, String), S3Error> {
+	let parts: Vec<&str> = credential.split('/').collect();
+	if parts.len() != 5 {
+		return Err(S3Error::authorization_header_malformed());
+	}
+
+	return Ok((parts[0].to_string(), parts[1].to_string(), parts[2].to_string(), parts[3].to_string(), parts[4].to_string()));
+}
+
+fn parse_signed_headers(signed_headers: &str) -> Vec<String> {
+	return signed_headers.split(';').map(|name| name.trim().to_lowercase()).collect();
+}
+
+fn parse_authorization_header(header: &str, headers: &HeaderMap) -> Result<SignatureFields, S3Error> {
+	let fields = header.strip_prefix(ALGORITHM).ok_or_else(S3Error::authorization_header_malformed)?;
+
+	let mut credential = None;
+	let mut signed_headers = None;
+	let mut signature = None;
+	for field in fields.split(',') {
+		match field.trim().split_once('=') {
+			Some(("Credential", value)) => credential = Some(value),
+			Some(("SignedHeaders", value)) => signed_headers = Some(value),
+			Some(("Signature", value)) => signature = Some(value),
+			_ => return Err(S3Error::authorization_header_malformed()),
+		}
+	}
+
+	let (access_key, scope_date, region, service, terminator) =
+		parse_credential(credential.ok_or_else(S3Error::authorization_header_malformed)?)?;
+
+	let amz_date = match header_value(headers, "x-amz-date") {
+		Some(amz_date) => amz_date.to_string(),
+		None => {
+			let date = header_value(headers, "date").ok_or_else(S3Error::access_denied)?;
+			DateTime::parse_from_rfc2822(date).map_err(|_| S3Error::access_denied())?
+				.with_timezone(&Utc)
+				.format(AMZ_DATE_FORMAT)
+				.to_string()
+		}
+	};
+
+	return Ok(SignatureFields {
+		access_key,
+		scope_date,
+		region,
+		service,
+		terminator,
+		signed_headers: parse_signed_headers(signed_headers.ok_or_else(S3Error::authorization_header_malformed)?),
+		signature: signature.ok_or_else(S3Error::authorization_header_malformed)?.to_string(),
+		amz_date,
+		expires: None,
+	});
+}
+
+fn parse_presigned_query(query: &[(String, String)]) -> Result<SignatureFields, S3Error> {
+	let param = |name: &str| {
+		query.iter()
+			.find(|(key, _)| key == name)
+			.map(|(_, value)| value.as_str())
+			.ok_or_else(S3Error::authorization_query_parameters_error)
+	};
+
+	if param("X-Amz-Algorithm")? != ALGORITHM {
+		return Err(S3Error::authorization_query_parameters_error());
+	}
+
+	let (access_key, scope_date, region, service, terminator) = parse_credential(param("X-Amz-Credential")?)?;
+	let expires = param("X-Amz-Expires")?.parse::<i64>().map_err(|_| S3Error::authorization_query_parameters_error())?;
+
+	return Ok(SignatureFields {
+		access_key,
+		scope_date,
+		region,
+		service,
+		terminator,
+		signed_headers: parse_signed_headers(param("X-Amz-SignedHeaders")?),
+		signature: param("X-Amz-Signature")?.to_string(),
+		amz_date: param("X-Amz-Date")?.to_string(),
+		expires: Some(expires),
+	});
+}
@@ -0,0 +1,213 @@
+use std::fmt;
+use std::io;
+
+use hyper::{Body, Response, StatusCode};
+
+use crate::error::SolarError;
+use crate::server::xml;
+
+#[derive(Debug, Clone)]
+pub struct S3Error {
+	pub status: StatusCode,
+	pub code: &'static str,
+	pub message: String,
+}
+
+impl S3Error {
+	pub fn new(status: StatusCode, code: &'static str, message: &str) -> S3Error {
+		return S3Error {
+			status,
+			code,
+			message: message.to_string(),
+		};
+	}
+
+	pub fn no_such_bucket() -> S3Error {
+		return S3Error::new(StatusCode::NOT_FOUND, "NoSuchBucket", "The specified bucket does not exist");
+	}
+
+	pub fn no_such_version() -> S3Error {
+		return S3Error::new(StatusCode::NOT_FOUND, "NoSuchVersion", "The specified version does not exist.");
+	}
+
+	pub fn no_such_key() -> S3Error {
+		return S3Error::new(StatusCode::NOT_FOUND, "NoSuchKey", "The specified key does not exist.");
+	}
+
+	pub fn bucket_already_exists() -> S3Error {
+		return S3Error::new(StatusCode::CONFLICT, "BucketAlreadyOwnedByYou", "Your previous request to create the named bucket succeeded and you already own it.");
+	}
+
+	pub fn bucket_not_empty() -> S3Error {
+		return S3Error::new(StatusCode::CONFLICT, "BucketNotEmpty", "The bucket you tried to delete is not empty");
+	}
+
+	pub fn invalid_bucket_name() -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "InvalidBucketName", "The specified bucket is not valid.");
+	}
+
+	pub fn invalid_argument(message: &str) -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "InvalidArgument", message);
+	}
+
+	pub fn invalid_request(message: &str) -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "InvalidRequest", message);
+	}
+
+	pub fn entity_too_large() -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "EntityTooLarge", "Your proposed upload exceeds the maximum allowed object size.");
+	}
+
+	pub fn invalid_range() -> S3Error {
+		return S3Error::new(StatusCode::RANGE_NOT_SATISFIABLE, "InvalidRange", "The requested range is not satisfiable");
+	}
+
+	pub fn no_such_upload() -> S3Error {
+		return S3Error::new(StatusCode::NOT_FOUND, "NoSuchUpload", "The specified multipart upload does not exist. The upload ID might be invalid, or the multipart upload might have been aborted or completed.");
+	}
+
+	pub fn invalid_part() -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "InvalidPart", "One or more of the specified parts could not be found. The part might not have been uploaded, or the specified entity tag might not have matched the part's entity tag.");
+	}
+
+	pub fn invalid_part_order() -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "InvalidPartOrder", "The list of parts was not in ascending order. The parts list must be specified in order by part number.");
+	}
+
+	pub fn entity_too_small() -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "EntityTooSmall", "Your proposed upload is smaller than the minimum allowed object size.");
+	}
+
+	pub fn precondition_failed() -> S3Error {
+		return S3Error::new(StatusCode::PRECONDITION_FAILED, "PreconditionFailed", "At least one of the pre-conditions you specified did not hold");
+	}
+
+	pub fn not_modified() -> S3Error {
+		return S3Error::new(StatusCode::NOT_MODIFIED, "NotModified", "Not Modified");
+	}
+
+	pub fn malformed_xml() -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "The XML you provided was not well-formed or did not validate against our published schema.");
+	}
+
+	pub fn access_denied() -> S3Error {
+		return S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", "Access Denied");
+	}
+
+	pub fn invalid_access_key_id() -> S3Error {
+		return S3Error::new(StatusCode::FORBIDDEN, "InvalidAccessKeyId", "The AWS access key ID you provided does not exist in our records.");
+	}
+
+	pub fn signature_does_not_match() -> S3Error {
+		return S3Error::new(StatusCode::FORBIDDEN, "SignatureDoesNotMatch", "The request signature we calculated does not match the signature you provided. Check your key and signing method.");
+	}
+
+	pub fn request_time_too_skewed() -> S3Error {
+		return S3Error::new(StatusCode::FORBIDDEN, "RequestTimeTooSkewed", "The difference between the request time and the server's time is too large.");
+	}
+
+	pub fn request_expired() -> S3Error {
+		return S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", "Request has expired");
+	}
+
+	pub fn request_not_valid_yet() -> S3Error {
+		return S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", "Request is not valid yet");
+	}
+
+	pub fn authorization_header_malformed() -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "AuthorizationHeaderMalformed", "The authorization header that you provided is not valid.");
+	}
+
+	pub fn authorization_query_parameters_error() -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "AuthorizationQueryParametersError", "The query parameters that you provided are not valid.");
+	}
+
+	pub fn content_sha256_mismatch() -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "XAmzContentSHA256Mismatch", "The provided 'x-amz-content-sha256' header does not match what was computed.");
+	}
+
+	pub fn incomplete_body() -> S3Error {
+		return S3Error::new(StatusCode::BAD_REQUEST, "IncompleteBody", "You did not provide the number of bytes specified by the Content-Length HTTP header.");
+	}
+
+	pub fn method_not_allowed() -> S3Error {
+		return S3Error::new(StatusCode::METHOD_NOT_ALLOWED, "MethodNotAllowed", "The specified method is not allowed against this resource.");
+	}
+
+	pub fn not_implemented() -> S3Error {
+		return S3Error::new(StatusCode::NOT_IMPLEMENTED, "NotImplemented", "A header or query you provided implies functionality that is not implemented.");
+	}
+
+	pub fn internal(message: &str) -> S3Error {
+		return S3Error::new(StatusCode::INTERNAL_SERVER_ERROR, "InternalError", message);
+	}
+
+	pub fn into_response(self, resource: &str, request_id: &str) -> Response<Body> {
+		// A 304 response has no body.
+		if self.status == StatusCode::NOT_MODIFIED {
+			return Response::builder()
+				.status(self.status)
+				.header("x-amz-request-id", request_id)
+				.body(Body::empty())
+				.unwrap();
+		}
+
+		let body = xml::error(self.code, &self.message, resource, request_id);
+
+		return Response::builder()
+			.status(self.status)
+			.header("Content-Type", "application/xml")
+			.header("x-amz-request-id", request_id)
+			.body(Body::from(body))
+			.unwrap();
+	}
+}
+
+impl fmt::Display for S3Error {
+	fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+		return write!(formatter, "{}: {}", self.code, self.message);
+	}
+}
+
+impl std::error::Error for S3Error {}
+
+impl From<io::Error> for S3Error {
+	fn from(error: io::Error) -> S3Error {
+		if let Some(error) = error.get_ref().and_then(|error| error.downcast_ref::<S3Error>()) {
+			return error.clone();
+		}
+
+		return match error.kind() {
+			io::ErrorKind::StorageFull => S3Error::entity_too_large(),
+			io::ErrorKind::NotFound => S3Error::no_such_key(),
+			io::ErrorKind::IsADirectory => S3Error::invalid_request("A directory with the same name already exists."),
+			_ => S3Error::internal(&error.to_string()),
+		};
+	}
+}
+
+impl From<SolarError> for S3Error {
+	fn from(error: SolarError) -> S3Error {
+		return match error {
+			SolarError::KeyNotFound(_) => S3Error::no_such_key(),
+			SolarError::NoSuchBucket(_) => S3Error::no_such_bucket(),
+			SolarError::BucketNotEmpty(_) => S3Error::bucket_not_empty(),
+			SolarError::QuotaExceeded(_) => S3Error::entity_too_large(),
+			SolarError::AlreadyExists(message) => S3Error::new(StatusCode::CONFLICT, "OperationAborted", &message),
+			SolarError::NotADirectory(_) => S3Error::invalid_request("An object with the same name as the directory already exists."),
+			SolarError::IsADirectory(_) => S3Error::invalid_request("A directory with the same name already exists."),
+			SolarError::InvalidInput(message) => S3Error::invalid_argument(&message),
+			SolarError::RangeNotSatisfiable(_) => S3Error::invalid_range(),
+			SolarError::NoSuchUpload(_) => S3Error::no_such_upload(),
+			SolarError::NoSuchVersion(_) => S3Error::no_such_version(),
+			SolarError::InvalidPart => S3Error::invalid_part(),
+			SolarError::InvalidPartOrder => S3Error::invalid_part_order(),
+			SolarError::EntityTooSmall => S3Error::entity_too_small(),
+			SolarError::PreconditionFailed(_) => S3Error::precondition_failed(),
+			SolarError::NotModified(_) => S3Error::not_modified(),
+			SolarError::AccessDenied(_) => S3Error::access_denied(),
+			SolarError::Io(error) => S3Error::from(error),
+			SolarError::Db(_) | SolarError::Xattr(_) | SolarError::Json(_) => S3Error::internal(&error.to_string()),
+		};
+	}
+}
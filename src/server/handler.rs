@@ -0,0 +1,660 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use hyper::header::HeaderValue;
+use hyper::http::request::Parts;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use percent_encoding::percent_decode_str;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use tokio::sync::Mutex;
+use tokio_util::io::{ReaderStream, StreamReader};
+use uuid::Uuid;
+
+use crate::bucket::access::Permission;
+use crate::bucket::bucket::{Bucket, KeyValue};
+use crate::bucket::fs_metadata::PropertiesItem;
+use crate::bucket::listing::{ListObjectsOptions, MAX_KEYS};
+use crate::bucket::multipart::{MultipartUpload, PartItem, MAX_PART_NUMBER, MIN_PART_NUMBER, STALE_UPLOAD_AGE};
+use crate::bucket::precondition::Preconditions;
+use crate::bucket::range::ByteRange;
+use crate::server::auth::{self, Authorization};
+use crate::server::error::S3Error;
+use crate::server::xml::{self, ListObjectsResult, ListPartsResult, ObjectEntry};
+use crate::storage::credentials::Credentials;
+use crate::storage::store::{Storage, Store};
+use crate::storage::users::{User, Users};
+
+pub type S3Result = Result<Response<Body>, S3Error>;
+
+const MAX_PARTS: usize = 1000;
+const MAX_COMPLETE_BODY_SIZE: u64 = 1024 * 1024;
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+pub struct S3Handler {
+	store_path: PathBuf,
+	lock: Mutex<()>,
+}
+
+impl S3Handler {
+	pub fn new(store_path: impl AsRef<Path>) -> S3Handler {
+		return S3Handler {
+			store_path: store_path.as_ref().to_path_buf(),
+			lock: Mutex::new(()),
+		};
+	}
+
+	pub fn store_path(&self) -> &Path {
+		return &self.store_path;
+	}
+
+	pub async fn handle(&self, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+		let request_id = Uuid::new_v4().simple().to_string().to_uppercase();
+		let resource = request.uri().path().to_string();
+
+		let mut response = match self.route(request).await {
+			Ok(response) => response,
+			Err(error) => error.into_response(&resource, &request_id),
+		};
+
+		let headers = response.headers_mut();
+		headers.insert("x-amz-request-id", HeaderValue::from_str(&request_id).unwrap());
+		headers.insert("Server", HeaderValue::from_static("solar-s3"));
+
+		return Ok(response);
+	}
+
+	// Applies the lifecycle rules, purges the expired trash and aborts the stale uploads
+	// of every bucket. Holds the lock, so it never sees a half finished change. Parts are
+	// written without it, so an upload with a part on its way is left for a later run.
+	pub async fn run_maintenance(&self) -> crate::error::Result<()> {
+		let _guard = self.lock.lock().await;
+		let store = Store::open(&self.store_path).await?;
+		store.apply_lifecycle(Utc::now().timestamp()).await?;
+		store.purge_trash(Utc::now().timestamp()).await?;
+		store.clear_multiparts(Utc::now().timestamp() - STALE_UPLOAD_AGE).await?;
+		return Ok(());
+	}
+
+	async fn route(&self, request: Request<Body>) -> S3Result {
+		let (parts, body) = request.into_parts();
+		let credentials = Credentials::load(&self.store_path).await?;
+		let authorization = auth::authorize(&parts, &credentials, Utc::now())?;
+
+		let mut query = parse_query(parts.uri.query());
+		query.retain(|name, _| !name.starts_with("X-Amz-") && name != "x-id");
+
+		let path = percent_decode_str(parts.uri.path()).decode_utf8()
+			.map_err(|_| S3Error::invalid_argument("The request path is not valid UTF-8."))?
+			.to_string();
+		let path = path.trim_start_matches('/');
+		let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+
+		// Changes to the store run one at a time. A request with a body to store takes the
+		// lock itself, so that other requests go on while the body is read.
+		let _guard = match streams_body(&parts.method, key, &query) {
+			true => None,
+			false => Some(self.lock.lock().await),
+		};
+
+		if bucket.is_empty() {
+			return match parts.method {
+				Method::GET => self.list_buckets(&authorization).await,
+				_ => Err(S3Error::method_not_allowed()),
+			};
+		}
+
+		if key.is_empty() {
+			return match parts.method {
+				Method::GET if query.contains_key("location") => Ok(xml_response(StatusCode::OK, xml::location())),
+				Method::GET if query.get("list-type").map(String::as_str) == Some("2") => self.list_objects_v2(bucket, &query, &authorization).await,
+				Method::PUT if query.is_empty() => self.create_bucket(bucket, &authorization).await,
+				Method::HEAD => self.head_bucket(bucket, &authorization).await,
+				Method::DELETE if query.is_empty() => self.delete_bucket(bucket, &authorization).await,
+				Method::GET | Method::PUT | Method::DELETE => Err(S3Error::not_implemented()),
+				_ => Err(S3Error::method_not_allowed()),
+			};
+		}
+
+		if parts.headers.contains_key("x-amz-copy-source") {
+			return Err(S3Error::not_implemented());
+		}
+
+		validate_key(key)?;
+		if let Some(upload_id) = query.get("uploadId") {
+			return match parts.method {
+				Method::PUT if query.contains_key("partNumber") => self.upload_part(bucket, key, upload_id, &query, &parts, &authorization, body).await,
+				Method::GET => self.list_parts(bucket, key, upload_id, &query, &authorization).await,
+				Method::POST => self.complete_multipart_upload(bucket, key, upload_id, &parts, &authorization, body).await,
+				Method::DELETE => self.abort_multipart_upload(bucket, key, upload_id, &authorization).await,
+				_ => Err(S3Error::method_not_allowed()),
+			};
+		}
+
+		if query.contains_key("uploads") {
+			return match parts.method {
+				Method::POST => self.create_multipart_upload(bucket, key, &authorization).await,
+				_ => Err(S3Error::method_not_allowed()),
+			};
+		}
+
+		if !query.is_empty() {
+			return Err(S3Error::not_implemented());
+		}
+
+		return match parts.method {
+			Method::PUT => self.put_object(bucket, key, &parts, &authorization, body).await,
+			Method::GET => self.get_object(bucket, key, &parts, &authorization, false).await,
+			Method::HEAD => self.get_object(bucket, key, &parts, &authorization, true).await,
+			Method::DELETE => self.delete_object(bucket, key, &parts, &authorization).await,
+			_ => Err(S3Error::method_not_allowed()),
+		};
+	}
+
+	// Opens the bucket as the user of the access key. A key without a user acts as the
+	// store and is only recorded as the author of the changes.
+	async fn open_bucket(&self, name: &str, authorization: &Authorization) -> Result<Bucket, S3Error> {
+		let store = Store::open(&self.store_path).await?;
+		let mut bucket = store.open_bucket(name).await?;
+		// The audit log names the user, a key of the store itself is named by the key.
+		match self.acting_user(authorization).await? {
+			Some(user) => bucket.set_user(Some(&user)),
+			None => bucket.set_author(Some(&authorization.access_key), None),
+		}
+
+		return Ok(bucket);
+	}
+
+	// The user of the access key. The key of a removed user is denied.
+	async fn acting_user(&self, authorization: &Authorization) -> Result<Option<User>, S3Error> {
+		let user_uuid = match Credentials::find(&self.store_path, &authorization.access_key).await?.and_then(|credential| credential.user_uuid) {
+			None => return Ok(None),
+			Some(user_uuid) => user_uuid,
+		};
+
+		return match Users::find(&self.store_path, &user_uuid).await? {
+			None => Err(S3Error::access_denied()),
+			Some(user) => Ok(Some(user)),
+		};
+	}
+
+	// The buckets the user owns, administers or holds a grant in.
+	async fn list_buckets(&self, authorization: &Authorization) -> S3Result {
+		let store = Store::open(&self.store_path).await?;
+		let mut buckets = Vec::new();
+		for info in store.get_buckets().await? {
+			let bucket = self.open_bucket(&info.name, authorization).await?;
+			if bucket.access().await?.can_see("") {
+				buckets.push(info);
+			}
+		}
+
+		return Ok(xml_response(StatusCode::OK, xml::list_buckets(&store.uuid, &buckets)));
+	}
+
+	async fn create_bucket(&self, name: &str, authorization: &Authorization) -> S3Result {
+		validate_bucket_name(name)?;
+
+		let store = Store::open(&self.store_path).await?;
+		if store.has_bucket(name).await? {
+			return Err(S3Error::bucket_already_exists());
+		}
+
+		let bucket_space = store.available_space.saturating_sub(store.usage_space);
+		store.create_bucket(name, bucket_space, self.acting_user(authorization).await?.as_ref()).await?;
+
+		return Ok(Response::builder()
+			.status(StatusCode::OK)
+			.header("Location", format!("/{name}"))
+			.body(Body::empty())
+			.unwrap());
+	}
+
+	async fn head_bucket(&self, name: &str, authorization: &Authorization) -> S3Result {
+		let store = Store::open(&self.store_path).await?;
+		if !store.has_bucket(name).await? {
+			return Err(S3Error::no_such_bucket());
+		}
+
+		let bucket = self.open_bucket(name, authorization).await?;
+		bucket.access().await?.check_see("")?;
+		return Ok(empty_response(StatusCode::OK));
+	}
+
+	async fn delete_bucket(&self, name: &str, authorization: &Authorization) -> S3Result {
+		let bucket = self.open_bucket(name, authorization).await?;
+		bucket.delete(false).await?;
+
+		return Ok(empty_response(StatusCode::NO_CONTENT));
+	}
+
+	async fn put_object(&self, bucket_name: &str, key: &str, parts: &Parts, authorization: &Authorization, body: Body) -> S3Result {
+		let mut reader = StreamReader::new(authorization.payload(body, &parts.headers));
+
+		if key.ends_with('/') {
+			let mut data = Vec::new();
+			reader.read_to_end(&mut data).await?;
+			if !data.is_empty() {
+				return Err(S3Error::invalid_request("A key ending with '/' can't hold data."));
+			}
+
+			let _guard = self.lock.lock().await;
+			let mut bucket = self.open_bucket(bucket_name, authorization).await?;
+			bucket.authorize(key, Permission::Write).await?;
+			ensure_dirs(&mut bucket, key).await?;
+			let properties = bucket.properties(&Bucket::calculate_key(key)).await?;
+			return Ok(object_response(StatusCode::OK, &properties, None));
+		}
+
+		let (mut bucket, parent_key, name) = {
+			let _guard = self.lock.lock().await;
+			let mut bucket = self.open_bucket(bucket_name, authorization).await?;
+			bucket.authorize(key, Permission::Write).await?;
+			let (parent_key, name) = match key.rsplit_once('/') {
+				None => (None, key),
+				Some((parent, name)) => (Some(ensure_dirs(&mut bucket, &format!("{parent}/")).await?), name),
+			};
+
+			if bucket.exists(&Bucket::calculate_key(&format!("{key}/"))).await? {
+				return Err(S3Error::invalid_request("A directory with the same name already exists."));
+			}
+			(bucket, parent_key, name)
+		};
+
+		let staged = bucket.stage_stream(parent_key.as_ref(), name, &mut reader).await?;
+
+		let _guard = self.lock.lock().await;
+		let object_key = bucket.commit_stream(staged, &preconditions(parts)).await?;
+
+		let properties = bucket.properties(&object_key).await?;
+		return Ok(object_response(StatusCode::OK, &properties, None));
+	}
+
+	async fn get_object(&self, bucket_name: &str, key: &str, parts: &Parts, authorization: &Authorization, info_only: bool) -> S3Result {
+		let bucket = self.open_bucket(bucket_name, authorization).await?;
+		bucket.authorize(key, Permission::Read).await?;
+		let object_key = Bucket::calculate_key(key);
+
+		if !bucket.exists(&object_key).await? {
+			return Err(S3Error::no_such_key());
+		}
+
+		let properties = bucket.properties(&object_key).await?;
+		if properties.is_delete {
+			return Err(S3Error::no_such_key());
+		}
+		preconditions(parts).check_read(&object_key, &properties)?;
+
+		if properties.is_dir {
+			let item = bucket.get(&object_key, true).await?;
+			let mut response = object_response(StatusCode::OK, &properties, Some(&item.mime_type));
+			response.headers_mut().insert("Content-Length", HeaderValue::from(0));
+			return Ok(response);
+		}
+
+		// A malformed or multi-part Range header is ignored and the whole object is returned, like S3 does.
+		let range = parts.headers.get("Range")
+			.and_then(|range| range.to_str().ok())
+			.and_then(|range| ByteRange::parse(range).ok());
+
+		let reader = bucket.read(&object_key, range.as_ref()).await?;
+
+		let status = if range.is_some() { StatusCode::PARTIAL_CONTENT } else { StatusCode::OK };
+		let mut response = object_response(status, &properties, Some(&reader.item.mime_type));
+		let headers = response.headers_mut();
+		headers.insert("Accept-Ranges", HeaderValue::from_static("bytes"));
+		headers.insert("Content-Length", HeaderValue::from(reader.length));
+		if range.is_some() {
+			let end = reader.start + reader.length - 1;
+			let content_range = format!("bytes {}-{}/{}", reader.start, end, reader.size);
+			headers.insert("Content-Range", HeaderValue::from_str(&content_range).unwrap());
+		}
+
+		if !info_only {
+			*response.body_mut() = Body::wrap_stream(ReaderStream::with_capacity(reader, READ_BUFFER_SIZE));
+		}
+
+		return Ok(response);
+	}
+
+	async fn delete_object(&self, bucket_name: &str, key: &str, parts: &Parts, authorization: &Authorization) -> S3Result {
+		let mut bucket = self.open_bucket(bucket_name, authorization).await?;
+		bucket.authorize(key, Permission::Write).await?;
+		let object_key = Bucket::calculate_key(key);
+
+		let preconditions = preconditions(parts);
+		if !preconditions.is_empty() {
+			let properties = match bucket.exists(&object_key).await? {
+				true => Some(bucket.properties(&object_key).await?),
+				false => None,
+			};
+			preconditions.check_write(&object_key, properties.as_ref())?;
+		}
+
+		if bucket.exists(&object_key).await? {
+			let properties = bucket.properties(&object_key).await?;
+			if !properties.is_delete && (!properties.is_dir || bucket.get_items(Some(&object_key)).await?.is_empty()) {
+				// With versioning the object goes to the trash with its versions, a new
+				// object with the same key keeps it as a version.
+				match bucket.is_versioning().await? {
+					true => bucket.set_delete(vec![object_key]).await?,
+					false => bucket.remove(vec![object_key]).await?,
+				}
+			}
+		}
+
+		return Ok(empty_response(StatusCode::NO_CONTENT));
+	}
+
+	async fn create_multipart_upload(&self, bucket_name: &str, key: &str, authorization: &Authorization) -> S3Result {
+		let mut bucket = self.open_bucket(bucket_name, authorization).await?;
+		if key.ends_with('/') {
+			return Err(S3Error::invalid_request("A key ending with '/' can't hold data."));
+		}
+		bucket.authorize(key, Permission::Write).await?;
+
+		let (parent_key, name) = match key.rsplit_once('/') {
+			None => (None, key),
+			Some((parent, name)) => (Some(ensure_dirs(&mut bucket, &format!("{parent}/")).await?), name),
+		};
+
+		if bucket.exists(&Bucket::calculate_key(&format!("{key}/"))).await? {
+			return Err(S3Error::invalid_request("A directory with the same name already exists."));
+		}
+
+		let upload_id = bucket.create_multipart(parent_key.as_ref(), name).await?;
+		return Ok(xml_response(StatusCode::OK, xml::initiate_multipart_upload(bucket_name, key, &upload_id)));
+	}
+
+	#[allow(clippy::too_many_arguments)]
+	async fn upload_part(&self, bucket_name: &str, key: &str, upload_id: &str, query: &HashMap<String, String>, parts: &Parts, authorization: &Authorization, body: Body) -> S3Result {
+		let bucket = {
+			let _guard = self.lock.lock().await;
+			let bucket = self.open_bucket(bucket_name, authorization).await?;
+			open_upload(&bucket, key, upload_id).await?;
+			bucket
+		};
+
+		// A part only changes its upload and the bucket quota, which is reserved atomically,
+		// so the lock isn't needed. Maintenance leaves an upload alone while a part is written.
+		let part_number = query.get("partNumber")
+			.and_then(|part_number| part_number.parse::<u32>().ok())
+			.filter(|part_number| (MIN_PART_NUMBER..=MAX_PART_NUMBER).contains(part_number))
+			.ok_or_else(|| S3Error::invalid_argument("Part number must be an integer between 1 and 10000, inclusive"))?;
+
+		let mut reader = StreamReader::new(authorization.payload(body, &parts.headers));
+		let part = bucket.upload_part(upload_id, part_number, &mut reader).await?;
+
+		return Ok(Response::builder()
+			.status(StatusCode::OK)
+			.header("ETag", format!("\"{}\"", part.etag))
+			.body(Body::empty())
+			.unwrap());
+	}
+
+	async fn list_parts(&self, bucket_name: &str, key: &str, upload_id: &str, query: &HashMap<String, String>, authorization: &Authorization) -> S3Result {
+		let bucket = self.open_bucket(bucket_name, authorization).await?;
+		open_upload(&bucket, key, upload_id).await?;
+
+		let max_parts = match query.get("max-parts") {
+			None => MAX_PARTS,
+			Some(max_parts) => max_parts.parse::<usize>()
+				.map_err(|_| S3Error::invalid_argument("Provided max-parts not an integer or within integer range"))?
+				.min(MAX_PARTS),
+		};
+		let part_number_marker = match query.get("part-number-marker") {
+			None => 0,
+			Some(marker) => marker.parse::<u32>()
+				.map_err(|_| S3Error::invalid_argument("Provided part-number-marker not an integer or within integer range"))?,
+		};
+
+		let mut parts: Vec<PartItem> = bucket.list_parts(upload_id).await?
+			.into_iter()
+			.filter(|part| part.part_number > part_number_marker)
+			.collect();
+
+		let next_part_number_marker = if parts.len() > max_parts {
+			parts.truncate(max_parts);
+			parts.last().map(|part| part.part_number)
+		} else {
+			None
+		};
+
+		return Ok(xml_response(StatusCode::OK, xml::list_parts(&ListPartsResult {
+			bucket: bucket_name.to_string(),
+			key: key.to_string(),
+			upload_id: upload_id.to_string(),
+			part_number_marker,
+			next_part_number_marker,
+			max_parts,
+			parts,
+		})));
+	}
+
+	async fn complete_multipart_upload(&self, bucket_name: &str, key: &str, upload_id: &str, parts: &Parts, authorization: &Authorization, body: Body) -> S3Result {
+		let mut data = Vec::new();
+		let reader = StreamReader::new(authorization.payload(body, &parts.headers));
+		reader.take(MAX_COMPLETE_BODY_SIZE + 1).read_to_end(&mut data).await?;
+
+		let _guard = self.lock.lock().await;
+		let mut bucket = self.open_bucket(bucket_name, authorization).await?;
+		open_upload(&bucket, key, upload_id).await?;
+		if data.len() as u64 > MAX_COMPLETE_BODY_SIZE {
+			return Err(S3Error::malformed_xml());
+		}
+
+		let completed_parts = String::from_utf8(data).ok()
+			.and_then(|body| xml::parse_complete_multipart_upload(&body))
+			.filter(|completed_parts| !completed_parts.is_empty())
+			.ok_or_else(S3Error::malformed_xml)?;
+
+		if let Some((parent, _)) = key.rsplit_once('/') {
+			ensure_dirs(&mut bucket, &format!("{parent}/")).await?;
+		}
+		if bucket.exists(&Bucket::calculate_key(&format!("{key}/"))).await? {
+			return Err(S3Error::invalid_request("A directory with the same name already exists."));
+		}
+
+		let (_, etag) = bucket.complete_multipart(upload_id, &completed_parts).await?;
+		return Ok(xml_response(StatusCode::OK, xml::complete_multipart_upload(bucket_name, key, &etag)));
+	}
+
+	async fn abort_multipart_upload(&self, bucket_name: &str, key: &str, upload_id: &str, authorization: &Authorization) -> S3Result {
+		let bucket = self.open_bucket(bucket_name, authorization).await?;
+		open_upload(&bucket, key, upload_id).await?;
+
+		bucket.abort_multipart(upload_id).await?;
+		return Ok(empty_response(StatusCode::NO_CONTENT));
+	}
+
+	async fn list_objects_v2(&self, bucket_name: &str, query: &HashMap<String, String>, authorization: &Authorization) -> S3Result {
+		let bucket = self.open_bucket(bucket_name, authorization).await?;
+
+		let prefix = query.get("prefix").cloned().unwrap_or_default();
+		let delimiter = query.get("delimiter").filter(|delimiter| !delimiter.is_empty()).cloned();
+		let start_after = query.get("start-after").cloned();
+		let continuation_token = query.get("continuation-token").cloned();
+		let max_keys = match query.get("max-keys") {
+			None => MAX_KEYS,
+			Some(max_keys) => max_keys.parse::<usize>()
+				.map_err(|_| S3Error::invalid_argument("Provided max-keys not an integer or within integer range"))?
+				.min(MAX_KEYS),
+		};
+
+		let listing = bucket.list_objects(&ListObjectsOptions {
+			prefix: prefix.clone(),
+			delimiter: delimiter.clone(),
+			start_after: start_after.clone(),
+			continuation_token: continuation_token.clone(),
+			max_keys,
+		}).await?;
+
+		let objects = listing.objects.into_iter()
+			.map(|object| ObjectEntry {
+				etag: object_etag(object.etag.as_deref(), object.size, object.update_at),
+				key: object.path,
+				size: object.size,
+				update_at: object.update_at,
+			})
+			.collect();
+
+		let result = ListObjectsResult {
+			bucket: bucket_name.to_string(),
+			prefix,
+			delimiter,
+			start_after,
+			max_keys,
+			encoding_url: query.get("encoding-type").map(String::as_str) == Some("url"),
+			continuation_token,
+			next_continuation_token: listing.next_continuation_token,
+			objects,
+			common_prefixes: listing.common_prefixes,
+		};
+
+		return Ok(xml_response(StatusCode::OK, xml::list_objects_v2(&result)));
+	}
+}
+
+// Whether the request has a body to store: PutObject, UploadPart and
+// CompleteMultipartUpload.
+fn streams_body(method: &Method, key: &str, query: &HashMap<String, String>) -> bool {
+	return match *method {
+		Method::PUT => !key.is_empty() && (query.is_empty() || query.contains_key("uploadId")),
+		Method::POST => query.contains_key("uploadId"),
+		_ => false,
+	};
+}
+
+// Checks that the upload exists and was created for `key`.
+async fn open_upload(bucket: &Bucket, key: &str, upload_id: &str) -> Result<MultipartUpload, S3Error> {
+	let upload = bucket.get_multipart(upload_id).await?;
+	if upload.path != key {
+		return Err(S3Error::no_such_upload());
+	}
+
+	return Ok(upload);
+}
+
+async fn ensure_dirs(bucket: &mut Bucket, dir_path: &str) -> Result<String, S3Error> {
+	let mut parent_key: Option<String> = None;
+	let mut current_path = String::new();
+
+	for name in dir_path.trim_end_matches('/').split('/') {
+		current_path.push_str(name);
+		if bucket.exists(&Bucket::calculate_key(&current_path)).await? {
+			return Err(S3Error::invalid_request("An object with the same name as the directory already exists."));
+		}
+
+		current_path.push('/');
+		let key = Bucket::calculate_key(&current_path);
+		if !bucket.exists(&key).await? {
+			bucket.add(&KeyValue {
+				key: parent_key.clone(),
+				name: name.to_string(),
+				value: None,
+			}).await?;
+		}
+		parent_key = Some(key);
+	}
+
+	return Ok(parent_key.unwrap());
+}
+
+fn etag(properties: &PropertiesItem) -> String {
+	return object_etag(properties.etag.as_deref(), properties.size, properties.update_at);
+}
+
+// Objects stored before ETags were recorded get one made of their size and mtime.
+fn object_etag(etag: Option<&str>, size: u64, update_at: u64) -> String {
+	if let Some(etag) = etag {
+		return etag.to_string();
+	}
+	return format!("{:x}-{:x}", size, update_at);
+}
+
+fn object_response(status: StatusCode, properties: &PropertiesItem, mime_type: Option<&str>) -> Response<Body> {
+	let mut response = Response::builder()
+		.status(status)
+		.header("ETag", format!("\"{}\"", etag(properties)))
+		.header("Last-Modified", xml::http_date(properties.update_at as i64));
+
+	if let Some(mime_type) = mime_type {
+		response = response.header("Content-Type", mime_type);
+	}
+
+	return response.body(Body::empty()).unwrap();
+}
+
+fn xml_response(status: StatusCode, body: String) -> Response<Body> {
+	return Response::builder()
+		.status(status)
+		.header("Content-Type", "application/xml")
+		.body(Body::from(body))
+		.unwrap();
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+	return Response::builder()
+		.status(status)
+		.body(Body::empty())
+		.unwrap();
+}
+
+// Reads the conditional request headers. A date that can't be parsed is ignored, as
+// RFC 7232 asks.
+fn preconditions(parts: &Parts) -> Preconditions {
+	let header = |name: &str| parts.headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+	let date = |name: &str| header(name)
+		.and_then(|value| DateTime::parse_from_rfc2822(&value).ok())
+		.map(|date| date.timestamp().max(0) as u64);
+
+	return Preconditions {
+		if_match: header("If-Match"),
+		if_none_match: header("If-None-Match"),
+		if_modified_since: date("If-Modified-Since"),
+		if_unmodified_since: date("If-Unmodified-Since"),
+	};
+}
+
+fn parse_query(query: Option<&str>) -> HashMap<String, String> {
+	let mut params = HashMap::new();
+	for pair in query.unwrap_or("").split('&').filter(|pair| !pair.is_empty()) {
+		let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+		params.insert(decode_component(name), decode_component(value));
+	}
+
+	return params;
+}
+
+fn decode_component(value: &str) -> String {
+	return percent_decode_str(&value.replace('+', " ")).decode_utf8_lossy().to_string();
+}
+
+fn validate_bucket_name(name: &str) -> Result<(), S3Error> {
+	let is_valid = (3..=63).contains(&name.len())
+		&& name.chars().all(|char| char.is_ascii_lowercase() || char.is_ascii_digit() || char == '-' || char == '.')
+		&& name.starts_with(|char: char| char.is_ascii_alphanumeric())
+		&& name.ends_with(|char: char| char.is_ascii_alphanumeric())
+		&& !name.contains("..");
+
+	if !is_valid {
+		return Err(S3Error::invalid_bucket_name());
+	}
+
+	return Ok(());
+}
+
+fn validate_key(key: &str) -> Result<(), S3Error> {
+	let is_valid = key.len() <= 1024
+		&& !key.contains('\0')
+		&& key.trim_end_matches('/').split('/').all(|name| !name.is_empty() && name != "." && name != "..")
+		&& !key.ends_with("//");
+
+	if !is_valid {
+		return Err(S3Error::invalid_argument("The object key can't be stored on the filesystem."));
+	}
+
+	return Ok(());
+}
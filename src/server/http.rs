@@ -0,0 +1,60 @@
+use std::future::Future;
+use std::io::Result;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use tokio::net::TcpListener;
+use tokio::task::{self, LocalSet};
+
+use crate::server::handler::S3Handler;
+
+// How often the lifecycle rules run and the expired trash of the buckets is purged.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Copy)]
+struct LocalExec;
+
+impl<F> hyper::rt::Executor<F> for LocalExec where F: Future + 'static {
+	fn execute(&self, future: F) {
+		task::spawn_local(future);
+	}
+}
+
+pub async fn run(store_path: impl AsRef<Path>, address: SocketAddr) -> Result<()> {
+	let handler = Rc::new(S3Handler::new(store_path));
+	let listener = TcpListener::bind(address).await?;
+	let local = LocalSet::new();
+
+	return local.run_until(async move {
+		let maintenance_handler = handler.clone();
+		task::spawn_local(async move {
+			let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+			loop {
+				interval.tick().await;
+				if let Err(error) = maintenance_handler.run_maintenance().await {
+					eprintln!("Maintenance error: {error}");
+				}
+			}
+		});
+
+		loop {
+			let (stream, _) = listener.accept().await?;
+			let handler = handler.clone();
+
+			task::spawn_local(async move {
+				let service = service_fn(move |request| {
+					let handler = handler.clone();
+					async move { handler.handle(request).await }
+				});
+
+				if let Err(error) = Http::new().with_executor(LocalExec).serve_connection(stream, service).await {
+					eprintln!("Connection error: {error}");
+				}
+			});
+		}
+	}).await;
+}
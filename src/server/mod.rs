@@ -0,0 +1,8 @@
+pub mod auth;
+pub mod error;
+pub mod handler;
+pub mod http;
+pub mod xml;
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,194 @@
+use chrono::{TimeZone, Utc};
+use regex::Regex;
+
+use crate::bucket::multipart::{CompletedPart, PartItem};
+use crate::storage::registry::BucketInfo;
+
+const XML_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+const S3_NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+pub struct ObjectEntry {
+	pub key: String,
+	pub size: u64,
+	pub update_at: u64,
+	pub etag: String,
+}
+
+pub struct ListObjectsResult {
+	pub bucket: String,
+	pub prefix: String,
+	pub delimiter: Option<String>,
+	pub start_after: Option<String>,
+	pub max_keys: usize,
+	pub encoding_url: bool,
+	pub continuation_token: Option<String>,
+	pub next_continuation_token: Option<String>,
+	pub objects: Vec<ObjectEntry>,
+	pub common_prefixes: Vec<String>,
+}
+
+pub struct ListPartsResult {
+	pub bucket: String,
+	pub key: String,
+	pub upload_id: String,
+	pub part_number_marker: u32,
+	pub next_part_number_marker: Option<u32>,
+	pub max_parts: usize,
+	pub parts: Vec<PartItem>,
+}
+
+pub fn escape(value: &str) -> String {
+	let mut escaped = String::with_capacity(value.len());
+	for char in value.chars() {
+		match char {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&apos;"),
+			_ => escaped.push(char),
+		}
+	}
+
+	return escaped;
+}
+
+pub fn timestamp(seconds: i64) -> String {
+	return Utc.timestamp_opt(seconds, 0).unwrap().format("%Y-%m-%dT%H:%M:%S.000Z").to_string();
+}
+
+pub fn http_date(seconds: i64) -> String {
+	return Utc.timestamp_opt(seconds, 0).unwrap().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+}
+
+pub fn error(code: &str, message: &str, resource: &str, request_id: &str) -> String {
+	return format!(
+		"{XML_HEADER}<Error><Code>{}</Code><Message>{}</Message><Resource>{}</Resource><RequestId>{}</RequestId></Error>",
+		code, escape(message), escape(resource), request_id,
+	);
+}
+
+pub fn location() -> String {
+	return format!(r#"{XML_HEADER}<LocationConstraint xmlns="{S3_NAMESPACE}"></LocationConstraint>"#);
+}
+
+pub fn list_buckets(owner_id: &str, buckets: &[BucketInfo]) -> String {
+	let mut xml = format!(r#"{XML_HEADER}<ListAllMyBucketsResult xmlns="{S3_NAMESPACE}">"#);
+	xml.push_str(&format!("<Owner><ID>{0}</ID><DisplayName>{0}</DisplayName></Owner><Buckets>", escape(owner_id)));
+	for bucket in buckets {
+		xml.push_str(&format!(
+			"<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>",
+			escape(&bucket.name), timestamp(bucket.create_at),
+		));
+	}
+	xml.push_str("</Buckets></ListAllMyBucketsResult>");
+
+	return xml;
+}
+
+pub fn list_objects_v2(result: &ListObjectsResult) -> String {
+	let encode = |value: &str| {
+		if result.encoding_url {
+			return escape(&percent_encoding::utf8_percent_encode(value, percent_encoding::NON_ALPHANUMERIC).to_string());
+		}
+		return escape(value);
+	};
+
+	let mut xml = format!(r#"{XML_HEADER}<ListBucketResult xmlns="{S3_NAMESPACE}">"#);
+	xml.push_str(&format!("<Name>{}</Name>", escape(&result.bucket)));
+	xml.push_str(&format!("<Prefix>{}</Prefix>", encode(&result.prefix)));
+	if let Some(delimiter) = &result.delimiter {
+		xml.push_str(&format!("<Delimiter>{}</Delimiter>", encode(delimiter)));
+	}
+	if let Some(start_after) = &result.start_after {
+		xml.push_str(&format!("<StartAfter>{}</StartAfter>", encode(start_after)));
+	}
+	if result.encoding_url {
+		xml.push_str("<EncodingType>url</EncodingType>");
+	}
+	xml.push_str(&format!("<MaxKeys>{}</MaxKeys>", result.max_keys));
+	xml.push_str(&format!("<KeyCount>{}</KeyCount>", result.objects.len() + result.common_prefixes.len()));
+	if let Some(token) = &result.continuation_token {
+		xml.push_str(&format!("<ContinuationToken>{}</ContinuationToken>", escape(token)));
+	}
+	xml.push_str(&format!("<IsTruncated>{}</IsTruncated>", result.next_continuation_token.is_some()));
+	if let Some(token) = &result.next_continuation_token {
+		xml.push_str(&format!("<NextContinuationToken>{}</NextContinuationToken>", escape(token)));
+	}
+
+	for object in &result.objects {
+		xml.push_str(&format!(
+			"<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>&quot;{}&quot;</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+			encode(&object.key), timestamp(object.update_at as i64), escape(&object.etag), object.size,
+		));
+	}
+	for prefix in &result.common_prefixes {
+		xml.push_str(&format!("<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>", encode(prefix)));
+	}
+	xml.push_str("</ListBucketResult>");
+
+	return xml;
+}
+
+pub fn initiate_multipart_upload(bucket: &str, key: &str, upload_id: &str) -> String {
+	return format!(
+		r#"{XML_HEADER}<InitiateMultipartUploadResult xmlns="{S3_NAMESPACE}"><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId></InitiateMultipartUploadResult>"#,
+		escape(bucket), escape(key), escape(upload_id),
+	);
+}
+
+pub fn complete_multipart_upload(bucket: &str, key: &str, etag: &str) -> String {
+	return format!(
+		r#"{XML_HEADER}<CompleteMultipartUploadResult xmlns="{S3_NAMESPACE}"><Location>/{}/{}</Location><Bucket>{}</Bucket><Key>{}</Key><ETag>&quot;{}&quot;</ETag></CompleteMultipartUploadResult>"#,
+		escape(bucket), escape(key), escape(bucket), escape(key), escape(etag),
+	);
+}
+
+pub fn list_parts(result: &ListPartsResult) -> String {
+	let mut xml = format!(r#"{XML_HEADER}<ListPartsResult xmlns="{S3_NAMESPACE}">"#);
+	xml.push_str(&format!("<Bucket>{}</Bucket>", escape(&result.bucket)));
+	xml.push_str(&format!("<Key>{}</Key>", escape(&result.key)));
+	xml.push_str(&format!("<UploadId>{}</UploadId>", escape(&result.upload_id)));
+	xml.push_str(&format!("<PartNumberMarker>{}</PartNumberMarker>", result.part_number_marker));
+	if let Some(marker) = result.next_part_number_marker {
+		xml.push_str(&format!("<NextPartNumberMarker>{}</NextPartNumberMarker>", marker));
+	}
+	xml.push_str(&format!("<MaxParts>{}</MaxParts>", result.max_parts));
+	xml.push_str(&format!("<IsTruncated>{}</IsTruncated>", result.next_part_number_marker.is_some()));
+	xml.push_str("<StorageClass>STANDARD</StorageClass>");
+
+	for part in &result.parts {
+		xml.push_str(&format!(
+			"<Part><PartNumber>{}</PartNumber><LastModified>{}</LastModified><ETag>&quot;{}&quot;</ETag><Size>{}</Size></Part>",
+			part.part_number, timestamp(part.update_at), escape(&part.etag), part.size,
+		));
+	}
+	xml.push_str("</ListPartsResult>");
+
+	return xml;
+}
+
+// Reads the parts out of a CompleteMultipartUpload request body.
+pub fn parse_complete_multipart_upload(body: &str) -> Option<Vec<CompletedPart>> {
+	let document_regex = Regex::new(r"(?s)<CompleteMultipartUpload(\s[^>]*)?>(.*)</CompleteMultipartUpload>").unwrap();
+	let part_regex = Regex::new(r"(?s)<Part>(.*?)</Part>").unwrap();
+	let number_regex = Regex::new(r"(?s)<PartNumber>\s*(\d+)\s*</PartNumber>").unwrap();
+	let etag_regex = Regex::new(r"(?s)<ETag>(.*?)</ETag>").unwrap();
+
+	let document = document_regex.captures(body)?.get(2)?.as_str();
+	let mut parts: Vec<CompletedPart> = Vec::new();
+	for part in part_regex.captures_iter(document) {
+		let part = part.get(1)?.as_str();
+		let part_number = number_regex.captures(part)?.get(1)?.as_str().parse::<u32>().ok()?;
+		let etag = etag_regex.captures(part)?.get(1)?.as_str()
+			.replace("&quot;", "\"")
+			.replace("&#34;", "\"");
+
+		parts.push(CompletedPart {
+			part_number,
+			etag: etag.trim().trim_matches('"').to_string(),
+		});
+	}
+
+	return Some(parts);
+}
@@ -0,0 +1,76 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::error::Result;
+
+const CREDENTIALS_FILE: &str = "credentials.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Credential {
+	pub access_key: String,
+	pub secret_key: String,
+	pub create_at: i64,
+	// The user the key acts as. A key without a user has full access to the store.
+	#[serde(default)]
+	pub user_uuid: Option<String>,
+}
+
+pub struct Credentials;
+
+impl Credentials {
+	pub async fn load(store_path: impl AsRef<Path>) -> Result<Vec<Credential>> {
+		let path = store_path.as_ref().join(CREDENTIALS_FILE);
+		if !path.exists() {
+			return Ok(Vec::new());
+		}
+
+		let file = fs::read(&path).await?;
+		let credentials: Vec<Credential> = serde_json::from_slice(&file)?;
+		return Ok(credentials);
+	}
+
+	pub async fn find(store_path: impl AsRef<Path>, access_key: &str) -> Result<Option<Credential>> {
+		let credentials = Credentials::load(store_path).await?;
+		return Ok(credentials.into_iter().find(|credential| credential.access_key == access_key));
+	}
+
+	pub async fn create(store_path: impl AsRef<Path>) -> Result<Credential> {
+		return Credentials::create_for(store_path, None).await;
+	}
+
+	/// Creates a key that acts as the user `user_uuid`, or as the store itself without one.
+	pub async fn create_for(store_path: impl AsRef<Path>, user_uuid: Option<&str>) -> Result<Credential> {
+		let mut credentials = Credentials::load(&store_path).await?;
+
+		let credential = Credential {
+			access_key: Uuid::new_v4().simple().to_string()[..20].to_uppercase(),
+			secret_key: format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+			create_at: Utc::now().timestamp(),
+			user_uuid: user_uuid.map(str::to_string),
+		};
+		credentials.push(credential.clone());
+		Credentials::save(&store_path, &credentials).await?;
+
+		return Ok(credential);
+	}
+
+	pub async fn remove(store_path: impl AsRef<Path>, access_key: &str) -> Result<()> {
+		let mut credentials = Credentials::load(&store_path).await?;
+		credentials.retain(|credential| credential.access_key != access_key);
+		Credentials::save(&store_path, &credentials).await?;
+		return Ok(());
+	}
+
+	async fn save(store_path: impl AsRef<Path>, credentials: &[Credential]) -> Result<()> {
+		let path = store_path.as_ref().join(CREDENTIALS_FILE);
+		let credentials_json = serde_json::to_string(credentials)?;
+		fs::write(&path, credentials_json.as_bytes()).await?;
+		fs::set_permissions(&path, PermissionsExt::from_mode(0o600)).await?;
+		return Ok(());
+	}
+}
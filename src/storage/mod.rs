@@ -1,5 +1,8 @@
 pub mod store;
 pub mod space;
+pub mod credentials;
+pub mod registry;
+pub mod users;
 
 #[cfg(test)]
 mod test;
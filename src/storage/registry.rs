@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::bucket::bucket::Bucket;
+use crate::bucket::fs_metadata::FsMetadata;
+use crate::error::{Result, SolarError};
+use crate::storage::store::Store;
+use crate::storage::users::User;
+
+const REGISTRY_FILE: &str = "buckets.json";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BucketEntry {
+	pub name: String,
+	pub uuid: String,
+	pub create_at: i64,
+}
+
+pub struct BucketInfo {
+	pub name: String,
+	pub uuid: String,
+	pub create_at: i64,
+	pub available_space: u64,
+	pub usage_space: u64,
+	pub owner_uuid: Option<String>,
+}
+
+/// The buckets of a store are registered by name in `<store>/buckets.json`, the
+/// bucket directories themselves are named by uuid.
+impl Store {
+	pub async fn get_buckets(&self) -> Result<Vec<BucketInfo>> {
+		let mut buckets: Vec<BucketInfo> = Vec::new();
+		for entry in self.load_registry().await? {
+			buckets.push(self.bucket_info(entry).await?);
+		}
+		buckets.sort_by(|a, b| a.name.cmp(&b.name));
+
+		return Ok(buckets);
+	}
+
+	pub async fn get_bucket(&self, name: &str) -> Result<BucketInfo> {
+		let entry = self.find_bucket(name).await?;
+		return self.bucket_info(entry).await;
+	}
+
+	pub async fn has_bucket(&self, name: &str) -> Result<bool> {
+		let entries = self.load_registry().await?;
+		return Ok(entries.iter().any(|entry| entry.name == name));
+	}
+
+	/// Creates the bucket `name`, owned by `owner` when there is one.
+	pub async fn create_bucket(&self, name: &str, bucket_space: u64, owner: Option<&User>) -> Result<Bucket> {
+		if name.is_empty() {
+			return Err(SolarError::InvalidInput("The bucket name can't be empty!".to_string()));
+		}
+
+		let mut entries = self.load_registry().await?;
+		if entries.iter().any(|entry| entry.name == name) {
+			return Err(SolarError::AlreadyExists(format!("Bucket {} already exists", name)));
+		}
+
+		let uuid = Uuid::new_v4().to_string();
+		let mut bucket = Bucket::create(self.clone(), &uuid, bucket_space).await?;
+		if let Some(owner) = owner {
+			bucket.set_owner(&owner.uuid).await?;
+		}
+		bucket.set_user(owner);
+
+		entries.push(BucketEntry {
+			name: name.to_string(),
+			uuid,
+			create_at: Utc::now().timestamp(),
+		});
+		self.save_registry(&entries).await?;
+
+		return Ok(bucket);
+	}
+
+	pub async fn open_bucket(&self, name: &str) -> Result<Bucket> {
+		let entry = self.find_bucket(name).await?;
+		return Bucket::open(self.clone(), &entry.uuid).await;
+	}
+
+	/// Purges the expired trash of every bucket and returns the number of removed items.
+	pub async fn purge_trash(&self, date: i64) -> Result<usize> {
+		let mut count = 0;
+		for entry in self.load_registry().await? {
+			let bucket = Bucket::open(self.clone(), &entry.uuid).await?;
+			count += bucket.purge_trash(date).await?.len();
+		}
+
+		return Ok(count);
+	}
+
+	/// Aborts the uploads of every bucket created before `date` and returns their number.
+	pub async fn clear_multiparts(&self, date: i64) -> Result<usize> {
+		let mut count = 0;
+		for entry in self.load_registry().await? {
+			let bucket = Bucket::open(self.clone(), &entry.uuid).await?;
+			count += bucket.clear_multiparts(date).await?.len();
+		}
+
+		return Ok(count);
+	}
+
+	/// Applies the lifecycle rules of every bucket and returns the number of affected
+	/// objects, versions and uploads.
+	pub async fn apply_lifecycle(&self, date: i64) -> Result<usize> {
+		let mut count = 0;
+		for entry in self.load_registry().await? {
+			let bucket = Bucket::open(self.clone(), &entry.uuid).await?;
+			let result = bucket.apply_lifecycle(date).await?;
+			count += result.expired.len() + result.versions.len() + result.uploads.len();
+		}
+
+		return Ok(count);
+	}
+
+	/// Drops the bucket `uuid` from the registry, its directory is left as it is.
+	pub async fn unregister_bucket(&self, uuid: &str) -> Result<()> {
+		let mut entries = self.load_registry().await?;
+		entries.retain(|entry| entry.uuid != uuid);
+		return self.save_registry(&entries).await;
+	}
+
+	async fn find_bucket(&self, name: &str) -> Result<BucketEntry> {
+		let entries = self.load_registry().await?;
+		return entries.into_iter()
+			.find(|entry| entry.name == name)
+			.ok_or_else(|| SolarError::NoSuchBucket(name.to_string()));
+	}
+
+	async fn bucket_info(&self, entry: BucketEntry) -> Result<BucketInfo> {
+		let bucket = FsMetadata::new(&Path::new(&self.store_path).join(&entry.uuid)).await?;
+		let space = bucket.get_space().await?;
+		return Ok(BucketInfo {
+			name: entry.name,
+			uuid: entry.uuid,
+			create_at: entry.create_at,
+			available_space: space.available_space,
+			usage_space: space.usage_space,
+			owner_uuid: bucket.get_owner().await?,
+		});
+	}
+
+	async fn load_registry(&self) -> Result<Vec<BucketEntry>> {
+		let path = Path::new(&self.store_path).join(REGISTRY_FILE);
+		if !path.exists() {
+			return Ok(Vec::new());
+		}
+
+		let file = fs::read(&path).await?;
+		let entries: Vec<BucketEntry> = serde_json::from_slice(&file)?;
+		return Ok(entries);
+	}
+
+	async fn save_registry(&self, entries: &[BucketEntry]) -> Result<()> {
+		let path = Path::new(&self.store_path).join(REGISTRY_FILE);
+		let entries_json = serde_json::to_string(entries)?;
+		fs::write(&path, entries_json.as_bytes()).await?;
+		return Ok(());
+	}
+}
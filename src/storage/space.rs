@@ -1,33 +1,58 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use futures::future::{BoxFuture, FutureExt};
 #[cfg(test)]
 use mocktopus::macros::*;
+use sysinfo::{DiskExt, RefreshKind, System, SystemExt};
 use tokio::fs;
 
+use crate::error::{Result, SolarError};
+
 pub struct Space;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disc {
+	pub total_space: u64,
+	pub free_space: u64,
+}
+
 #[cfg_attr(test, mockable)]
+#[cfg_attr(test, allow(clippy::swap_ptr_to_ref))]
 impl Space {
-	pub fn get_disc() -> u64 {
-		return 1099511627776;
+	/// Returns the size of the filesystem that contains `path` and the bytes still
+	/// available on it. The path doesn't have to exist yet, its closest existing
+	/// ancestor is used instead.
+	pub fn get_disc(path: &Path) -> Result<Disc> {
+		let path = path.ancestors()
+			.find_map(|ancestor| std::fs::canonicalize(ancestor).ok())
+			.ok_or_else(|| SolarError::InvalidInput(format!("Can't resolve the path {}", path.display())))?;
+
+		let mut system = System::new_with_specifics(RefreshKind::new());
+		system.refresh_disks_list();
+
+		return system.disks().iter()
+			.filter(|disk| path.starts_with(disk.mount_point()))
+			.max_by_key(|disk| disk.mount_point().as_os_str().len())
+			.map(|disk| Disc { total_space: disk.total_space(), free_space: disk.available_space() })
+			.ok_or_else(|| SolarError::InvalidInput(format!("Can't find the disk of {}", path.display())));
 	}
 	
-	pub fn dir_size(dir_path: &PathBuf) -> BoxFuture<u64> {
+	pub fn dir_size(dir_path: &PathBuf) -> BoxFuture<'_, Result<u64>> {
 		async move {
 			let mut total_size = 0;
-			let mut dir = fs::read_dir(dir_path).await.unwrap();
-			while let Some(item) = dir.next_entry().await.unwrap() {
-				if (item.file_name().to_str().unwrap() == "user-paths.sqlite") {
+			let mut dir = fs::read_dir(dir_path).await?;
+			while let Some(item) = dir.next_entry().await? {
+				if item.file_name() == "user-paths.sqlite" {
 					continue;
 				}
-				if item.metadata().await.unwrap().is_dir() {
-					total_size += Space::dir_size(&item.path()).await;
+				let metadata = item.metadata().await?;
+				if metadata.is_dir() {
+					total_size += Space::dir_size(&item.path()).await?;
 					continue;
 				}
-				total_size += item.metadata().await.unwrap().len();
+				total_size += metadata.len();
 			}
-			return total_size;
+			return Ok(total_size);
 		}.boxed()
 	}
 }
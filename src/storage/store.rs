@@ -1,4 +1,3 @@
-use std::io::{Error, ErrorKind, Result};
 use std::path::Path;
 use std::str;
 
@@ -8,6 +7,9 @@ use serde_json;
 use tokio::fs;
 use uuid::Uuid;
 
+use crate::bucket::fs_metadata::{FsMetadata, Size};
+use crate::bucket::fsck::data_size;
+use crate::error::{Result, SolarError};
 use crate::storage::space::Space;
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -25,72 +27,136 @@ pub trait Storage {
     async fn create(path: impl AsRef<Path>, available_space: u64, logging: Option<bool>) -> Result<Store>;
     async fn open(path: impl AsRef<Path>) -> Result<Store>;
     async fn update_space(&mut self, add_size: u64) -> Result<u64>;
-    async fn restore(&self) -> Result<Store>;
-    async fn recalculation_usage_space(&self) -> Result<u64>;
+    async fn release_space(&mut self, remove_size: u64) -> Result<u64>;
+    async fn restore(path: impl AsRef<Path>) -> Result<Store>;
+    async fn recalculation_usage_space(&mut self) -> Result<u64>;
 }
 
 #[async_trait(? Send)]
 impl Storage for Store {
     async fn create(path: impl AsRef<Path>, available_space: u64, logging: Option<bool>) -> Result<Store> {
-        let disk_space: u64 = Space::get_disc();
-        let free_size: i128 = disk_space as i128 - available_space as i128;
-        if free_size <= 0 {
-            return Err(Error::new(ErrorKind::StorageFull, "There is no free space to initialize the Storage!"));
+        let path = Path::new(path.as_ref());
+        let disc = Space::get_disc(path)?;
+        if available_space >= disc.free_space {
+            return Err(SolarError::QuotaExceeded("There is no free space to initialize the Storage!".to_string()));
         }
 
-        let path = Path::new(path.as_ref());
         fs::create_dir(&path).await?;
 
         let store = Store {
             uuid: Uuid::new_v4().to_string(),
-            store_path: path.to_str().unwrap().to_string(),
-            store_name: path.file_name().unwrap().to_str().unwrap().to_string(),
+            store_path: path.to_string_lossy().to_string(),
+            store_name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
             available_space,
             usage_space: 0,
             logging: logging.unwrap_or(false),
         };
 
-        let store_json = serde_json::to_string(&store)?;
-        fs::write(&path.join("storage.json"), &store_json.as_bytes()).await?;
-
+        store.save().await?;
         return Ok(store);
     }
 
     async fn open(path: impl AsRef<Path>) -> Result<Store> {
         let path = Path::new(path.as_ref()).join("storage.json");
         let file = fs::read(&path).await?;
-        let json_str = str::from_utf8(&file).unwrap();
-        let storage: Store = serde_json::from_str(json_str)?;
+        let storage: Store = serde_json::from_slice(&file)?;
         return Ok(storage);
     }
 
+    // Every bucket holds its own copy of the store, so the usage is always applied
+    // to the one saved in `storage.json`.
     async fn update_space(&mut self, add_size: u64) -> Result<u64> {
-        let new_size = self.usage_space + add_size;
-        if new_size > self.available_space {
-            return Err(Error::new(ErrorKind::StorageFull, "There is no free space to update!"));
+        let current = Store::open(&self.store_path).await?;
+        let new_size = current.usage_space + add_size;
+        if new_size > current.available_space {
+            return Err(SolarError::QuotaExceeded("There is no free space to update!".to_string()));
         }
 
+        self.available_space = current.available_space;
         self.usage_space = new_size;
-        let path = Path::new(&self.store_path).join("storage.json");
+        self.save().await?;
 
-        let store_json = serde_json::to_string(&self)?;
-        fs::write(&path, &store_json.as_bytes()).await?;
+        return Ok(new_size);
+    }
+
+    async fn release_space(&mut self, remove_size: u64) -> Result<u64> {
+        let current = Store::open(&self.store_path).await?;
+        let new_size = current.usage_space.saturating_sub(remove_size);
+
+        self.available_space = current.available_space;
+        self.usage_space = new_size;
+        self.save().await?;
 
         return Ok(new_size);
     }
 
-    async fn restore(&self) -> Result<Store> {
-        return Ok(Store {
-            uuid: "".to_string(),
-            store_path: "".to_string(),
-            store_name: "".to_string(),
-            available_space: 0,
-            usage_space: 0,
-            logging: false,
-        });
+    /// Rebuilds `storage.json` from the bucket directories of the store. The uuid,
+    /// the quota and the logging flag are kept when the old file can still be read,
+    /// otherwise the quota becomes the sum of the bucket quotas.
+    async fn restore(path: impl AsRef<Path>) -> Result<Store> {
+        let path = Path::new(path.as_ref());
+        let (buckets, usage_space) = recalculate_buckets(path).await?;
+
+        let store = match Store::open(path).await {
+            Ok(store) => Store {
+                store_path: path.to_string_lossy().to_string(),
+                store_name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                usage_space,
+                ..store
+            },
+            Err(_) => Store {
+                uuid: Uuid::new_v4().to_string(),
+                store_path: path.to_string_lossy().to_string(),
+                store_name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                available_space: buckets.iter().map(|bucket| bucket.available_space).sum(),
+                usage_space,
+                logging: false,
+            },
+        };
+
+        store.save().await?;
+        return Ok(store);
+    }
+
+    /// Recounts the usage of every bucket from the files on disk, then the usage of the store.
+    async fn recalculation_usage_space(&mut self) -> Result<u64> {
+        let (_, usage_space) = recalculate_buckets(Path::new(&self.store_path)).await?;
+        self.usage_space = usage_space;
+        self.save().await?;
+
+        return Ok(self.usage_space);
     }
+}
 
-    async fn recalculation_usage_space(&self) -> Result<u64> {
-        return Ok(0);
+impl Store {
+    async fn save(&self) -> Result<()> {
+        let path = Path::new(&self.store_path).join("storage.json");
+        let store_json = serde_json::to_string(&self)?;
+        fs::write(&path, &store_json.as_bytes()).await?;
+        return Ok(());
     }
 }
+
+// Every directory of the store with a `files` directory inside is a bucket. Returns
+// the sizes of the buckets and the usage of the store, counted like `Bucket::check`.
+async fn recalculate_buckets(path: &Path) -> Result<(Vec<Size>, u64)> {
+    let mut buckets: Vec<Size> = Vec::new();
+    let mut store_usage_space = 0;
+    let mut dir = fs::read_dir(path).await?;
+    while let Some(item) = dir.next_entry().await? {
+        if !item.path().join("files").is_dir() {
+            continue;
+        }
+
+        let bucket = FsMetadata::new(&item.path()).await?;
+        let data_size = data_size(&item.path()).await?;
+        bucket.set_usage_space(data_size.bucket_size()).await?;
+        buckets.push(Size {
+            available_space: bucket.get_space().await?.available_space,
+            usage_space: data_size.bucket_size(),
+        });
+        store_usage_space += data_size.stored_size;
+    }
+
+    return Ok((buckets, store_usage_space));
+}
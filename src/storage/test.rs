@@ -1,13 +1,15 @@
 #[cfg(test)]
 mod tests_store {
-    use std::io::Result;
     use std::path::{Path, PathBuf};
 
     use mocktopus::mocking::*;
     use tokio::fs;
     use uuid::Uuid;
 
-    use crate::storage::space::Space;
+    use crate::bucket::bucket::{Bucket, KeyValue};
+    use crate::bucket::fs_metadata::FsMetadata;
+    use crate::error::Result;
+    use crate::storage::space::{Disc, Space};
     use crate::storage::store::{Storage, Store};
 
     async fn prepare_test() -> Result<PathBuf> {
@@ -40,7 +42,7 @@ mod tests_store {
 
     #[tokio::test]
 	async fn test_create_failed() {
-		Space::get_disc.mock_safe(|| MockResult::Return(0));
+		Space::get_disc.mock_safe(|_| MockResult::Return(Ok(Disc { total_space: 0, free_space: 0 })));
 		let path = prepare_test().await.unwrap();
 
         let res = Store::create(&path, 10, None).await.is_err();
@@ -51,7 +53,7 @@ mod tests_store {
 
     #[tokio::test]
 	async fn test_open() {
-		Space::get_disc.mock_safe(|| MockResult::Return(10000));
+		Space::get_disc.mock_safe(|_| MockResult::Return(Ok(Disc { total_space: 10000, free_space: 10000 })));
 		let path = prepare_test().await.unwrap();
 
         Store::create(&path, 1000, None).await.unwrap();
@@ -95,4 +97,184 @@ mod tests_store {
 
         fs::remove_dir_all(&path).await.is_err();
     }
+
+    #[tokio::test]
+    async fn test_restore() {
+        let path = prepare_test().await.unwrap();
+
+        let store = Store::create(&path, 1000, None).await.unwrap();
+        let mut bucket = Bucket::create(store.clone(), &Uuid::new_v4().to_string(), 600).await.unwrap();
+        bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"console.log".to_vec()) }).await.unwrap();
+        Bucket::create(store.clone(), &Uuid::new_v4().to_string(), 300).await.unwrap();
+
+        fs::remove_file(path.join("storage.json")).await.unwrap();
+        let res = Store::restore(&path).await.unwrap();
+        assert_eq!(res.available_space, 900);
+        assert_eq!(res.usage_space, 11);
+        assert_ne!(res.uuid, store.uuid);
+
+        fs::write(path.join("storage.json"), b"{\"uuid\": ").await.unwrap();
+        Store::restore(&path).await.unwrap();
+        let res = Store::open(&path).await.unwrap();
+        assert_eq!(res.available_space, 900);
+        assert_eq!(res.usage_space, 11);
+
+        fs::remove_dir_all(&path).await.is_err();
+    }
+
+    #[tokio::test]
+    async fn test_recalculation_usage_space() {
+        let path = prepare_test().await.unwrap();
+
+        let store = Store::create(&path, 1000, None).await.unwrap();
+        let mut bucket = Bucket::create(store, &Uuid::new_v4().to_string(), 600).await.unwrap();
+        bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"console.log".to_vec()) }).await.unwrap();
+        FsMetadata::new(&bucket.path).await.unwrap().set_usage_space(500).await.unwrap();
+
+        let mut res = Store::open(&path).await.unwrap();
+        res.update_space(100).await.unwrap();
+        let size = res.recalculation_usage_space().await.unwrap();
+        assert_eq!(size, 11);
+        assert_eq!(Store::open(&path).await.unwrap().usage_space, 11);
+        assert_eq!(Bucket::open(res, &bucket.uuid).await.unwrap().usage_space, 11);
+
+        fs::remove_dir_all(&path).await.is_err();
+    }
+
+    #[tokio::test]
+    async fn test_get_disc() {
+        let path = prepare_test().await.unwrap();
+
+        let disc = Space::get_disc(&path).unwrap();
+        assert!(disc.total_space > 0);
+        assert!(disc.free_space <= disc.total_space);
+    }
+}
+
+#[cfg(test)]
+mod tests_credentials {
+    use std::path::Path;
+
+    use tokio::fs;
+    use uuid::Uuid;
+
+    use crate::storage::credentials::Credentials;
+    use crate::storage::store::{Storage, Store};
+
+    #[tokio::test]
+    async fn test_create_find_remove() {
+        let path = Path::new("../storages").join(Uuid::new_v4().to_string());
+        fs::create_dir_all("../storages").await.is_err();
+        Store::create(&path, 1000, None).await.unwrap();
+
+        let credential = Credentials::create(&path).await.unwrap();
+        assert_eq!(credential.access_key.len(), 20);
+        assert!(path.join("credentials.json").exists());
+
+        let found = Credentials::find(&path, &credential.access_key).await.unwrap().unwrap();
+        assert_eq!(found.secret_key, credential.secret_key);
+
+        Credentials::remove(&path, &credential.access_key).await.unwrap();
+        assert!(Credentials::find(&path, &credential.access_key).await.unwrap().is_none());
+
+        fs::remove_dir_all(&path).await.is_err();
+    }
+}
+
+#[cfg(test)]
+mod tests_users {
+    use std::path::Path;
+
+    use tokio::fs;
+    use uuid::Uuid;
+
+    use crate::error::SolarError;
+    use crate::storage::credentials::Credentials;
+    use crate::storage::store::{Storage, Store};
+    use crate::storage::users::Users;
+
+    #[tokio::test]
+    async fn test_create_find_remove() {
+        let path = Path::new("../storages").join(Uuid::new_v4().to_string());
+        fs::create_dir_all("../storages").await.is_err();
+        Store::create(&path, 1000, None).await.unwrap();
+
+        let user = Users::create(&path, "alice", false).await.unwrap();
+        Users::create(&path, "bob", true).await.unwrap();
+        assert!(path.join("users.json").exists());
+        assert!(matches!(Users::create(&path, "alice", true).await.err().unwrap(), SolarError::AlreadyExists(_)));
+        assert!(matches!(Users::create(&path, "", true).await.err().unwrap(), SolarError::InvalidInput(_)));
+
+        assert_eq!(Users::find(&path, &user.uuid).await.unwrap(), Some(user.clone()));
+        assert!(Users::find_by_name(&path, "bob").await.unwrap().unwrap().is_admin);
+
+        let credential = Credentials::create_for(&path, Some(&user.uuid)).await.unwrap();
+        let found = Credentials::find(&path, &credential.access_key).await.unwrap().unwrap();
+        assert_eq!(found.user_uuid, Some(user.uuid.clone()));
+        assert_eq!(Credentials::create(&path).await.unwrap().user_uuid, None);
+
+        Users::remove(&path, &user.uuid).await.unwrap();
+        assert!(Users::find(&path, &user.uuid).await.unwrap().is_none());
+        assert_eq!(Users::load(&path).await.unwrap().len(), 1);
+
+        fs::remove_dir_all(&path).await.is_err();
+    }
+}
+
+#[cfg(test)]
+mod tests_registry {
+	use std::path::Path;
+
+	use tokio::fs;
+	use uuid::Uuid;
+
+	use crate::bucket::bucket::KeyValue;
+	use crate::error::SolarError;
+	use crate::storage::store::{Storage, Store};
+
+	#[tokio::test]
+	async fn test_create_bucket() {
+		let path = Path::new("../storages").join(Uuid::new_v4().to_string());
+		fs::create_dir_all("../storages").await.is_err();
+		let store = Store::create(&path, 1000, None).await.unwrap();
+
+		let mut bucket = store.create_bucket("videos", 600, None).await.unwrap();
+		bucket.add(&KeyValue { key: None, name: "index.js".to_string(), value: Some(b"console.log".to_vec()) }).await.unwrap();
+		store.create_bucket("photos", 300, None).await.unwrap();
+
+		let error = store.create_bucket("videos", 10, None).await.err().unwrap();
+		assert!(matches!(error, SolarError::AlreadyExists(_)));
+
+		let buckets = store.get_buckets().await.unwrap();
+		let names: Vec<&str> = buckets.iter().map(|bucket| bucket.name.as_str()).collect();
+		assert_eq!(names, vec!["photos", "videos"]);
+
+		let info = store.get_bucket("videos").await.unwrap();
+		assert_eq!(info.uuid, bucket.uuid);
+		assert_eq!((info.available_space, info.usage_space), (600, 11));
+		assert!(store.has_bucket("photos").await.unwrap());
+
+		let opened = store.open_bucket("videos").await.unwrap();
+		assert_eq!(opened.path, bucket.path);
+		assert_eq!(info.owner_uuid, None);
+
+		fs::remove_dir_all(&path).await.is_err();
+	}
+
+	#[tokio::test]
+	async fn test_missing_bucket() {
+		let path = Path::new("../storages").join(Uuid::new_v4().to_string());
+		fs::create_dir_all("../storages").await.is_err();
+		let store = Store::create(&path, 1000, None).await.unwrap();
+
+		let bucket = store.create_bucket("videos", 600, None).await.unwrap();
+		store.unregister_bucket(&bucket.uuid).await.unwrap();
+
+		assert!(!store.has_bucket("videos").await.unwrap());
+		assert!(store.get_buckets().await.unwrap().is_empty());
+		let error = store.open_bucket("videos").await.err().unwrap();
+		assert!(matches!(error, SolarError::NoSuchBucket(_)));
+
+		fs::remove_dir_all(&path).await.is_err();
+	}
 }
@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::error::{Result, SolarError};
+
+const USERS_FILE: &str = "users.json";
+
+/// A user of the store. An admin has full access to every bucket, everyone else
+/// only to the buckets they own and to what is granted to them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct User {
+	pub uuid: String,
+	pub name: String,
+	pub is_admin: bool,
+	pub create_at: i64,
+}
+
+pub struct Users;
+
+impl Users {
+	pub async fn load(store_path: impl AsRef<Path>) -> Result<Vec<User>> {
+		let path = store_path.as_ref().join(USERS_FILE);
+		if !path.exists() {
+			return Ok(Vec::new());
+		}
+
+		let file = fs::read(&path).await?;
+		let users: Vec<User> = serde_json::from_slice(&file)?;
+		return Ok(users);
+	}
+
+	pub async fn find(store_path: impl AsRef<Path>, uuid: &str) -> Result<Option<User>> {
+		let users = Users::load(store_path).await?;
+		return Ok(users.into_iter().find(|user| user.uuid == uuid));
+	}
+
+	pub async fn find_by_name(store_path: impl AsRef<Path>, name: &str) -> Result<Option<User>> {
+		let users = Users::load(store_path).await?;
+		return Ok(users.into_iter().find(|user| user.name == name));
+	}
+
+	pub async fn create(store_path: impl AsRef<Path>, name: &str, is_admin: bool) -> Result<User> {
+		if name.is_empty() {
+			return Err(SolarError::InvalidInput("The user name can't be empty!".to_string()));
+		}
+
+		let mut users = Users::load(&store_path).await?;
+		if users.iter().any(|user| user.name == name) {
+			return Err(SolarError::AlreadyExists(format!("User {} already exists", name)));
+		}
+
+		let user = User {
+			uuid: Uuid::new_v4().to_string(),
+			name: name.to_string(),
+			is_admin,
+			create_at: Utc::now().timestamp(),
+		};
+		users.push(user.clone());
+		Users::save(&store_path, &users).await?;
+
+		return Ok(user);
+	}
+
+	/// Removes the user. The objects and buckets they own keep their owner, the
+	/// credentials of the user stop working.
+	pub async fn remove(store_path: impl AsRef<Path>, uuid: &str) -> Result<()> {
+		let mut users = Users::load(&store_path).await?;
+		users.retain(|user| user.uuid != uuid);
+		Users::save(&store_path, &users).await?;
+		return Ok(());
+	}
+
+	async fn save(store_path: impl AsRef<Path>, users: &[User]) -> Result<()> {
+		let path = store_path.as_ref().join(USERS_FILE);
+		let users_json = serde_json::to_string(users)?;
+		fs::write(&path, users_json.as_bytes()).await?;
+		return Ok(());
+	}
+}